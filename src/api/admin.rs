@@ -0,0 +1,121 @@
+use crate::app_error::AppError;
+use crate::app_state::AppState;
+use crate::domain::{EvmNetwork, SubscriptionKey};
+use crate::services::subscription_manager::SessionInfo;
+use alloy::primitives::Address;
+use axum::extract::{Path, State};
+use axum::Json;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+#[derive(Serialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionInfo>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotBalance {
+    pub amount: String,
+    pub block_number: String,
+}
+
+#[derive(Serialize)]
+pub struct SessionSnapshotResponse {
+    pub tokens: Vec<Address>,
+    pub balances: HashMap<Address, SnapshotBalance>,
+}
+
+pub async fn list_sessions(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SessionsResponse>, AppError> {
+    let sessions = state.sub_manager.list_sessions().await;
+    Ok(Json(SessionsResponse { sessions }))
+}
+
+pub async fn get_session(
+    Path((network, owner)): Path<(EvmNetwork, Address)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SessionInfo>, AppError> {
+    let key = SubscriptionKey { network, owner };
+
+    state
+        .sub_manager
+        .session_info(key)
+        .await
+        .map(Json)
+        .ok_or(AppError::NoSession(network, owner))
+}
+
+pub async fn get_session_snapshot(
+    Path((network, owner)): Path<(EvmNetwork, Address)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SessionSnapshotResponse>, AppError> {
+    let key = SubscriptionKey { network, owner };
+
+    let sub = state
+        .sub_manager
+        .get_subscription(key)
+        .await
+        .ok_or(AppError::NoSession(network, owner))?;
+
+    let tokens: Vec<Address> = sub.tokens.read().await.iter().copied().collect();
+    let balances = sub
+        .balances_snapshot
+        .read()
+        .await
+        .iter()
+        .map(|(address, balance)| {
+            (
+                *address,
+                SnapshotBalance {
+                    amount: balance.amount.to_string(),
+                    block_number: balance.block_number.to_string(),
+                },
+            )
+        })
+        .collect();
+
+    Ok(Json(SessionSnapshotResponse { tokens, balances }))
+}
+
+// force the full snapshot update out of schedule
+pub async fn resync_session(
+    Path((network, owner)): Path<(EvmNetwork, Address)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(), AppError> {
+    let key = SubscriptionKey { network, owner };
+
+    let sub = state
+        .sub_manager
+        .get_subscription(key)
+        .await
+        .ok_or(AppError::NoSession(network, owner))?;
+
+    if !sub.watchers_spawned.load(Ordering::SeqCst) {
+        return Err(AppError::BadRequest(
+            "watchers are not spawned for the session".to_string(),
+        ));
+    }
+
+    sub.resync.notify_one();
+    tracing::info!(sub = %key, "session resync requested");
+
+    Ok(())
+}
+
+// cancel watchers of the session and close client streams
+pub async fn terminate_session(
+    Path((network, owner)): Path<(EvmNetwork, Address)>,
+    State(state): State<Arc<AppState>>,
+) -> Result<(), AppError> {
+    let key = SubscriptionKey { network, owner };
+
+    state
+        .sub_manager
+        .terminate(key)
+        .await
+        .map_err(|_| AppError::NoSession(network, owner))
+}
@@ -2,9 +2,10 @@ use crate::app_error::AppError;
 use crate::app_state::AppState;
 use crate::domain::EvmNetwork;
 use crate::evm::erc20::ERC20;
+use crate::middleware::api_auth::ApiKeyContext;
 use alloy::primitives::Address;
 use axum::extract::{Path, State};
-use axum::Json;
+use axum::{Extension, Json};
 use serde::Serialize;
 use std::sync::Arc;
 
@@ -16,7 +17,10 @@ pub struct BalanceResponse {
 pub async fn get_token_balance(
     Path((chain, owner, token)): Path<(EvmNetwork, Address, Address)>,
     State(state): State<Arc<AppState>>,
+    Extension(api_key): Extension<ApiKeyContext>,
 ) -> Result<Json<BalanceResponse>, AppError> {
+    api_key.ensure_chain_allowed(chain)?;
+
     let provider = state
         .providers
         .get(&chain)
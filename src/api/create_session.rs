@@ -3,15 +3,20 @@ use std::sync::Arc;
 use alloy::primitives::Address;
 use axum::{
     extract::{Path, State},
-    Json,
+    Extension, Json,
 };
 use serde::Deserialize;
 
 use crate::{
     app_error::AppError,
     app_state::AppState,
-    config::constants::DEFAULT_MAX_WATCHED_TOKENS_LIMIT,
-    domain::{EvmNetwork, SubscriptionKey},
+    config::constants::{
+        MAX_ALERT_RULES_PER_SESSION, MAX_ERC1155_TOKENS_PER_SESSION, MAX_FINALITY_CONFIRMATIONS,
+        MAX_NFT_COLLECTIONS_PER_SESSION,
+    },
+    domain::{AlertRule, AlertRuleRequest, Erc1155Token, EvmNetwork, Finality, SubscriptionKey},
+    middleware::api_auth::ApiKeyContext,
+    services::webhook::WebhookTarget,
 };
 
 #[derive(Deserialize, Clone, Debug)]
@@ -21,13 +26,43 @@ pub struct CreateSessionRequest {
 
     #[serde(default)]
     custom_tokens: Vec<Address>,
+
+    #[serde(default)]
+    discover_tokens: bool,
+
+    // events are also POSTed to the url, the session runs without SSE clients
+    #[serde(default)]
+    webhook: Option<WebhookTarget>,
+
+    // tokens of the rules are watched as custom tokens
+    #[serde(default)]
+    alerts: Vec<AlertRuleRequest>,
+
+    // balances at this level are sent besides the latest ones
+    #[serde(default)]
+    finality: Option<Finality>,
+
+    // pending_balance events project balances with the owner's mempool transactions
+    #[serde(default)]
+    pending: bool,
+
+    // ERC-721 collections, token IDs of the owner are sent as nft_update events
+    #[serde(default)]
+    nft_collections: Vec<Address>,
+
+    // ERC-1155 (contract, id) pairs, changed balances are sent as erc1155_update events
+    #[serde(default)]
+    erc1155_tokens: Vec<Erc1155Token>,
 }
 
 pub async fn create_session(
     Path((network, owner)): Path<(EvmNetwork, Address)>,
     State(state): State<Arc<AppState>>,
+    Extension(api_key): Extension<ApiKeyContext>,
     Json(body): Json<CreateSessionRequest>,
 ) -> Result<(), AppError> {
+    api_key.ensure_chain_allowed(network)?;
+
     if body.tokens_lists_urls.is_empty() {
         return Err(AppError::BadRequest(
             "tokens_lists_urls should not be empty".into(),
@@ -36,6 +71,40 @@ pub async fn create_session(
 
     let key = SubscriptionKey { network, owner };
 
+    let webhook = body
+        .webhook
+        .map(|webhook| {
+            WebhookTarget::new(
+                webhook.url,
+                webhook.secret,
+                webhook.alerts_only,
+                &state.network_config.webhooks,
+            )
+        })
+        .transpose()?;
+    let alert_rules = AlertRule::from_requests(body.alerts, MAX_ALERT_RULES_PER_SESSION)?;
+    let finality = body
+        .finality
+        .map(|finality| finality.validate(MAX_FINALITY_CONFIRMATIONS))
+        .transpose()?;
+    if body.pending && !state.mempool_dispatchers.contains_key(&network) {
+        return Err(AppError::BadRequest(format!(
+            "pending transactions are not tracked in network {network}"
+        )));
+    }
+
+    if body.nft_collections.len() > MAX_NFT_COLLECTIONS_PER_SESSION {
+        return Err(AppError::BadRequest(format!(
+            "nft_collections should not contain more than {MAX_NFT_COLLECTIONS_PER_SESSION} collections"
+        )));
+    }
+
+    if body.erc1155_tokens.len() > MAX_ERC1155_TOKENS_PER_SESSION {
+        return Err(AppError::BadRequest(format!(
+            "erc1155_tokens should not contain more than {MAX_ERC1155_TOKENS_PER_SESSION} tokens"
+        )));
+    }
+
     let fetcher = Arc::clone(&state.token_list_fetcher);
 
     let mut tokens = fetcher
@@ -43,19 +112,58 @@ pub async fn create_session(
         .await
         .map_err(|err| AppError::BadRequest(err.to_string()))?;
 
-    let weth_address = state.network_config.weth_address(&network);
-    tokens.insert(weth_address);
+    // both sides of configured wraps are watched, the native balance is always fetched
+    let native_address = network.native_token_address();
+    let wrapped = state.network_config.wrapped_token_set(&network);
+
+    let mut custom_tokens = body.custom_tokens;
+    custom_tokens.extend(
+        alert_rules
+            .iter()
+            .map(|rule| rule.token)
+            .filter(|token| *token != native_address),
+    );
 
     let mut combined = tokens.clone();
-    combined.extend(body.custom_tokens.clone());
+    combined.extend(custom_tokens.clone());
 
-    if combined.len() > DEFAULT_MAX_WATCHED_TOKENS_LIMIT {
+    let max_tokens = api_key.max_tokens_per_session(state.network_config.max_watched_tokens_limit);
+    if combined.difference(&wrapped).count() > max_tokens {
         return Err(AppError::TokenLimitExceeded);
     }
 
-    tokens.extend(body.custom_tokens);
+    tokens.extend(custom_tokens);
+    tokens.extend(wrapped);
 
-    let _ = state.sub_manager.create_or_update(key, tokens).await;
+    let subscription = state
+        .sub_manager
+        .create_or_update(key, tokens, body.discover_tokens, api_key.session_creator())
+        .await?;
+    if let Some(webhook) = webhook {
+        state.sub_manager.set_webhook(key, &subscription, webhook);
+    }
+    if !alert_rules.is_empty() {
+        subscription.set_alert_rules(alert_rules);
+    }
+    if let Some(finality) = finality {
+        subscription.set_finality(finality).await;
+    }
+    if body.pending {
+        subscription.set_pending_enabled(true);
+    }
+    if !body.nft_collections.is_empty() {
+        subscription
+            .add_nft_collections(&body.nft_collections)
+            .await;
+    }
+    if !body.erc1155_tokens.is_empty() {
+        subscription.add_erc1155_tokens(&body.erc1155_tokens).await;
+    }
+    state.sub_manager.share_session(key).await;
+
+    if subscription.has_webhook() {
+        state.ensure_watchers(key, &subscription).await?;
+    }
 
     tracing::warn!(
         "session for wallet:network {}:{} was created, watched tokens count is {}",
@@ -1,23 +1,46 @@
 use crate::api::errors::StreamError;
 use crate::app_state::AppState;
-use crate::domain::{BalanceEvent, EvmNetwork, SubscriptionKey};
+use crate::domain::{BalanceEvent, EvmNetwork, Finality, ShareBalance, SubscriptionKey, Valuation};
+use crate::middleware::api_auth::ApiKeyContext;
 use crate::services::cleanup_stream;
-use crate::services::watcher::{Watcher, WatcherContext};
+use crate::services::errors::SubscriptionError;
+use crate::services::subscription_manager::Subscription;
 use alloy::primitives::Address;
 use axum::{
     extract::{Path, State},
     response::sse::{Event, Sse},
+    Extension,
 };
 use futures::{Stream, StreamExt};
 use metrics::counter;
 use serde::Serialize;
-use std::sync::atomic::Ordering;
 use std::{collections::HashMap, convert::Infallible, sync::Arc};
 use tokio_stream::wrappers::BroadcastStream;
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct BalancesResponse {
     pub balances: HashMap<Address, String>,
+    pub finality: Finality,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub valuation: Option<Valuation>,
+}
+
+#[derive(Serialize)]
+struct TokensRemovedSseEvent {
+    tokens: Vec<Address>,
+}
+
+#[derive(Serialize)]
+struct SharesSseEvent {
+    balances: HashMap<Address, ShareBalance>,
+}
+
+#[derive(Serialize)]
+struct SourceStatusSseEvent {
+    message: String,
 }
 
 #[derive(Serialize)]
@@ -29,77 +52,57 @@ struct ErrorBalanceSseEvent {
 pub async fn create_sse_session(
     Path((network, owner)): Path<(EvmNetwork, Address)>,
     State(state): State<Arc<AppState>>,
+    Extension(api_key): Extension<ApiKeyContext>,
 ) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, StreamError> {
+    api_key
+        .ensure_chain_allowed(network)
+        .map_err(|err| StreamError {
+            code: 403,
+            message: err.to_string(),
+        })?;
+
     let sub_key = SubscriptionKey { owner, network };
     tracing::info!(
         sub = %sub_key,
         "new sse connection request accepted"
     );
 
-    let provider = match state.providers.get(&network) {
-        Some(provider) => provider.clone(),
-        None => {
-            return Err(StreamError {
-                code: 404,
-                message: format!("No provider for network {}", network),
-            })
-        }
-    };
+    let ctx = state.watcher_context(sub_key).map_err(|err| StreamError {
+        code: 404,
+        message: err.to_string(),
+    })?;
 
-    let ws_provider = match state.ws_providers.get(&network) {
-        None => {
-            return Err(StreamError {
-                code: 404,
-                message: format!("No ws provider for network {}", network),
-            });
-        }
-        Some(ws_provider) => ws_provider.clone(),
-    };
-
-    let multicall3 = state.network_config.multicall_address();
-    if multicall3.is_empty() {
-        return Err(StreamError {
-            code: 404,
-            message: format!("No multicall3 for network {}", network),
-        });
-    }
+    let (rx, subscription) = state
+        .sub_manager
+        .subscribe(sub_key, api_key.key_name())
+        .await
+        .map_err(|e| StreamError {
+            code: match e {
+                SubscriptionError::ForeignSession => 403,
+                _ => 500,
+            },
+            message: e.to_string(),
+        })?;
 
-    let (rx, subscription) =
-        state
-            .sub_manager
-            .subscribe(sub_key)
-            .await
-            .map_err(|e| StreamError {
-                code: 500,
-                message: e.to_string(),
-            })?;
+    let should_spawn_watchers = subscription.claim_watchers();
 
-    let weth9_address = state.network_config.weth_address(&network);
+    // the leader on another replica could update the shared snapshot
+    if should_spawn_watchers {
+        state.sub_manager.sync_from_cluster(sub_key).await;
+    }
 
-    let should_spawn_watchers = subscription
-        .watchers_spawned
-        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
-        .is_ok();
+    // restored sessions have the snapshot before watchers are spawned
+    let has_snapshot = !subscription.balances_snapshot.read().await.is_empty();
 
     if should_spawn_watchers {
-        let ctx = WatcherContext {
-            provider,
-            owner,
-            network,
-            multicall3: *multicall3,
-            ws_provider,
-            weth9_address,
-        };
-
         tracing::info!(
             sub = %sub_key,
-            "create first sse subscription and spawn watchers"
+            "create first sse subscription"
         );
+        state.run_watchers(sub_key, &subscription, ctx).await;
+    }
 
-        Watcher::new(ctx, Arc::clone(&subscription))
-            .spawn_watchers(state.network_config.snapshot_interval)
-            .await;
-    } else {
+    if !should_spawn_watchers || has_snapshot {
         let balance_snapshot = subscription.balances_snapshot.read().await;
 
         let event = if balance_snapshot.is_empty() {
@@ -117,7 +120,10 @@ pub async fn create_sse_session(
                 .into_iter()
                 .map(|(address, balance)| (address, balance.amount.to_string()))
                 .collect();
-            BalanceEvent::BalanceUpdate(balance_snapshot)
+            BalanceEvent::Snapshot {
+                balances: balance_snapshot,
+                valuation: subscription.valuation(),
+            }
         };
 
         tracing::info!(
@@ -132,35 +138,65 @@ pub async fn create_sse_session(
                 "error when send balance_snapshot update"
             );
         });
+
+        // balances at the finality level of the session follow the latest ones
+        if let Some(event) = confirmed_snapshot_event(&subscription).await {
+            let _ = subscription.sender.send(event);
+        }
+
+        let share_balances = subscription.share_balances();
+        if !share_balances.is_empty() {
+            let _ = subscription
+                .sender
+                .send(BalanceEvent::SharesUpdate(share_balances));
+        }
+
+        // every held token ID is listed as added
+        if let Some(nft_snapshot) = subscription.nft_snapshot() {
+            let _ = subscription
+                .sender
+                .send(BalanceEvent::NftUpdate(nft_snapshot));
+        }
+
+        if let Some(erc1155_snapshot) = subscription.erc1155_snapshot() {
+            let _ = subscription
+                .sender
+                .send(BalanceEvent::Erc1155Update(erc1155_snapshot));
+        }
     }
 
     let manager_for_cleanup = Arc::clone(&state.sub_manager);
+    // stream is closed when the session is terminated
+    let cancelled = subscription.cancel_token.clone().cancelled_owned();
 
-    let sse_stream = BroadcastStream::new(rx).filter_map(|result| async move {
-        match result {
-            Ok(event) => {
-                let sse_event = match balance_event_to_sse(event) {
-                    Ok(sse_event) => Some(Ok(sse_event)),
+    let sse_stream =
+        BroadcastStream::new(rx)
+            .take_until(cancelled)
+            .filter_map(|result| async move {
+                match result {
+                    Ok(event) => {
+                        let sse_event = match balance_event_to_sse(event) {
+                            Ok(sse_event) => Some(Ok(sse_event)),
+                            Err(err) => {
+                                tracing::error!(
+                                    error = %err,
+                                    "error when convert balance event to sse event",
+                                );
+                                None
+                            }
+                        };
+                        sse_event
+                    }
                     Err(err) => {
+                        counter!("broadcast_lagged_total").increment(1);
                         tracing::error!(
                             error = %err,
-                            "error when convert balance event to sse event",
+                            "broadcast stream error",
                         );
                         None
                     }
-                };
-                sse_event
-            }
-            Err(err) => {
-                counter!("broadcast_lagged_total").increment(1);
-                tracing::error!(
-                    error = %err,
-                    "broadcast stream error",
-                );
-                None
-            }
-        }
-    });
+                }
+            });
 
     let cleanup_stream =
         cleanup_stream::CleanupStream::new(sse_stream, manager_for_cleanup, sub_key);
@@ -168,15 +204,95 @@ pub async fn create_sse_session(
     Ok(Sse::new(cleanup_stream))
 }
 
+async fn confirmed_snapshot_event(subscription: &Subscription) -> Option<BalanceEvent> {
+    let finality = subscription.finality();
+    let confirmed_snapshot = subscription.confirmed_snapshot.read().await;
+    if finality.is_latest() || confirmed_snapshot.is_empty() {
+        return None;
+    }
+
+    let block_number = confirmed_snapshot
+        .values()
+        .map(|balance| balance.block_number)
+        .max()
+        .unwrap_or_default();
+    let balances = confirmed_snapshot
+        .iter()
+        .map(|(address, balance)| (*address, balance.amount.to_string()))
+        .collect();
+
+    Some(BalanceEvent::ConfirmedUpdate {
+        finality,
+        balances,
+        block_number: block_number.to_string(),
+    })
+}
+
 fn balance_event_to_sse(event: BalanceEvent) -> Result<Event, axum::Error> {
     match event {
         BalanceEvent::BalanceUpdate(balances_map) => Event::default()
             .event("balance_update")
             .json_data(BalancesResponse {
                 balances: balances_map,
+                finality: Finality::Latest,
+                block_number: None,
+                valuation: None,
+            }),
+        BalanceEvent::Snapshot {
+            balances,
+            valuation,
+        } => Event::default()
+            .event("balance_update")
+            .json_data(BalancesResponse {
+                balances,
+                finality: Finality::Latest,
+                block_number: None,
+                valuation,
+            }),
+        BalanceEvent::ConfirmedUpdate {
+            finality,
+            balances,
+            block_number,
+        } => Event::default()
+            .event("balance_update")
+            .json_data(BalancesResponse {
+                balances,
+                finality,
+                block_number: Some(block_number),
+                valuation: None,
             }),
+        BalanceEvent::TokensRemoved(tokens) => Event::default()
+            .event("tokens_removed")
+            .json_data(TokensRemovedSseEvent { tokens }),
         BalanceEvent::Error { code, message } => Event::default()
             .event("error")
             .json_data(ErrorBalanceSseEvent { code, message }),
+        BalanceEvent::Degraded { message } => Event::default()
+            .event("degraded")
+            .json_data(SourceStatusSseEvent { message }),
+        BalanceEvent::Recovered { message } => Event::default()
+            .event("recovered")
+            .json_data(SourceStatusSseEvent { message }),
+        BalanceEvent::AlertTriggered(alert) => {
+            Event::default().event("alert_triggered").json_data(alert)
+        }
+        BalanceEvent::AlertResolved(alert) => {
+            Event::default().event("alert_resolved").json_data(alert)
+        }
+        BalanceEvent::PriceUpdate(valuation) => {
+            Event::default().event("price_update").json_data(valuation)
+        }
+        BalanceEvent::SharesUpdate(share_balances) => Event::default()
+            .event("shares_update")
+            .json_data(SharesSseEvent {
+                balances: share_balances,
+            }),
+        BalanceEvent::NftUpdate(update) => Event::default().event("nft_update").json_data(update),
+        BalanceEvent::Erc1155Update(update) => {
+            Event::default().event("erc1155_update").json_data(update)
+        }
+        BalanceEvent::PendingBalance(pending) => {
+            Event::default().event("pending_balance").json_data(pending)
+        }
     }
 }
@@ -1,6 +1,11 @@
+pub mod admin;
 pub mod balance;
 pub mod create_session;
 pub mod create_sse_session;
+pub mod remove_session_tokens;
+pub mod remove_session_webhook;
+pub mod replace_session_tokens;
+pub mod session_alerts;
 pub mod update_session;
 
 mod errors;
@@ -0,0 +1,88 @@
+use std::{collections::HashSet, sync::Arc};
+
+use alloy::primitives::Address;
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    app_error::AppError,
+    app_state::AppState,
+    domain::{EvmNetwork, SubscriptionKey},
+    middleware::api_auth::ApiKeyContext,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveSessionTokensRequest {
+    #[serde(default)]
+    tokens_lists_urls: Vec<String>,
+
+    #[serde(default)]
+    custom_tokens: Vec<Address>,
+}
+
+// stop watching tokens of the lists and custom tokens
+// every token of a removed list is dropped, even if it is also present in another watched list,
+// except wrapped tokens
+pub async fn remove_session_tokens(
+    Path((network, owner)): Path<(EvmNetwork, Address)>,
+    State(state): State<Arc<AppState>>,
+    Extension(api_key): Extension<ApiKeyContext>,
+    Json(body): Json<RemoveSessionTokensRequest>,
+) -> Result<(), AppError> {
+    api_key.ensure_chain_allowed(network)?;
+
+    if body.custom_tokens.is_empty() && body.tokens_lists_urls.is_empty() {
+        return Err(AppError::BadRequest(
+            "tokens_lists_urls && custom_tokens are empty".to_string(),
+        ));
+    }
+
+    let key = SubscriptionKey { network, owner };
+
+    let sub = state
+        .sub_manager
+        .get_owned_subscription(key, api_key.key_name())
+        .await?
+        .ok_or(AppError::NoSession(network, owner))?;
+
+    let mut tokens = state
+        .token_list_fetcher
+        .get_tokens(&body.tokens_lists_urls, network)
+        .await
+        .map_err(|err| AppError::BadRequest(err.to_string()))?;
+    tokens.extend(body.custom_tokens);
+    // wrapped tokens are watched by every session
+    let wrapped = state.network_config.wrapped_token_set(&network);
+    tokens.retain(|token| !wrapped.contains(token));
+
+    // alerts would silently stop evaluating without balances of their tokens
+    let remaining: HashSet<Address> = sub
+        .tokens
+        .read()
+        .await
+        .difference(&tokens)
+        .copied()
+        .collect();
+    let conflicts = sub.alert_rules_outside(&remaining, network.native_token_address());
+    if !conflicts.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "tokens of alert rules {} would be removed, replace the alerts first",
+            conflicts.join(", ")
+        )));
+    }
+
+    let removed = sub.remove_tokens(&tokens).await;
+    state.sub_manager.share_session(key).await;
+
+    tracing::info!(
+        removed_len = removed.len(),
+        sub = %key,
+        "tokens were removed from session",
+    );
+
+    Ok(())
+}
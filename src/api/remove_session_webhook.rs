@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use alloy::primitives::Address;
+use axum::{
+    extract::{Path, State},
+    Extension,
+};
+
+use crate::{
+    app_error::AppError,
+    app_state::AppState,
+    domain::{EvmNetwork, SubscriptionKey},
+    middleware::api_auth::ApiKeyContext,
+};
+
+// unregister the webhook of the session, without SSE clients the session expires as usual
+pub async fn remove_session_webhook(
+    Path((network, owner)): Path<(EvmNetwork, Address)>,
+    State(state): State<Arc<AppState>>,
+    Extension(api_key): Extension<ApiKeyContext>,
+) -> Result<(), AppError> {
+    api_key.ensure_chain_allowed(network)?;
+
+    let key = SubscriptionKey { network, owner };
+
+    let sub = state
+        .sub_manager
+        .get_owned_subscription(key, api_key.key_name())
+        .await?
+        .ok_or(AppError::NoSession(network, owner))?;
+
+    if state.sub_manager.remove_webhook(key, &sub) {
+        state.sub_manager.share_session(key).await;
+    }
+
+    Ok(())
+}
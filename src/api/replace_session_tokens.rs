@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use alloy::primitives::Address;
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use metrics::counter;
+use serde::Deserialize;
+
+use crate::{
+    app_error::AppError,
+    app_state::AppState,
+    domain::{EvmNetwork, SubscriptionKey},
+    middleware::api_auth::ApiKeyContext,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceSessionTokensRequest {
+    #[serde(default)]
+    tokens_lists_urls: Vec<String>,
+
+    #[serde(default)]
+    custom_tokens: Vec<Address>,
+}
+
+// replace the whole set of watched tokens
+pub async fn replace_session_tokens(
+    Path((network, owner)): Path<(EvmNetwork, Address)>,
+    State(state): State<Arc<AppState>>,
+    Extension(api_key): Extension<ApiKeyContext>,
+    Json(body): Json<ReplaceSessionTokensRequest>,
+) -> Result<(), AppError> {
+    api_key.ensure_chain_allowed(network)?;
+
+    if body.custom_tokens.is_empty() && body.tokens_lists_urls.is_empty() {
+        return Err(AppError::BadRequest(
+            "tokens_lists_urls && custom_tokens are empty".to_string(),
+        ));
+    }
+
+    let key = SubscriptionKey { network, owner };
+
+    let sub = state
+        .sub_manager
+        .get_owned_subscription(key, api_key.key_name())
+        .await?
+        .ok_or(AppError::NoSession(network, owner))?;
+
+    let mut tokens = state
+        .token_list_fetcher
+        .get_tokens(&body.tokens_lists_urls, network)
+        .await
+        .map_err(|err| AppError::BadRequest(err.to_string()))?;
+    tokens.extend(body.custom_tokens);
+
+    // wrapped tokens are always watched as for a new session and don't count against the limit
+    let wrapped = state.network_config.wrapped_token_set(&network);
+    let user_tokens_len = tokens.difference(&wrapped).count();
+    tokens.extend(wrapped);
+
+    if user_tokens_len
+        > api_key.max_tokens_per_session(state.network_config.max_watched_tokens_limit)
+    {
+        counter!("tokens_limit_exceeded_total").increment(1);
+        tracing::error!(
+            tokens_len = user_tokens_len,
+            "limit of watched tokens was exceeded",
+        );
+        return Err(AppError::TokenLimitExceeded);
+    }
+
+    // alerts would silently stop evaluating without balances of their tokens
+    let conflicts = sub.alert_rules_outside(&tokens, network.native_token_address());
+    if !conflicts.is_empty() {
+        return Err(AppError::BadRequest(format!(
+            "tokens of alert rules {} are not in the new set, replace the alerts first",
+            conflicts.join(", ")
+        )));
+    }
+
+    let prev_count = sub.tokens.read().await.len();
+    state
+        .sub_manager
+        .ensure_tokens_headroom(tokens.len().saturating_sub(prev_count))
+        .await?;
+
+    let tokens_len = tokens.len();
+    let removed = sub.replace_tokens(tokens).await;
+    state.sub_manager.share_session(key).await;
+
+    tracing::info!(
+        current_tokens_len = tokens_len,
+        removed_len = removed.len(),
+        sub = %key,
+        "session tokens were replaced",
+    );
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use alloy::primitives::Address;
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_error::AppError,
+    app_state::AppState,
+    config::constants::MAX_ALERT_RULES_PER_SESSION,
+    domain::{AlertComparator, AlertRule, AlertRuleRequest, EvmNetwork, SubscriptionKey},
+    middleware::api_auth::ApiKeyContext,
+};
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceSessionAlertsRequest {
+    alerts: Vec<AlertRuleRequest>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRuleInfo {
+    id: String,
+    token: Address,
+    comparator: AlertComparator,
+    threshold: String,
+    hysteresis: String,
+    triggered: bool,
+}
+
+#[derive(Serialize)]
+pub struct SessionAlertsResponse {
+    alerts: Vec<AlertRuleInfo>,
+}
+
+impl From<Vec<AlertRule>> for SessionAlertsResponse {
+    fn from(rules: Vec<AlertRule>) -> Self {
+        let alerts = rules
+            .into_iter()
+            .map(|rule| AlertRuleInfo {
+                id: rule.id,
+                token: rule.token,
+                comparator: rule.comparator,
+                threshold: rule.threshold.to_string(),
+                hysteresis: rule.hysteresis.to_string(),
+                triggered: rule.triggered,
+            })
+            .collect();
+
+        Self { alerts }
+    }
+}
+
+pub async fn get_session_alerts(
+    Path((network, owner)): Path<(EvmNetwork, Address)>,
+    State(state): State<Arc<AppState>>,
+    Extension(api_key): Extension<ApiKeyContext>,
+) -> Result<Json<SessionAlertsResponse>, AppError> {
+    api_key.ensure_chain_allowed(network)?;
+
+    let key = SubscriptionKey { network, owner };
+    let sub = state
+        .sub_manager
+        .get_owned_subscription(key, api_key.key_name())
+        .await?
+        .ok_or(AppError::NoSession(network, owner))?;
+
+    Ok(Json(sub.alert_rules().into()))
+}
+
+// replace all alert rules of the session, tokens of the rules are added to the watched set
+pub async fn replace_session_alerts(
+    Path((network, owner)): Path<(EvmNetwork, Address)>,
+    State(state): State<Arc<AppState>>,
+    Extension(api_key): Extension<ApiKeyContext>,
+    Json(body): Json<ReplaceSessionAlertsRequest>,
+) -> Result<Json<SessionAlertsResponse>, AppError> {
+    api_key.ensure_chain_allowed(network)?;
+
+    let rules = AlertRule::from_requests(body.alerts, MAX_ALERT_RULES_PER_SESSION)?;
+
+    let key = SubscriptionKey { network, owner };
+    let sub = state
+        .sub_manager
+        .get_owned_subscription(key, api_key.key_name())
+        .await?
+        .ok_or(AppError::NoSession(network, owner))?;
+
+    let native_address = network.native_token_address();
+    let missing: HashSet<Address> = {
+        let watched_tokens = sub.tokens.read().await;
+        rules
+            .iter()
+            .map(|rule| rule.token)
+            .filter(|token| *token != native_address && !watched_tokens.contains(token))
+            .collect()
+    };
+
+    if !missing.is_empty() {
+        state
+            .sub_manager
+            .ensure_tokens_headroom(missing.len())
+            .await?;
+
+        // wrapped tokens are watched by every session and don't count against the limit
+        let wrapped = state.network_config.wrapped_token_set(&network);
+        let mut watched_tokens = sub.tokens.write().await;
+        let total = watched_tokens.difference(&wrapped).count() + missing.len();
+        if total > api_key.max_tokens_per_session(state.network_config.max_watched_tokens_limit) {
+            counter!("tokens_limit_exceeded_total").increment(1);
+            return Err(AppError::TokenLimitExceeded);
+        }
+
+        watched_tokens.extend(missing.iter().copied());
+        drop(watched_tokens);
+
+        sub.notify_tokens_added(&missing.into_iter().collect::<Vec<_>>())
+            .await;
+    }
+
+    let rules_len = rules.len();
+    sub.set_alert_rules(rules);
+    state.sub_manager.share_session(key).await;
+
+    tracing::info!(
+        alerts_len = rules_len,
+        sub = %key,
+        "session alerts were replaced",
+    );
+
+    Ok(Json(sub.alert_rules().into()))
+}
@@ -1,9 +1,9 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use alloy::primitives::Address;
 use axum::{
     extract::{Path, State},
-    Json,
+    Extension, Json,
 };
 use metrics::counter;
 use serde::Deserialize;
@@ -11,7 +11,12 @@ use serde::Deserialize;
 use crate::{
     app_error::AppError,
     app_state::AppState,
-    domain::{EvmNetwork, SubscriptionKey},
+    config::constants::{
+        MAX_ERC1155_TOKENS_PER_SESSION, MAX_FINALITY_CONFIRMATIONS, MAX_NFT_COLLECTIONS_PER_SESSION,
+    },
+    domain::{Erc1155Token, EvmNetwork, Finality, SubscriptionKey},
+    middleware::api_auth::ApiKeyContext,
+    services::webhook::WebhookTarget,
 };
 
 #[derive(Deserialize, Clone, Debug)]
@@ -22,27 +27,111 @@ pub struct UpdateSessionRequest {
 
     #[serde(default)]
     custom_tokens: Vec<Address>,
+
+    // registers or replaces the webhook of the session
+    #[serde(default)]
+    webhook: Option<WebhookTarget>,
+
+    // changes the confirmation level, balances of the previous level are dropped
+    #[serde(default)]
+    finality: Option<Finality>,
+
+    // enables or disables pending transaction projections
+    #[serde(default)]
+    pending: Option<bool>,
+
+    // ERC-721 collections added to the watched ones
+    #[serde(default)]
+    nft_collections: Vec<Address>,
+
+    // ERC-1155 (contract, id) pairs added to the watched ones
+    #[serde(default)]
+    erc1155_tokens: Vec<Erc1155Token>,
 }
 
 pub async fn update_session(
     Path((network, owner)): Path<(EvmNetwork, Address)>,
     State(state): State<Arc<AppState>>,
+    Extension(api_key): Extension<ApiKeyContext>,
     Json(body): Json<UpdateSessionRequest>,
 ) -> Result<(), AppError> {
-    if body.custom_tokens.is_empty() && body.tokens_lists_urls.is_empty() {
+    api_key.ensure_chain_allowed(network)?;
+
+    if body.custom_tokens.is_empty()
+        && body.tokens_lists_urls.is_empty()
+        && body.webhook.is_none()
+        && body.finality.is_none()
+        && body.pending.is_none()
+        && body.nft_collections.is_empty()
+        && body.erc1155_tokens.is_empty()
+    {
         return Err(AppError::BadRequest(
-            "tokens_lists_urls && custom_tokens are empty".to_string(),
+            "tokens_lists_urls && custom_tokens && webhook && finality && pending && nft_collections && erc1155_tokens are empty"
+                .to_string(),
         ));
     }
 
     let key = SubscriptionKey { network, owner };
 
+    let webhook = body
+        .webhook
+        .map(|webhook| {
+            WebhookTarget::new(
+                webhook.url,
+                webhook.secret,
+                webhook.alerts_only,
+                &state.network_config.webhooks,
+            )
+        })
+        .transpose()?;
+    let finality = body
+        .finality
+        .map(|finality| finality.validate(MAX_FINALITY_CONFIRMATIONS))
+        .transpose()?;
+    if body.pending == Some(true) && !state.mempool_dispatchers.contains_key(&network) {
+        return Err(AppError::BadRequest(format!(
+            "pending transactions are not tracked in network {network}"
+        )));
+    }
+
     let sub = state
         .sub_manager
-        .get_subscription(key)
-        .await
+        .get_owned_subscription(key, api_key.key_name())
+        .await?
         .ok_or(AppError::NoSession(network, owner))?;
 
+    let nft_collections_count = {
+        let nft_collections = sub.nft_collections.read().await;
+        nft_collections.len()
+            + body
+                .nft_collections
+                .iter()
+                .filter(|collection| !nft_collections.contains(*collection))
+                .collect::<HashSet<_>>()
+                .len()
+    };
+    if nft_collections_count > MAX_NFT_COLLECTIONS_PER_SESSION {
+        return Err(AppError::BadRequest(format!(
+            "nft_collections should not contain more than {MAX_NFT_COLLECTIONS_PER_SESSION} collections"
+        )));
+    }
+
+    let erc1155_tokens_count = {
+        let erc1155_tokens = sub.erc1155_tokens.read().await;
+        erc1155_tokens.len()
+            + body
+                .erc1155_tokens
+                .iter()
+                .filter(|token| !erc1155_tokens.contains(*token))
+                .collect::<HashSet<_>>()
+                .len()
+    };
+    if erc1155_tokens_count > MAX_ERC1155_TOKENS_PER_SESSION {
+        return Err(AppError::BadRequest(format!(
+            "erc1155_tokens should not contain more than {MAX_ERC1155_TOKENS_PER_SESSION} tokens"
+        )));
+    }
+
     let token_list_fetcher = Arc::clone(&state.token_list_fetcher);
 
     let mut tokens = token_list_fetcher
@@ -51,8 +140,19 @@ pub async fn update_session(
         .map_err(|err| AppError::BadRequest(err.to_string()))?;
     tokens.extend(body.custom_tokens);
 
+    let new_unique = {
+        let watched_tokens = sub.tokens.read().await;
+        tokens
+            .iter()
+            .filter(|t| !watched_tokens.contains(*t))
+            .count()
+    };
+    state.sub_manager.ensure_tokens_headroom(new_unique).await?;
+
+    // wrapped tokens are watched by every session and don't count against the limit
+    let wrapped = state.network_config.wrapped_token_set(&network);
     let mut watched_tokens = sub.tokens.write().await;
-    let prev_count = watched_tokens.len();
+    let prev_count = watched_tokens.difference(&wrapped).count();
 
     // count how many new unique tokens would be added
     let new_unique = tokens
@@ -61,7 +161,8 @@ pub async fn update_session(
         .count();
 
     let total_unique = prev_count + new_unique;
-    if total_unique > state.network_config.max_watched_tokens_limit {
+    if total_unique > api_key.max_tokens_per_session(state.network_config.max_watched_tokens_limit)
+    {
         counter!("tokens_limit_exceeded_total").increment(1);
         tracing::error!(
             tokens_len = total_unique,
@@ -71,8 +172,27 @@ pub async fn update_session(
         return Err(AppError::TokenLimitExceeded);
     }
 
-    watched_tokens.extend(tokens);
+    let added: Vec<Address> = tokens
+        .into_iter()
+        .filter(|token| watched_tokens.insert(*token))
+        .collect();
     let new_count = watched_tokens.len();
+    drop(watched_tokens);
+
+    sub.notify_tokens_added(&added).await;
+    if let Some(finality) = finality {
+        sub.set_finality(finality).await;
+    }
+    if let Some(pending) = body.pending {
+        sub.set_pending_enabled(pending);
+    }
+    sub.add_nft_collections(&body.nft_collections).await;
+    sub.add_erc1155_tokens(&body.erc1155_tokens).await;
+    if let Some(webhook) = webhook {
+        state.sub_manager.set_webhook(key, &sub, webhook);
+        state.ensure_watchers(key, &sub).await?;
+    }
+    state.sub_manager.share_session(key).await;
 
     tracing::info!(
         tokens_len_before = prev_count,
@@ -1,9 +1,15 @@
 use alloy::primitives::Address;
-use axum::{http::StatusCode, response::IntoResponse, Json};
+use axum::{
+    http::{header::RETRY_AFTER, StatusCode},
+    response::IntoResponse,
+    Json,
+};
 use serde::Serialize;
 use thiserror::Error;
 
+use crate::domain::errors::{AlertError, FinalityError};
 use crate::domain::EvmNetwork;
+use crate::services::errors::{SubscriptionError, WatcherSetupError, WebhookError};
 
 #[derive(Error, Debug)]
 pub enum AppError {
@@ -21,6 +27,62 @@ pub enum AppError {
 
     #[error("Token limit exceeded")]
     TokenLimitExceeded,
+
+    #[error("Unauthorized")]
+    Unauthorized,
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("Too many requests, retry after {0} seconds")]
+    RateLimited(u64),
+
+    #[error("Service capacity exceeded: {0}")]
+    CapacityExceeded(String),
+}
+
+impl From<SubscriptionError> for AppError {
+    fn from(err: SubscriptionError) -> Self {
+        match err {
+            SubscriptionError::KeySessionsLimitExceeded
+            | SubscriptionError::OwnerSessionsLimitExceeded => {
+                AppError::QuotaExceeded(err.to_string())
+            }
+            SubscriptionError::SessionsLimitExceeded
+            | SubscriptionError::TotalTokensLimitExceeded => {
+                AppError::CapacityExceeded(err.to_string())
+            }
+            SubscriptionError::ForeignSession => AppError::Forbidden(err.to_string()),
+            err => AppError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl From<AlertError> for AppError {
+    fn from(err: AlertError) -> Self {
+        AppError::BadRequest(err.to_string())
+    }
+}
+
+impl From<FinalityError> for AppError {
+    fn from(err: FinalityError) -> Self {
+        AppError::BadRequest(err.to_string())
+    }
+}
+
+impl From<WebhookError> for AppError {
+    fn from(err: WebhookError) -> Self {
+        AppError::BadRequest(err.to_string())
+    }
+}
+
+impl From<WatcherSetupError> for AppError {
+    fn from(err: WatcherSetupError) -> Self {
+        AppError::Internal(err.to_string())
+    }
 }
 
 #[derive(Serialize)]
@@ -37,15 +99,22 @@ impl IntoResponse for AppError {
             AppError::ProviderIsNotDefined(_) => (StatusCode::NOT_FOUND, &self.to_string()),
             AppError::NoSession(_, _) => (StatusCode::NOT_FOUND, &self.to_string()),
             AppError::TokenLimitExceeded => (StatusCode::BAD_REQUEST, &self.to_string()),
+            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, &self.to_string()),
+            AppError::Forbidden(_) => (StatusCode::FORBIDDEN, &self.to_string()),
+            AppError::QuotaExceeded(_) => (StatusCode::TOO_MANY_REQUESTS, &self.to_string()),
+            AppError::RateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, &self.to_string()),
+            AppError::CapacityExceeded(_) => (StatusCode::SERVICE_UNAVAILABLE, &self.to_string()),
         };
 
-        (
-            status,
-            Json(ErrorBody {
-                code: status.as_u16(),
-                message: message.clone(),
-            }),
-        )
-            .into_response()
+        let body = Json(ErrorBody {
+            code: status.as_u16(),
+            message: message.clone(),
+        });
+
+        if let AppError::RateLimited(retry_after_secs) = self {
+            return (status, [(RETRY_AFTER, retry_after_secs.to_string())], body).into_response();
+        }
+
+        (status, body).into_response()
     }
 }
@@ -1,7 +1,21 @@
+use crate::config::cluster_config::ClusterBackendKind;
 use crate::config::network_config::NetworkConfig;
-use crate::domain::EvmNetwork;
-use crate::services::subscription_manager::SubscriptionManager;
+use crate::domain::{EvmNetwork, SubscriptionKey};
+use crate::services::api_keys::ApiKeyRegistry;
+use crate::services::balance_strategies::BalanceStrategies;
+use crate::services::cluster::Cluster;
+use crate::services::errors::{ClusterError, WatcherSetupError};
+use crate::services::log_dispatcher::LogDispatcher;
+use crate::services::mempool_dispatcher::MempoolDispatcher;
+use crate::services::price_oracle::PriceOracle;
+use crate::services::rate_limiter::RateLimiter;
+use crate::services::redis_backend::RedisBackend;
+use crate::services::session_store::SessionStore;
+use crate::services::subscription_manager::{Subscription, SubscriptionManager};
 use crate::services::token_list_fetcher::TokenListFetcher;
+use crate::services::trace_dispatcher::TraceDispatcher;
+use crate::services::watcher::{Watcher, WatcherContext};
+use crate::services::webhook::WebhookClient;
 use alloy::network::Ethereum;
 use alloy::providers::{DynProvider, Provider, ProviderBuilder, WsConnect};
 use std::collections::HashMap;
@@ -11,30 +25,279 @@ use std::sync::Arc;
 pub struct AppState {
     pub network_config: Arc<NetworkConfig>,
     pub providers: Arc<HashMap<EvmNetwork, DynProvider<Ethereum>>>,
-    pub ws_providers: Arc<HashMap<EvmNetwork, DynProvider>>,
+    pub log_dispatchers: Arc<HashMap<EvmNetwork, Arc<LogDispatcher>>>,
+    pub mempool_dispatchers: Arc<HashMap<EvmNetwork, Arc<MempoolDispatcher>>>,
+    pub trace_dispatchers: Arc<HashMap<EvmNetwork, Arc<TraceDispatcher>>>,
     pub sub_manager: Arc<SubscriptionManager>,
     pub token_list_fetcher: Arc<TokenListFetcher>,
+    pub api_keys: Arc<ApiKeyRegistry>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub session_store: Option<Arc<SessionStore>>,
+    pub price_oracle: Option<Arc<PriceOracle>>,
+    pub balance_strategies: Option<Arc<BalanceStrategies>>,
 }
 
 impl AppState {
-    pub async fn build(network_config: NetworkConfig) -> Arc<Self> {
+    pub async fn build(
+        network_config: NetworkConfig,
+        api_keys: ApiKeyRegistry,
+        price_oracle: Option<PriceOracle>,
+        balance_strategies: Option<BalanceStrategies>,
+        webhooks: WebhookClient,
+    ) -> Result<Arc<Self>, ClusterError> {
         let providers = Self::build_rpc_roviders_map(&network_config).await;
         let ws_providers = Self::build_ws_rpc_providers(&network_config).await;
+        let log_dispatchers =
+            Self::build_log_dispatchers(&network_config, &providers, ws_providers);
+        let mempool_dispatchers = Self::build_mempool_dispatchers(&network_config);
+        let trace_dispatchers = Self::build_trace_dispatchers(&network_config, &providers);
 
-        let sub_manager = Arc::new(SubscriptionManager::new());
+        let cluster = Self::build_cluster(&network_config).await?;
+        let webhooks = Arc::new(webhooks);
+        let sub_manager = Arc::new(SubscriptionManager::new(
+            network_config.session_limits,
+            cluster,
+            webhooks,
+        ));
         Arc::clone(&sub_manager).spawn_cleanup();
+        Arc::clone(&sub_manager).spawn_cluster_listener();
 
-        let token_list_fetcher = Arc::new(TokenListFetcher::new());
+        let session_store =
+            Self::init_session_store(&network_config, &sub_manager, &api_keys).await;
 
-        Arc::new(Self {
+        let token_list_fetcher = Arc::new(TokenListFetcher::new(
+            network_config.token_list_paths.clone(),
+        ));
+        token_list_fetcher.init_local_lists().await;
+
+        let rate_limiter = Arc::new(RateLimiter::new(network_config.rate_limits.clone()));
+        Arc::clone(&rate_limiter).spawn_cleanup();
+
+        let state = Arc::new(Self {
             network_config: Arc::new(network_config),
             providers: Arc::new(providers),
-            ws_providers: Arc::new(ws_providers),
+            log_dispatchers: Arc::new(log_dispatchers),
+            mempool_dispatchers: Arc::new(mempool_dispatchers),
+            trace_dispatchers: Arc::new(trace_dispatchers),
             sub_manager,
             token_list_fetcher,
+            api_keys: Arc::new(api_keys),
+            rate_limiter,
+            session_store,
+            price_oracle: price_oracle.map(Arc::new),
+            balance_strategies: balance_strategies.map(Arc::new),
+        });
+
+        // restored sessions with a webhook have no clients to spawn their watchers
+        for (key, subscription) in state.sub_manager.webhook_sessions().await {
+            if let Err(err) = state.ensure_watchers(key, &subscription).await {
+                tracing::error!(error = %err, sub = %key, "unable to spawn watchers");
+            }
+        }
+
+        Ok(state)
+    }
+
+    pub fn watcher_context(
+        &self,
+        key: SubscriptionKey,
+    ) -> Result<WatcherContext, WatcherSetupError> {
+        let network = key.network;
+
+        let provider = self
+            .providers
+            .get(&network)
+            .ok_or(WatcherSetupError::Provider(network))?;
+
+        let log_dispatcher = self
+            .log_dispatchers
+            .get(&network)
+            .ok_or(WatcherSetupError::LogSource(network))?;
+
+        let multicall3 = self.network_config.multicall_address();
+        if multicall3.is_empty() {
+            return Err(WatcherSetupError::Multicall(network));
+        }
+
+        Ok(WatcherContext {
+            provider: provider.clone(),
+            owner: key.owner,
+            network,
+            multicall3: *multicall3,
+            log_dispatcher: Arc::clone(log_dispatcher),
+            wrapped_tokens: self.network_config.wrapped_tokens(&network),
+            discovery: self.network_config.discovery.clone(),
+            max_watched_tokens_limit: self.network_config.max_watched_tokens_limit,
+            max_backfill_blocks: self.network_config.persistence.max_backfill_blocks,
+            finality_poll_interval: self.network_config.finality_poll_interval,
+            price_oracle: self.price_oracle.clone(),
+            balance_strategies: self.balance_strategies.clone(),
+            mempool_dispatcher: self.mempool_dispatchers.get(&network).cloned(),
+            pending_check_interval: self.network_config.mempool.check_interval,
+            pending_tx_timeout: self.network_config.mempool.tx_timeout,
+            trace_dispatcher: self.trace_dispatchers.get(&network).cloned(),
+            sub_manager: Arc::clone(&self.sub_manager),
         })
     }
 
+    // watchers are spawned once per session: by its first SSE client or by its webhook
+    pub async fn ensure_watchers(
+        self: &Arc<Self>,
+        key: SubscriptionKey,
+        subscription: &Arc<Subscription>,
+    ) -> Result<(), WatcherSetupError> {
+        let ctx = self.watcher_context(key)?;
+
+        if subscription.claim_watchers() {
+            self.run_watchers(key, subscription, ctx).await;
+        }
+
+        Ok(())
+    }
+
+    pub async fn run_watchers(
+        self: &Arc<Self>,
+        key: SubscriptionKey,
+        subscription: &Arc<Subscription>,
+        ctx: WatcherContext,
+    ) {
+        let snapshot_interval = self.network_config.snapshot_interval;
+        match self.sub_manager.cluster() {
+            // watchers of the session run only on the replica holding its lock
+            Some(cluster) => {
+                tracing::info!(sub = %key, "join leader election");
+
+                cluster.spawn_leader_election(
+                    Arc::clone(&self.sub_manager),
+                    key,
+                    Arc::clone(subscription),
+                    ctx,
+                    snapshot_interval,
+                );
+            }
+            None => {
+                tracing::info!(sub = %key, "spawn watchers");
+
+                Watcher::new(
+                    ctx,
+                    Arc::clone(subscription),
+                    subscription.cancel_token.clone(),
+                )
+                .spawn_watchers(snapshot_interval)
+                .await;
+            }
+        }
+    }
+
+    // replicas share sessions only with the redis backend,
+    // unlike the rpc providers it is required to start
+    async fn build_cluster(cfg: &NetworkConfig) -> Result<Option<Arc<Cluster>>, ClusterError> {
+        match cfg.cluster.backend {
+            ClusterBackendKind::Local => Ok(None),
+            ClusterBackendKind::Redis => {
+                let backend = RedisBackend::connect(&cfg.cluster.redis_url).await?;
+                let cluster = Cluster::new(Arc::new(backend), &cfg.cluster);
+
+                Ok(Some(Arc::new(cluster)))
+            }
+        }
+    }
+
+    // open the store and restore sessions saved before restart
+    // the service works without persistence if the store can't be opened
+    async fn init_session_store(
+        cfg: &NetworkConfig,
+        sub_manager: &Arc<SubscriptionManager>,
+        api_keys: &ApiKeyRegistry,
+    ) -> Option<Arc<SessionStore>> {
+        let path = cfg.persistence.path.as_ref()?;
+
+        let store = match SessionStore::open(path) {
+            Ok(store) => Arc::new(store),
+            Err(err) => {
+                tracing::error!(error = %err, "persistence is disabled");
+                return None;
+            }
+        };
+
+        sub_manager
+            .restore_sessions(store.load(), |name| api_keys.max_sessions_of(name))
+            .await;
+        Arc::clone(&store).spawn_flusher(Arc::clone(sub_manager), cfg.persistence.flush_interval);
+
+        Some(store)
+    }
+
+    // save sessions and close client streams, so the server could stop gracefully
+    pub async fn shutdown(&self) {
+        if let Some(store) = &self.session_store {
+            store.save_sessions(&self.sub_manager).await;
+        }
+
+        self.sub_manager.close_all().await;
+    }
+
+    // every network with http provider gets a log source,
+    // ws provider which failed to connect on start is recreated by the dispatcher
+    fn build_log_dispatchers(
+        cfg: &NetworkConfig,
+        providers: &HashMap<EvmNetwork, DynProvider<Ethereum>>,
+        mut ws_providers: HashMap<EvmNetwork, DynProvider>,
+    ) -> HashMap<EvmNetwork, Arc<LogDispatcher>> {
+        let mut dispatchers = HashMap::new();
+
+        for (network, http_provider) in providers {
+            let ws_provider = ws_providers.remove(network);
+
+            let dispatcher = LogDispatcher::new(
+                *network,
+                cfg.alchemy_ws_url(*network),
+                ws_provider,
+                http_provider.clone(),
+                cfg.log_source.clone(),
+            );
+            dispatchers.insert(*network, Arc::new(dispatcher));
+        }
+
+        dispatchers
+    }
+
+    // only networks listed in MEMPOOL_NETWORKS, the subscription is opened with the first session
+    fn build_mempool_dispatchers(
+        cfg: &NetworkConfig,
+    ) -> HashMap<EvmNetwork, Arc<MempoolDispatcher>> {
+        cfg.mempool
+            .networks
+            .iter()
+            .map(|network| {
+                let dispatcher = MempoolDispatcher::new(
+                    *network,
+                    cfg.alchemy_ws_url(*network),
+                    cfg.wrapped_tokens(network),
+                    cfg.log_source.reconnect(*network),
+                );
+                (*network, Arc::new(dispatcher))
+            })
+            .collect()
+    }
+
+    // only networks listed in TRACE_NETWORKS, blocks are traced while the network has sessions
+    fn build_trace_dispatchers(
+        cfg: &NetworkConfig,
+        providers: &HashMap<EvmNetwork, DynProvider<Ethereum>>,
+    ) -> HashMap<EvmNetwork, Arc<TraceDispatcher>> {
+        cfg.trace
+            .networks
+            .iter()
+            .filter_map(|(network, method)| {
+                let provider = providers.get(network)?;
+                let dispatcher =
+                    TraceDispatcher::new(*network, provider.clone(), *method, &cfg.trace);
+                Some((*network, Arc::new(dispatcher)))
+            })
+            .collect()
+    }
+
     async fn build_rpc_roviders_map(
         cfg: &NetworkConfig,
     ) -> HashMap<EvmNetwork, DynProvider<Ethereum>> {
@@ -22,11 +22,172 @@ pub struct Args {
     #[arg(long, env = "MAX_WATCHED_TOKENS_LIMIT", default_value = "1000")]
     pub max_watched_tokens_limit: String,
 
+    #[arg(long, env = "MAX_SESSIONS", default_value = "10000")]
+    pub max_sessions: String,
+
+    #[arg(long, env = "MAX_SESSIONS_PER_OWNER", default_value = "10")]
+    pub max_sessions_per_owner: String,
+
+    #[arg(long, env = "MAX_TOTAL_TOKENS", default_value = "2000000")]
+    pub max_total_tokens: String,
+
+    #[arg(long, env = "LOG_SOURCE_MODE", default_value = "auto")]
+    pub log_source_mode: String,
+
+    #[arg(long, env = "LOG_POLLING_NETWORKS", default_value = "")]
+    pub log_polling_networks: String,
+
+    #[arg(long, env = "COW_NETWORKS", default_value = "")]
+    pub cow_networks: String,
+
+    #[arg(long, env = "LOG_POLLING_INTERVAL_MS", default_value = "2000")]
+    pub log_polling_interval_ms: String,
+
+    #[arg(long, env = "LOG_POLLING_BLOCK_RANGE", default_value = "500")]
+    pub log_polling_block_range: String,
+
+    #[arg(long, env = "LOG_POLLING_MAX_FILTERED_OWNERS", default_value = "1000")]
+    pub log_polling_max_filtered_owners: String,
+
+    #[arg(long, env = "WS_FAILURES_BEFORE_POLLING", default_value = "5")]
+    pub ws_failures_before_polling: String,
+
+    #[arg(long, env = "WS_RECONNECT_INITIAL_DELAY_MS", default_value = "500")]
+    pub ws_reconnect_initial_delay_ms: String,
+
+    #[arg(long, env = "WS_RECONNECT_MAX_DELAY_MS", default_value = "30000")]
+    pub ws_reconnect_max_delay_ms: String,
+
+    #[arg(long, env = "WS_DEGRADED_AFTER_ATTEMPTS", default_value = "5")]
+    pub ws_degraded_after_attempts: String,
+
+    // <chain_id>:<initial_delay_ms>:<max_delay_ms>:<degraded_after_attempts>, comma-separated
+    #[arg(long, env = "WS_RECONNECT_NETWORKS", default_value = "")]
+    pub ws_reconnect_networks: String,
+
+    #[arg(long, env = "FINALITY_POLL_INTERVAL_SECS", default_value = "12")]
+    pub finality_poll_interval_secs: String,
+
+    #[arg(long, env = "MEMPOOL_NETWORKS", default_value = "")]
+    pub mempool_networks: String,
+
+    #[arg(long, env = "PENDING_CHECK_INTERVAL_SECS", default_value = "3")]
+    pub pending_check_interval_secs: String,
+
+    #[arg(long, env = "PENDING_TX_TIMEOUT_SECS", default_value = "600")]
+    pub pending_tx_timeout_secs: String,
+
+    // <chain_id>[:<trace|debug>], comma-separated
+    #[arg(long, env = "TRACE_NETWORKS", default_value = "")]
+    pub trace_networks: String,
+
+    #[arg(long, env = "TRACE_MAX_BLOCKS_PER_POLL", default_value = "10")]
+    pub trace_max_blocks_per_poll: String,
+
+    #[arg(long, env = "PERSISTENCE_PATH", default_value = "")]
+    pub persistence_path: String,
+
+    #[arg(long, env = "PERSISTENCE_FLUSH_INTERVAL_SECS", default_value = "10")]
+    pub persistence_flush_interval_secs: String,
+
+    #[arg(long, env = "PERSISTENCE_MAX_BACKFILL_BLOCKS", default_value = "10000")]
+    pub persistence_max_backfill_blocks: String,
+
+    #[arg(long, env = "CLUSTER_BACKEND", default_value = "local")]
+    pub cluster_backend: String,
+
+    #[arg(long, env = "REDIS_URL", default_value = "redis://127.0.0.1:6379")]
+    pub redis_url: String,
+
+    #[arg(long, env = "REPLICA_ID", default_value = "")]
+    pub replica_id: String,
+
+    #[arg(long, env = "CLUSTER_LEADER_TTL_MS", default_value = "15000")]
+    pub cluster_leader_ttl_ms: String,
+
+    #[arg(long, env = "CLUSTER_SESSION_TTL_SECS", default_value = "3600")]
+    pub cluster_session_ttl_secs: String,
+
+    #[arg(long, env = "WEBHOOK_TIMEOUT_MS", default_value = "5000")]
+    pub webhook_timeout_ms: String,
+
+    #[arg(long, env = "WEBHOOK_MAX_ATTEMPTS", default_value = "5")]
+    pub webhook_max_attempts: String,
+
+    #[arg(long, env = "WEBHOOK_RETRY_INITIAL_DELAY_MS", default_value = "1000")]
+    pub webhook_retry_initial_delay_ms: String,
+
+    #[arg(long, env = "WEBHOOK_RETRY_MAX_DELAY_MS", default_value = "60000")]
+    pub webhook_retry_max_delay_ms: String,
+
+    #[arg(long, env = "WEBHOOK_ALLOWED_HOSTS", default_value = "")]
+    pub webhook_allowed_hosts: String,
+
+    #[arg(long, env = "WEBHOOK_ALLOW_PRIVATE_HOSTS", default_value = "false")]
+    pub webhook_allow_private_hosts: String,
+
+    #[arg(long, env = "WEBHOOK_SESSION_TTL_SECS", default_value = "86400")]
+    pub webhook_session_ttl_secs: String,
+
+    #[arg(long, env = "BALANCE_STRATEGIES", default_value = "")]
+    pub balance_strategies: String,
+
+    #[arg(long, env = "BALANCE_STRATEGIES_PATH", default_value = "")]
+    pub balance_strategies_path: String,
+
+    #[arg(long, env = "PRICE_FEEDS", default_value = "")]
+    pub price_feeds: String,
+
+    #[arg(long, env = "PRICE_FEEDS_PATH", default_value = "")]
+    pub price_feeds_path: String,
+
+    #[arg(long, env = "PRICE_TWAP_WINDOW_SECS", default_value = "1800")]
+    pub price_twap_window_secs: String,
+
+    #[arg(long, env = "PRICE_MAX_AGE_SECS", default_value = "90000")]
+    pub price_max_age_secs: String,
+
+    #[arg(long, env = "PRICE_UPDATE_THRESHOLD_BPS", default_value = "100")]
+    pub price_update_threshold_bps: String,
+
     #[arg(long, env = "ALLOWED_ORIGINS", default_value = "")]
     pub allowed_origins: String,
 
     #[arg(long, env = "WETH_CONTRACT_ADDRESSES", default_value = "")]
     pub weth_contract_addresses: String,
+
+    #[arg(long, env = "ADMIN_API_KEY", default_value = "")]
+    pub admin_api_key: String,
+
+    #[arg(long, env = "API_KEYS", default_value = "")]
+    pub api_keys: String,
+
+    #[arg(long, env = "API_KEYS_PATH", default_value = "")]
+    pub api_keys_path: String,
+
+    #[arg(long, env = "RATE_LIMIT_BALANCE_PER_MINUTE", default_value = "60")]
+    pub rate_limit_balance_per_minute: String,
+
+    #[arg(long, env = "RATE_LIMIT_SESSIONS_PER_MINUTE", default_value = "30")]
+    pub rate_limit_sessions_per_minute: String,
+
+    #[arg(long, env = "RATE_LIMIT_SSE_PER_MINUTE", default_value = "30")]
+    pub rate_limit_sse_per_minute: String,
+
+    #[arg(long, env = "TRUST_X_FORWARDED_FOR", default_value = "false")]
+    pub trust_x_forwarded_for: String,
+
+    #[arg(long, env = "TRUSTED_PROXY_HOPS", default_value = "1")]
+    pub trusted_proxy_hops: String,
+
+    #[arg(long, env = "DISCOVERY_BLOCK_RANGE", default_value = "100000")]
+    pub discovery_block_range: String,
+
+    #[arg(long, env = "DISCOVERY_PAGE_SIZE", default_value = "2000")]
+    pub discovery_page_size: String,
+
+    #[arg(long, env = "DISCOVERY_TOKEN_BLOCKLIST", default_value = "")]
+    pub discovery_token_blocklist: String,
 }
 
 impl Args {
@@ -0,0 +1,36 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Where sessions and events are shared between replicas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClusterBackendKind {
+    /// Single replica, sessions and events are process-local
+    Local,
+    /// Sessions in Redis keys, events over Redis pub/sub, watchers leader per session
+    Redis,
+}
+
+impl FromStr for ClusterBackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "local" => Ok(ClusterBackendKind::Local),
+            "redis" => Ok(ClusterBackendKind::Redis),
+            other => Err(format!("unknown cluster backend {other}")),
+        }
+    }
+}
+
+/// Settings of running several replicas behind a load balancer
+#[derive(Debug, Clone)]
+pub struct ClusterConfig {
+    pub backend: ClusterBackendKind,
+    pub redis_url: String,
+    /// Identity of this replica in leader locks and messages
+    pub replica_id: String,
+    /// Lock TTL of the watchers leader, the lock is renewed every third of it
+    pub leader_ttl: Duration,
+    /// How long shared session state is kept after the last save
+    pub session_ttl: Duration,
+}
@@ -8,3 +8,115 @@ pub const BROADCAST_CHANNEL_CAPACITY: usize = 256;
 pub const DEFAULT_SNAPSHOT_INTERVAL_SECS: usize = 60;
 
 pub const DEFAULT_MAX_WATCHED_TOKENS_LIMIT: usize = 1000;
+
+/// Interval (seconds) between checks of local token list files for changes
+pub const LOCAL_TOKEN_LISTS_RELOAD_INTERVAL_SECS: u64 = 10;
+
+/// Default number of blocks scanned back for token discovery
+pub const DEFAULT_DISCOVERY_BLOCK_RANGE: u64 = 100_000;
+
+/// Default number of blocks per eth_getLogs page for token discovery
+pub const DEFAULT_DISCOVERY_PAGE_SIZE: u64 = 2_000;
+
+/// Default interval (milliseconds) between eth_blockNumber polls in polling log source mode
+pub const DEFAULT_LOG_POLLING_INTERVAL_MS: u64 = 2_000;
+
+/// Default number of blocks per eth_getLogs request in polling log source mode
+pub const DEFAULT_LOG_POLLING_BLOCK_RANGE: u64 = 500;
+
+/// Default number of registered owners up to which polling requests only their logs
+pub const DEFAULT_LOG_POLLING_MAX_FILTERED_OWNERS: usize = 1_000;
+
+/// Default number of consecutive failed WS subscribe attempts before `auto` mode switches to polling
+pub const DEFAULT_WS_FAILURES_BEFORE_POLLING: u32 = 5;
+
+/// Default WS reconnect backoff (milliseconds)
+pub const DEFAULT_WS_RECONNECT_INITIAL_DELAY_MS: u64 = 500;
+pub const DEFAULT_WS_RECONNECT_MAX_DELAY_MS: u64 = 30_000;
+
+/// Default number of consecutive failed WS reconnect attempts before sessions are notified with `degraded`
+pub const DEFAULT_WS_DEGRADED_AFTER_ATTEMPTS: u32 = 5;
+
+/// Default interval (seconds) between saves of sessions to the store
+pub const DEFAULT_PERSISTENCE_FLUSH_INTERVAL_SECS: u64 = 10;
+
+/// Default max gap (blocks) backfilled for restored sessions
+pub const DEFAULT_PERSISTENCE_MAX_BACKFILL_BLOCKS: u64 = 10_000;
+
+/// Blocks per eth_getLogs request when restored sessions backfill the gap
+pub const BACKFILL_PAGE_SIZE: u64 = 2_000;
+
+/// Default lock TTL (milliseconds) of the watchers leader of a session
+pub const DEFAULT_CLUSTER_LEADER_TTL_MS: u64 = 15_000;
+
+/// Default TTL (seconds) of session state shared between replicas
+pub const DEFAULT_CLUSTER_SESSION_TTL_SECS: u64 = 3_600;
+
+/// Delay (milliseconds) before resubscribing to cluster messages after the connection is lost
+pub const CLUSTER_RESUBSCRIBE_DELAY_MS: u64 = 1_000;
+
+/// Default interval (seconds) between checks of the finality block of sessions
+pub const DEFAULT_FINALITY_POLL_INTERVAL_SECS: u64 = 12;
+
+/// Maximum confirmations of the session finality
+pub const MAX_FINALITY_CONFIRMATIONS: u64 = 10_000;
+
+/// Default interval (seconds) between receipt checks of pending transactions
+pub const DEFAULT_PENDING_CHECK_INTERVAL_SECS: u64 = 3;
+
+/// Default time (seconds) after which a pending transaction which is not mined is dropped
+pub const DEFAULT_PENDING_TX_TIMEOUT_SECS: u64 = 600;
+
+/// Maximum pending transactions tracked per session, newer ones are ignored
+pub const MAX_PENDING_TXS_PER_SESSION: usize = 100;
+
+/// Default number of blocks traced per poll for internal native transfers
+pub const DEFAULT_TRACE_MAX_BLOCKS_PER_POLL: u64 = 10;
+
+/// Maximum NFT collections watched per session
+pub const MAX_NFT_COLLECTIONS_PER_SESSION: usize = 100;
+
+/// Token IDs requested via tokenOfOwnerByIndex per collection, larger holdings are tracked from transfers
+pub const MAX_ENUMERATED_NFTS_PER_COLLECTION: u64 = 500;
+
+/// Maximum ERC-1155 (contract, id) pairs watched per session
+pub const MAX_ERC1155_TOKENS_PER_SESSION: usize = 500;
+
+/// Maximum alert rules per session
+pub const MAX_ALERT_RULES_PER_SESSION: usize = 50;
+
+/// Default timeout (milliseconds) of one webhook request
+pub const DEFAULT_WEBHOOK_TIMEOUT_MS: u64 = 5_000;
+
+/// Default attempts to deliver one event to a webhook (the first request + retries)
+pub const DEFAULT_WEBHOOK_MAX_ATTEMPTS: u32 = 5;
+
+/// Default webhook retry backoff (milliseconds)
+pub const DEFAULT_WEBHOOK_RETRY_INITIAL_DELAY_MS: u64 = 1_000;
+pub const DEFAULT_WEBHOOK_RETRY_MAX_DELAY_MS: u64 = 60_000;
+
+/// Default time (seconds) a webhook session lives without SSE clients
+pub const DEFAULT_WEBHOOK_SESSION_TTL_SECS: u64 = 86_400;
+
+/// Events waiting for delivery per webhook, newer events are dropped when it is full
+pub const WEBHOOK_QUEUE_CAPACITY: usize = 1_000;
+
+/// Default window (seconds) of the Uniswap V3 TWAP price fallback
+pub const DEFAULT_PRICE_TWAP_WINDOW_SECS: u64 = 1_800;
+
+/// Default age (seconds) after which a Chainlink answer is stale,
+/// most feeds have a 24h heartbeat
+pub const DEFAULT_PRICE_MAX_AGE_SECS: u64 = 90_000;
+
+/// Default change of the total USD value (basis points) which is published as `price_update`
+pub const DEFAULT_PRICE_UPDATE_THRESHOLD_BPS: u32 = 100;
+
+/// Default rate limits (requests per minute) per client, 0 disables the limit
+pub const DEFAULT_RATE_LIMIT_BALANCE_PER_MINUTE: u32 = 60;
+pub const DEFAULT_RATE_LIMIT_SESSIONS_PER_MINUTE: u32 = 30;
+pub const DEFAULT_RATE_LIMIT_SSE_PER_MINUTE: u32 = 30;
+
+/// Default global limits, 0 disables the limit
+pub const DEFAULT_MAX_SESSIONS: usize = 10_000;
+pub const DEFAULT_MAX_SESSIONS_PER_OWNER: usize = 10;
+pub const DEFAULT_MAX_TOTAL_TOKENS: usize = 2_000_000;
@@ -0,0 +1,10 @@
+use alloy::primitives::{address, Address};
+
+// GPv2Settlement, deployed at the same address in every network
+pub const COW_SETTLEMENT_ADDRESS: Address = address!("0x9008D19f58AAbD9eD0D60971565AA8510560ab41");
+
+// CoWSwapEthFlow production deployments (v1.0 and v1.1), the same in every network
+pub const COW_ETH_FLOW_ADDRESSES: [Address; 2] = [
+    address!("0x40A50cf069e992AA4536211B23F286eF88752187"),
+    address!("0xbA3cB449bD2B4ADddBc894D8697F5170800EAdeC"),
+];
@@ -0,0 +1,43 @@
+use alloy::primitives::{Address, U256};
+use std::collections::HashSet;
+
+/// Settings of token discovery from wallet transfer history
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    /// How many blocks back from the latest one are scanned for incoming transfers
+    pub block_range: u64,
+    /// Blocks per eth_getLogs request
+    pub page_size: u64,
+    /// Tokens which are never added by discovery
+    pub blocklist: HashSet<Address>,
+}
+
+impl DiscoveryConfig {
+    // zero-value transfers are the usual way of address poisoning,
+    // so they never reveal a token which is really held
+    pub fn is_spam_transfer(&self, token: &Address, value: U256) -> bool {
+        value.is_zero() || self.blocklist.contains(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::address;
+
+    const USDC: Address = address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const SPAM: Address = address!("0x00000000000000000000000000000000000000aa");
+
+    #[test]
+    fn zero_value_and_blocklisted_transfers_are_spam() {
+        let config = DiscoveryConfig {
+            block_range: 100,
+            page_size: 10,
+            blocklist: HashSet::from([SPAM]),
+        };
+
+        assert!(!config.is_spam_transfer(&USDC, U256::from(1)));
+        assert!(config.is_spam_transfer(&USDC, U256::ZERO));
+        assert!(config.is_spam_transfer(&SPAM, U256::from(1)));
+    }
+}
@@ -0,0 +1,7 @@
+use alloy::primitives::{address, Address};
+
+// ERC-4337 EntryPoint v0.6 and v0.7, deployed at the same address in every network
+pub const ENTRY_POINT_ADDRESSES: [Address; 2] = [
+    address!("0x5FF137D4b0FDCD49DcA30c7CF57E578a026d2789"),
+    address!("0x0000000071727De22E5E9d8BAf0edAc6f37da032"),
+];
@@ -0,0 +1,130 @@
+use crate::domain::EvmNetwork;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// How a network receives Transfer/Deposit/Withdrawal logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSourceMode {
+    /// eth_subscribe over WebSocket only
+    Ws,
+    /// eth_blockNumber + eth_getLogs over HTTP for every new block range
+    Polling,
+    /// WebSocket, switches to polling when subscribing keeps failing
+    Auto,
+}
+
+impl LogSourceMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogSourceMode::Ws => "ws",
+            LogSourceMode::Polling => "polling",
+            LogSourceMode::Auto => "auto",
+        }
+    }
+}
+
+impl FromStr for LogSourceMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ws" => Ok(LogSourceMode::Ws),
+            "polling" => Ok(LogSourceMode::Polling),
+            "auto" => Ok(LogSourceMode::Auto),
+            other => Err(format!("unknown log source mode {other}")),
+        }
+    }
+}
+
+/// Reconnect backoff of the WS log subscription
+#[derive(Debug, Clone, Copy)]
+pub struct ReconnectConfig {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Consecutive failed attempts after which sessions receive a `degraded` event, 0 - never
+    pub degraded_after: u32,
+}
+
+impl ReconnectConfig {
+    // exponential backoff with jitter: a random delay in [exp / 2, exp],
+    // where exp = initial_delay * 2^(attempt - 1) capped by max_delay
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        let half = exp / 2;
+        half + rand::rng().random_range(Duration::ZERO..=exp - half)
+    }
+}
+
+/// Settings of the per-network log source
+#[derive(Debug, Clone)]
+pub struct LogSourceConfig {
+    pub mode: LogSourceMode,
+    /// Networks which always use polling regardless of `mode`
+    pub polling_networks: HashSet<EvmNetwork>,
+    pub poll_interval: Duration,
+    /// Blocks per eth_getLogs request while polling
+    pub poll_block_range: u64,
+    /// Registered owners up to which eth_getLogs is filtered by their topics, 0 - never filter
+    pub poll_max_filtered_owners: usize,
+    /// Consecutive failed WS subscribe attempts before `auto` switches to polling
+    pub ws_failures_before_polling: u32,
+    pub reconnect: ReconnectConfig,
+    /// Per-network reconnect settings which replace `reconnect`
+    pub reconnect_overrides: HashMap<EvmNetwork, ReconnectConfig>,
+    /// Networks which also listen to CoW Protocol settlement Trade and EthFlow order events
+    pub cow_networks: HashSet<EvmNetwork>,
+}
+
+impl LogSourceConfig {
+    pub fn mode(&self, network: EvmNetwork) -> LogSourceMode {
+        if self.polling_networks.contains(&network) {
+            return LogSourceMode::Polling;
+        }
+
+        self.mode
+    }
+
+    pub fn reconnect(&self, network: EvmNetwork) -> ReconnectConfig {
+        self.reconnect_overrides
+            .get(&network)
+            .copied()
+            .unwrap_or(self.reconnect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_is_jittered_exponential_backoff() {
+        let config = ReconnectConfig {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(4),
+            degraded_after: 5,
+        };
+
+        for (attempt, exp) in [
+            (0, 500),
+            (1, 500),
+            (2, 1000),
+            (3, 2000),
+            (4, 4000),
+            (10, 4000),
+        ] {
+            let exp = Duration::from_millis(exp);
+            for _ in 0..20 {
+                let delay = config.delay(attempt);
+                assert!(delay >= exp / 2 && delay <= exp, "{attempt}: {delay:?}");
+            }
+        }
+
+        assert!(config.delay(u32::MAX) <= config.max_delay);
+    }
+}
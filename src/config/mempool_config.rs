@@ -0,0 +1,14 @@
+use crate::domain::EvmNetwork;
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Settings of pending transaction projections
+#[derive(Debug, Clone)]
+pub struct MempoolConfig {
+    /// Networks subscribed to full pending transactions, sessions of other networks can't enable it
+    pub networks: HashSet<EvmNetwork>,
+    /// Interval between receipt checks of tracked transactions
+    pub check_interval: Duration,
+    /// Transactions which are not mined in time are dropped
+    pub tx_timeout: Duration,
+}
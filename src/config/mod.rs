@@ -1,3 +1,14 @@
+pub mod cluster_config;
 pub mod constants;
+pub mod cow_address;
+pub mod discovery_config;
+pub mod entry_point_address;
+pub mod log_source_config;
+pub mod mempool_config;
 pub mod network_config;
+pub mod persistence_config;
+pub mod pricing_config;
+pub mod session_limits;
+pub mod trace_config;
+pub mod webhook_config;
 mod wrapped_address;
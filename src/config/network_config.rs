@@ -1,23 +1,76 @@
-use super::constants::{DEFAULT_MAX_WATCHED_TOKENS_LIMIT, DEFAULT_SNAPSHOT_INTERVAL_SECS};
+use super::constants::{
+    DEFAULT_CLUSTER_LEADER_TTL_MS, DEFAULT_CLUSTER_SESSION_TTL_SECS, DEFAULT_DISCOVERY_BLOCK_RANGE,
+    DEFAULT_DISCOVERY_PAGE_SIZE, DEFAULT_FINALITY_POLL_INTERVAL_SECS,
+    DEFAULT_LOG_POLLING_BLOCK_RANGE, DEFAULT_LOG_POLLING_INTERVAL_MS,
+    DEFAULT_LOG_POLLING_MAX_FILTERED_OWNERS, DEFAULT_MAX_SESSIONS, DEFAULT_MAX_SESSIONS_PER_OWNER,
+    DEFAULT_MAX_TOTAL_TOKENS, DEFAULT_MAX_WATCHED_TOKENS_LIMIT,
+    DEFAULT_PENDING_CHECK_INTERVAL_SECS, DEFAULT_PENDING_TX_TIMEOUT_SECS,
+    DEFAULT_PERSISTENCE_FLUSH_INTERVAL_SECS, DEFAULT_PERSISTENCE_MAX_BACKFILL_BLOCKS,
+    DEFAULT_PRICE_MAX_AGE_SECS, DEFAULT_PRICE_TWAP_WINDOW_SECS, DEFAULT_PRICE_UPDATE_THRESHOLD_BPS,
+    DEFAULT_RATE_LIMIT_BALANCE_PER_MINUTE, DEFAULT_RATE_LIMIT_SESSIONS_PER_MINUTE,
+    DEFAULT_RATE_LIMIT_SSE_PER_MINUTE, DEFAULT_SNAPSHOT_INTERVAL_SECS,
+    DEFAULT_TRACE_MAX_BLOCKS_PER_POLL, DEFAULT_WEBHOOK_MAX_ATTEMPTS,
+    DEFAULT_WEBHOOK_RETRY_INITIAL_DELAY_MS, DEFAULT_WEBHOOK_RETRY_MAX_DELAY_MS,
+    DEFAULT_WEBHOOK_SESSION_TTL_SECS, DEFAULT_WEBHOOK_TIMEOUT_MS,
+    DEFAULT_WS_DEGRADED_AFTER_ATTEMPTS, DEFAULT_WS_FAILURES_BEFORE_POLLING,
+    DEFAULT_WS_RECONNECT_INITIAL_DELAY_MS, DEFAULT_WS_RECONNECT_MAX_DELAY_MS,
+};
 use crate::args::Args;
+use crate::config::cluster_config::{ClusterBackendKind, ClusterConfig};
+use crate::config::discovery_config::DiscoveryConfig;
+use crate::config::log_source_config::{LogSourceConfig, LogSourceMode, ReconnectConfig};
+use crate::config::mempool_config::MempoolConfig;
+use crate::config::persistence_config::PersistenceConfig;
+use crate::config::pricing_config::PricingConfig;
+use crate::config::session_limits::SessionLimits;
+use crate::config::trace_config::{TraceConfig, TraceMethod};
+use crate::config::webhook_config::WebhookConfig;
 use crate::config::wrapped_address::get_wrapped_address;
 use crate::domain::EvmNetwork;
+use crate::services::rate_limiter::{RateLimit, RouteClass};
 use alloy::primitives::Address;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub struct NetworkConfig {
     api_key: String,
+    admin_api_key: Option<String>,
     pub multicall_address: Address,
     pub snapshot_interval: usize,
+    pub finality_poll_interval: Duration,
     pub max_watched_tokens_limit: usize,
     pub allowed_origins: Vec<String>,
+    pub token_list_paths: Vec<PathBuf>,
+    pub discovery: DiscoveryConfig,
+    pub rate_limits: HashMap<RouteClass, RateLimit>,
+    pub trust_x_forwarded_for: bool,
+    /// Reverse proxies appending to X-Forwarded-For, the client is that many entries from the right
+    pub trusted_proxy_hops: usize,
+    pub session_limits: SessionLimits,
+    pub log_source: LogSourceConfig,
+    pub persistence: PersistenceConfig,
+    pub cluster: ClusterConfig,
+    pub webhooks: WebhookConfig,
+    pub pricing: PricingConfig,
+    pub mempool: MempoolConfig,
+    pub trace: TraceConfig,
+    // wrapper -> underlying (native token address for the native one) per network
+    wrapped_tokens: HashMap<EvmNetwork, HashMap<Address, Address>>,
 }
 
 impl NetworkConfig {
     pub fn init(args: &Args) -> Self {
         let api_key = args.alchemy_api_key.clone();
 
+        // admin API is disabled if there is no key
+        let admin_api_key = Some(args.admin_api_key.trim().to_string()).filter(|k| !k.is_empty());
+        if admin_api_key.is_none() {
+            tracing::warn!("ADMIN_API_KEY is not set, admin API is disabled");
+        }
+
         let multicall_address = Address::from_str(&args.multicall_address)
             .inspect_err(|err| {
                 tracing::error!("Failed to parse multicall_address {}", err);
@@ -32,6 +85,16 @@ impl NetworkConfig {
             })
             .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_SECS);
 
+        let finality_poll_interval_secs: u64 = args
+            .finality_poll_interval_secs
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid FINALITY_POLL_INTERVAL_SECS value");
+                DEFAULT_FINALITY_POLL_INTERVAL_SECS
+            });
+
         let max_watched_tokens_limit: usize = args
             .max_watched_tokens_limit
             .parse()
@@ -49,15 +112,648 @@ impl NetworkConfig {
 
         tracing::info!(origins = %allowed_origins.join(", "), "init origins from env");
 
+        let token_list_paths: Vec<PathBuf> = args
+            .token_list_path
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from)
+            .collect();
+
+        let discovery = Self::init_discovery(args);
+        let rate_limits = Self::init_rate_limits(args);
+        let session_limits = Self::init_session_limits(args);
+        let log_source = Self::init_log_source(args);
+        let persistence = Self::init_persistence(args);
+        let cluster = Self::init_cluster(args);
+        let webhooks = Self::init_webhooks(args);
+        let pricing = Self::init_pricing(args);
+        let mempool = Self::init_mempool(args);
+        let trace = Self::init_trace(args, log_source.poll_interval);
+        let wrapped_tokens = Self::init_wrapped_tokens(args);
+
+        let trust_x_forwarded_for: bool = args
+            .trust_x_forwarded_for
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid TRUST_X_FORWARDED_FOR value: {}", err);
+            })
+            .unwrap_or(false);
+        let trusted_proxy_hops: usize = args
+            .trusted_proxy_hops
+            .parse()
+            .ok()
+            .filter(|hops| *hops > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid TRUSTED_PROXY_HOPS value");
+                1
+            });
+
         Self {
             api_key,
+            admin_api_key,
             multicall_address,
             snapshot_interval,
+            finality_poll_interval: Duration::from_secs(finality_poll_interval_secs),
             max_watched_tokens_limit,
             allowed_origins,
+            token_list_paths,
+            discovery,
+            rate_limits,
+            trust_x_forwarded_for,
+            trusted_proxy_hops,
+            session_limits,
+            log_source,
+            persistence,
+            cluster,
+            webhooks,
+            pricing,
+            mempool,
+            trace,
+            wrapped_tokens,
+        }
+    }
+
+    // <chain_id>[:<method>] entries, the method is `trace` (trace_block) if it is omitted
+    // blocks are polled with the log polling interval
+    fn init_trace(args: &Args, poll_interval: Duration) -> TraceConfig {
+        let parse_entry = |entry: &str| -> Option<(EvmNetwork, TraceMethod)> {
+            let (network, method) = match entry.split_once(':') {
+                Some((network, method)) => (network, TraceMethod::from_str(method).ok()?),
+                None => (entry, TraceMethod::TraceBlock),
+            };
+
+            Some((EvmNetwork::from_str(network.trim()).ok()?, method))
+        };
+
+        let mut networks = HashMap::new();
+        for entry in args
+            .trace_networks
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+        {
+            match parse_entry(entry) {
+                Some((network, method)) => {
+                    networks.insert(network, method);
+                }
+                None => tracing::warn!("Invalid entry in TRACE_NETWORKS: {}", entry),
+            }
+        }
+
+        let max_blocks_per_poll: u64 = args
+            .trace_max_blocks_per_poll
+            .parse()
+            .ok()
+            .filter(|blocks| *blocks > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid TRACE_MAX_BLOCKS_PER_POLL value");
+                DEFAULT_TRACE_MAX_BLOCKS_PER_POLL
+            });
+
+        TraceConfig {
+            networks,
+            poll_interval,
+            max_blocks_per_poll,
+        }
+    }
+
+    // WETH9 of every network and <chain_id>:<wrapper>[:<underlying>] entries,
+    // the underlying is the native token if it is omitted
+    fn init_wrapped_tokens(args: &Args) -> HashMap<EvmNetwork, HashMap<Address, Address>> {
+        let mut wrapped_tokens: HashMap<EvmNetwork, HashMap<Address, Address>> = EvmNetwork::ALL
+            .into_iter()
+            .map(|network| {
+                let weth = HashMap::from([(
+                    get_wrapped_address(&network),
+                    network.native_token_address(),
+                )]);
+                (network, weth)
+            })
+            .collect();
+
+        let parse_entry = |entry: &str| -> Option<(EvmNetwork, Address, Address)> {
+            let parts: Vec<&str> = entry.split(':').map(|s| s.trim()).collect();
+            let (network, wrapper, underlying) = match parts.as_slice() {
+                [network, wrapper] => (*network, *wrapper, None),
+                [network, wrapper, underlying] => (*network, *wrapper, Some(*underlying)),
+                _ => return None,
+            };
+
+            let network = EvmNetwork::from_str(network).ok()?;
+            let underlying = match underlying {
+                Some(underlying) => Address::from_str(underlying).ok()?,
+                None => network.native_token_address(),
+            };
+
+            Some((network, Address::from_str(wrapper).ok()?, underlying))
+        };
+
+        for entry in args
+            .weth_contract_addresses
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+        {
+            match parse_entry(entry) {
+                Some((network, wrapper, underlying)) if wrapper != underlying => {
+                    wrapped_tokens
+                        .entry(network)
+                        .or_default()
+                        .insert(wrapper, underlying);
+                }
+                _ => tracing::warn!("Invalid entry in WETH_CONTRACT_ADDRESSES: {}", entry),
+            }
+        }
+
+        wrapped_tokens
+    }
+
+    fn init_mempool(args: &Args) -> MempoolConfig {
+        let networks = args
+            .mempool_networks
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| {
+                EvmNetwork::from_str(s)
+                    .inspect_err(|err| {
+                        tracing::warn!("Invalid network in MEMPOOL_NETWORKS {}: {}", s, err);
+                    })
+                    .ok()
+            })
+            .collect();
+
+        let check_interval_secs: u64 = args
+            .pending_check_interval_secs
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid PENDING_CHECK_INTERVAL_SECS value");
+                DEFAULT_PENDING_CHECK_INTERVAL_SECS
+            });
+
+        let tx_timeout_secs: u64 = args
+            .pending_tx_timeout_secs
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid PENDING_TX_TIMEOUT_SECS value");
+                DEFAULT_PENDING_TX_TIMEOUT_SECS
+            });
+
+        MempoolConfig {
+            networks,
+            check_interval: Duration::from_secs(check_interval_secs),
+            tx_timeout: Duration::from_secs(tx_timeout_secs),
+        }
+    }
+
+    fn init_pricing(args: &Args) -> PricingConfig {
+        let twap_window_secs: u64 = args
+            .price_twap_window_secs
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0 && *secs <= u64::from(u32::MAX))
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid PRICE_TWAP_WINDOW_SECS value");
+                DEFAULT_PRICE_TWAP_WINDOW_SECS
+            });
+
+        let max_price_age_secs: u64 = args
+            .price_max_age_secs
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid PRICE_MAX_AGE_SECS value");
+                DEFAULT_PRICE_MAX_AGE_SECS
+            });
+
+        let update_threshold_bps: u32 = args
+            .price_update_threshold_bps
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid PRICE_UPDATE_THRESHOLD_BPS value: {}", err);
+            })
+            .unwrap_or(DEFAULT_PRICE_UPDATE_THRESHOLD_BPS);
+
+        PricingConfig {
+            twap_window: Duration::from_secs(twap_window_secs),
+            max_price_age: Duration::from_secs(max_price_age_secs),
+            update_threshold_bps,
+        }
+    }
+
+    fn init_cluster(args: &Args) -> ClusterConfig {
+        let backend = args
+            .cluster_backend
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid CLUSTER_BACKEND value: {}", err);
+            })
+            .unwrap_or(ClusterBackendKind::Local);
+
+        // unique per process if not set, a restarted replica is a new one
+        let replica_id = Some(args.replica_id.trim().to_string())
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+
+        let leader_ttl_ms: u64 = args
+            .cluster_leader_ttl_ms
+            .parse()
+            .ok()
+            .filter(|ms| *ms > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid CLUSTER_LEADER_TTL_MS value");
+                DEFAULT_CLUSTER_LEADER_TTL_MS
+            });
+
+        let session_ttl_secs: u64 = args
+            .cluster_session_ttl_secs
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid CLUSTER_SESSION_TTL_SECS value");
+                DEFAULT_CLUSTER_SESSION_TTL_SECS
+            });
+
+        ClusterConfig {
+            backend,
+            redis_url: args.redis_url.clone(),
+            replica_id,
+            leader_ttl: Duration::from_millis(leader_ttl_ms),
+            session_ttl: Duration::from_secs(session_ttl_secs),
+        }
+    }
+
+    fn init_webhooks(args: &Args) -> WebhookConfig {
+        let positive = |value: &str, name: &str, default: u64| -> u64 {
+            value
+                .parse()
+                .ok()
+                .filter(|value| *value > 0)
+                .unwrap_or_else(|| {
+                    tracing::warn!("Invalid {} value", name);
+                    default
+                })
+        };
+
+        let timeout_ms = positive(
+            &args.webhook_timeout_ms,
+            "WEBHOOK_TIMEOUT_MS",
+            DEFAULT_WEBHOOK_TIMEOUT_MS,
+        );
+        let max_attempts = positive(
+            &args.webhook_max_attempts,
+            "WEBHOOK_MAX_ATTEMPTS",
+            DEFAULT_WEBHOOK_MAX_ATTEMPTS as u64,
+        );
+        let retry_initial_delay_ms = positive(
+            &args.webhook_retry_initial_delay_ms,
+            "WEBHOOK_RETRY_INITIAL_DELAY_MS",
+            DEFAULT_WEBHOOK_RETRY_INITIAL_DELAY_MS,
+        );
+        let retry_max_delay_ms = positive(
+            &args.webhook_retry_max_delay_ms,
+            "WEBHOOK_RETRY_MAX_DELAY_MS",
+            DEFAULT_WEBHOOK_RETRY_MAX_DELAY_MS,
+        );
+        let session_ttl_secs = positive(
+            &args.webhook_session_ttl_secs,
+            "WEBHOOK_SESSION_TTL_SECS",
+            DEFAULT_WEBHOOK_SESSION_TTL_SECS,
+        );
+
+        let allowed_hosts = args
+            .webhook_allowed_hosts
+            .split(',')
+            .map(|host| host.trim().to_lowercase())
+            .filter(|host| !host.is_empty())
+            .collect();
+
+        let allow_private_hosts: bool = args
+            .webhook_allow_private_hosts
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid WEBHOOK_ALLOW_PRIVATE_HOSTS value: {}", err);
+            })
+            .unwrap_or(false);
+
+        WebhookConfig {
+            timeout: Duration::from_millis(timeout_ms),
+            max_attempts: u32::try_from(max_attempts).unwrap_or(u32::MAX),
+            retry_initial_delay: Duration::from_millis(retry_initial_delay_ms),
+            retry_max_delay: Duration::from_millis(retry_max_delay_ms.max(retry_initial_delay_ms)),
+            allowed_hosts,
+            allow_private_hosts,
+            session_ttl: Duration::from_secs(session_ttl_secs),
+        }
+    }
+
+    fn init_persistence(args: &Args) -> PersistenceConfig {
+        let path = Some(args.persistence_path.trim())
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+
+        let flush_interval_secs: u64 = args
+            .persistence_flush_interval_secs
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid PERSISTENCE_FLUSH_INTERVAL_SECS value");
+                DEFAULT_PERSISTENCE_FLUSH_INTERVAL_SECS
+            });
+
+        let max_backfill_blocks: u64 = args
+            .persistence_max_backfill_blocks
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid PERSISTENCE_MAX_BACKFILL_BLOCKS value: {}", err);
+            })
+            .unwrap_or(DEFAULT_PERSISTENCE_MAX_BACKFILL_BLOCKS);
+
+        PersistenceConfig {
+            path,
+            flush_interval: Duration::from_secs(flush_interval_secs),
+            max_backfill_blocks,
+        }
+    }
+
+    fn init_log_source(args: &Args) -> LogSourceConfig {
+        let mode = args
+            .log_source_mode
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid LOG_SOURCE_MODE value: {}", err);
+            })
+            .unwrap_or(LogSourceMode::Auto);
+
+        let polling_networks = args
+            .log_polling_networks
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| {
+                EvmNetwork::from_str(s)
+                    .inspect_err(|err| {
+                        tracing::warn!("Invalid network in LOG_POLLING_NETWORKS {}: {}", s, err);
+                    })
+                    .ok()
+            })
+            .collect();
+
+        let cow_networks = args
+            .cow_networks
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| {
+                EvmNetwork::from_str(s)
+                    .inspect_err(|err| {
+                        tracing::warn!("Invalid network in COW_NETWORKS {}: {}", s, err);
+                    })
+                    .ok()
+            })
+            .collect();
+
+        let poll_interval_ms: u64 = args
+            .log_polling_interval_ms
+            .parse()
+            .ok()
+            .filter(|ms| *ms > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid LOG_POLLING_INTERVAL_MS value");
+                DEFAULT_LOG_POLLING_INTERVAL_MS
+            });
+
+        let poll_block_range: u64 = args
+            .log_polling_block_range
+            .parse()
+            .ok()
+            .filter(|range| *range > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid LOG_POLLING_BLOCK_RANGE value");
+                DEFAULT_LOG_POLLING_BLOCK_RANGE
+            });
+
+        let poll_max_filtered_owners: usize = args
+            .log_polling_max_filtered_owners
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid LOG_POLLING_MAX_FILTERED_OWNERS value: {}", err);
+            })
+            .unwrap_or(DEFAULT_LOG_POLLING_MAX_FILTERED_OWNERS);
+
+        let ws_failures_before_polling: u32 = args
+            .ws_failures_before_polling
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid WS_FAILURES_BEFORE_POLLING value: {}", err);
+            })
+            .unwrap_or(DEFAULT_WS_FAILURES_BEFORE_POLLING);
+
+        LogSourceConfig {
+            mode,
+            polling_networks,
+            poll_interval: Duration::from_millis(poll_interval_ms),
+            poll_block_range,
+            poll_max_filtered_owners,
+            ws_failures_before_polling,
+            reconnect: Self::init_reconnect(args),
+            reconnect_overrides: Self::init_reconnect_overrides(args),
+            cow_networks,
+        }
+    }
+
+    fn reconnect_config(
+        initial_delay_ms: u64,
+        max_delay_ms: u64,
+        degraded_after: u32,
+    ) -> ReconnectConfig {
+        let initial_delay = Duration::from_millis(initial_delay_ms);
+
+        ReconnectConfig {
+            initial_delay,
+            max_delay: Duration::from_millis(max_delay_ms).max(initial_delay),
+            degraded_after,
+        }
+    }
+
+    fn init_reconnect(args: &Args) -> ReconnectConfig {
+        let initial_delay_ms: u64 = args
+            .ws_reconnect_initial_delay_ms
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid WS_RECONNECT_INITIAL_DELAY_MS value: {}", err);
+            })
+            .unwrap_or(DEFAULT_WS_RECONNECT_INITIAL_DELAY_MS);
+
+        let max_delay_ms: u64 = args
+            .ws_reconnect_max_delay_ms
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid WS_RECONNECT_MAX_DELAY_MS value: {}", err);
+            })
+            .unwrap_or(DEFAULT_WS_RECONNECT_MAX_DELAY_MS);
+
+        let degraded_after: u32 = args
+            .ws_degraded_after_attempts
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid WS_DEGRADED_AFTER_ATTEMPTS value: {}", err);
+            })
+            .unwrap_or(DEFAULT_WS_DEGRADED_AFTER_ATTEMPTS);
+
+        Self::reconnect_config(initial_delay_ms, max_delay_ms, degraded_after)
+    }
+
+    // <chain_id>:<initial_delay_ms>:<max_delay_ms>:<degraded_after_attempts>
+    fn init_reconnect_overrides(args: &Args) -> HashMap<EvmNetwork, ReconnectConfig> {
+        let parse_entry = |entry: &str| -> Option<(EvmNetwork, ReconnectConfig)> {
+            let parts: Vec<&str> = entry.split(':').map(|s| s.trim()).collect();
+            let [network, initial_delay_ms, max_delay_ms, degraded_after] = parts.as_slice() else {
+                return None;
+            };
+
+            Some((
+                EvmNetwork::from_str(network).ok()?,
+                Self::reconnect_config(
+                    initial_delay_ms.parse().ok()?,
+                    max_delay_ms.parse().ok()?,
+                    degraded_after.parse().ok()?,
+                ),
+            ))
+        };
+
+        args.ws_reconnect_networks
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .filter_map(|entry| {
+                let parsed = parse_entry(entry);
+                if parsed.is_none() {
+                    tracing::warn!("Invalid entry in WS_RECONNECT_NETWORKS: {}", entry);
+                }
+                parsed
+            })
+            .collect()
+    }
+
+    fn init_session_limits(args: &Args) -> SessionLimits {
+        let parse = |value: &String, name: &str, default: usize| -> usize {
+            value
+                .parse()
+                .inspect_err(|err| {
+                    tracing::warn!("Invalid {} value: {}", name, err);
+                })
+                .unwrap_or(default)
+        };
+
+        SessionLimits {
+            max_sessions: parse(&args.max_sessions, "MAX_SESSIONS", DEFAULT_MAX_SESSIONS),
+            max_sessions_per_owner: parse(
+                &args.max_sessions_per_owner,
+                "MAX_SESSIONS_PER_OWNER",
+                DEFAULT_MAX_SESSIONS_PER_OWNER,
+            ),
+            max_total_tokens: parse(
+                &args.max_total_tokens,
+                "MAX_TOTAL_TOKENS",
+                DEFAULT_MAX_TOTAL_TOKENS,
+            ),
+        }
+    }
+
+    fn init_rate_limits(args: &Args) -> HashMap<RouteClass, RateLimit> {
+        let limits = [
+            (
+                RouteClass::BalanceRead,
+                &args.rate_limit_balance_per_minute,
+                DEFAULT_RATE_LIMIT_BALANCE_PER_MINUTE,
+            ),
+            (
+                RouteClass::SessionMutation,
+                &args.rate_limit_sessions_per_minute,
+                DEFAULT_RATE_LIMIT_SESSIONS_PER_MINUTE,
+            ),
+            (
+                RouteClass::SseConnect,
+                &args.rate_limit_sse_per_minute,
+                DEFAULT_RATE_LIMIT_SSE_PER_MINUTE,
+            ),
+        ];
+
+        let mut rate_limits = HashMap::new();
+        for (class, value, default) in limits {
+            let per_minute: u32 = value
+                .parse()
+                .inspect_err(|err| {
+                    tracing::warn!("Invalid rate limit for {}: {}", class.as_str(), err);
+                })
+                .unwrap_or(default);
+
+            // zero disables the limit
+            if per_minute > 0 {
+                rate_limits.insert(class, RateLimit::per_minute(per_minute));
+            }
+        }
+
+        rate_limits
+    }
+
+    fn init_discovery(args: &Args) -> DiscoveryConfig {
+        let block_range: u64 = args
+            .discovery_block_range
+            .parse()
+            .inspect_err(|err| {
+                tracing::warn!("Invalid DISCOVERY_BLOCK_RANGE value: {}", err);
+            })
+            .unwrap_or(DEFAULT_DISCOVERY_BLOCK_RANGE);
+
+        let page_size: u64 = args
+            .discovery_page_size
+            .parse()
+            .ok()
+            .filter(|page_size| *page_size > 0)
+            .unwrap_or_else(|| {
+                tracing::warn!("Invalid DISCOVERY_PAGE_SIZE value");
+                DEFAULT_DISCOVERY_PAGE_SIZE
+            });
+
+        let blocklist = args
+            .discovery_token_blocklist
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| {
+                Address::from_str(s)
+                    .inspect_err(|err| {
+                        tracing::warn!(
+                            "Invalid address in DISCOVERY_TOKEN_BLOCKLIST {}: {}",
+                            s,
+                            err
+                        );
+                    })
+                    .ok()
+            })
+            .collect();
+
+        DiscoveryConfig {
+            block_range,
+            page_size,
+            blocklist,
         }
     }
 
+    pub fn admin_api_key(&self) -> Option<&str> {
+        self.admin_api_key.as_deref()
+    }
+
     pub fn multicall_address(&self) -> &Address {
         &self.multicall_address
     }
@@ -80,7 +776,52 @@ impl NetworkConfig {
         format!("wss://{}.g.alchemy.com/v2/{}", subdomain, self.api_key)
     }
 
-    pub fn weth_address(&self, network: &EvmNetwork) -> Address {
-        get_wrapped_address(network)
+    // wrapper -> underlying of wrapped tokens watched by every session of the network
+    pub fn wrapped_tokens(&self, network: &EvmNetwork) -> HashMap<Address, Address> {
+        self.wrapped_tokens
+            .get(network)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn wrapped_token_set(&self, network: &EvmNetwork) -> HashSet<Address> {
+        wrapped_token_set(&self.wrapped_tokens(network), *network)
+    }
+}
+
+/// Wrappers and their ERC20 underlyings, every session watches them
+/// without counting them against its token limit
+pub fn wrapped_token_set(
+    wrapped_tokens: &HashMap<Address, Address>,
+    network: EvmNetwork,
+) -> HashSet<Address> {
+    let native_address = network.native_token_address();
+    wrapped_tokens
+        .iter()
+        .flat_map(|(wrapper, underlying)| [*wrapper, *underlying])
+        .filter(|token| *token != native_address)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::address;
+
+    const WETH: Address = address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+    const WSTETH: Address = address!("0x7f39C581F595B53c5cb19bD0b3f8dA6c935E2Ca0");
+    const STETH: Address = address!("0xae7ab96520DE3A18E5e111B5EaAb095312D7fE84");
+
+    #[test]
+    fn wrapped_token_set_skips_native_underlying() {
+        let wrapped = HashMap::from([
+            (WETH, EvmNetwork::Eth.native_token_address()),
+            (WSTETH, STETH),
+        ]);
+
+        assert_eq!(
+            wrapped_token_set(&wrapped, EvmNetwork::Eth),
+            HashSet::from([WETH, WSTETH, STETH])
+        );
     }
 }
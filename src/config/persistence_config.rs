@@ -0,0 +1,13 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Settings of the on-disk session store
+#[derive(Debug, Clone)]
+pub struct PersistenceConfig {
+    /// Directory of the store, persistence is disabled if not set
+    pub path: Option<PathBuf>,
+    /// How often sessions are saved
+    pub flush_interval: Duration,
+    /// Restored sessions lagging behind more blocks take a full snapshot update instead of backfill
+    pub max_backfill_blocks: u64,
+}
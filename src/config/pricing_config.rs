@@ -0,0 +1,12 @@
+use std::time::Duration;
+
+/// Settings of USD valuation, feeds are loaded separately (PRICE_FEEDS / PRICE_FEEDS_PATH)
+#[derive(Debug, Clone, Copy)]
+pub struct PricingConfig {
+    /// Window of the Uniswap V3 TWAP fallback
+    pub twap_window: Duration,
+    /// Chainlink answers older than this are stale, the TWAP is used instead
+    pub max_price_age: Duration,
+    /// Change of the total value (basis points) which is published as `price_update`
+    pub update_threshold_bps: u32,
+}
@@ -0,0 +1,42 @@
+/// Global limits on sessions and resources used by their watchers, 0 means no limit
+#[derive(Debug, Clone, Copy)]
+pub struct SessionLimits {
+    pub max_sessions: usize,
+    pub max_sessions_per_owner: usize,
+    /// Watched tokens across all sessions
+    pub max_total_tokens: usize,
+}
+
+impl SessionLimits {
+    pub fn is_exceeded(limit: usize, value: usize) -> bool {
+        limit != 0 && value > limit
+    }
+
+    // headroom for metrics, -1 if there is no limit
+    pub fn headroom(limit: usize, value: usize) -> f64 {
+        if limit == 0 {
+            return -1.0;
+        }
+
+        limit.saturating_sub(value) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_limit_is_never_exceeded() {
+        assert!(!SessionLimits::is_exceeded(0, usize::MAX));
+        assert!(!SessionLimits::is_exceeded(2, 2));
+        assert!(SessionLimits::is_exceeded(2, 3));
+    }
+
+    #[test]
+    fn headroom_of_limit() {
+        assert_eq!(SessionLimits::headroom(0, 5), -1.0);
+        assert_eq!(SessionLimits::headroom(10, 4), 6.0);
+        assert_eq!(SessionLimits::headroom(10, 12), 0.0);
+    }
+}
@@ -0,0 +1,44 @@
+use crate::domain::EvmNetwork;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// RPC method tracing calls of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceMethod {
+    /// `trace_block` (Erigon, Nethermind, Reth)
+    TraceBlock,
+    /// `debug_traceBlockByNumber` with the `callTracer` (Geth)
+    DebugTraceBlock,
+}
+
+impl TraceMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TraceMethod::TraceBlock => "trace",
+            TraceMethod::DebugTraceBlock => "debug",
+        }
+    }
+}
+
+impl FromStr for TraceMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "trace" => Ok(TraceMethod::TraceBlock),
+            "debug" => Ok(TraceMethod::DebugTraceBlock),
+            other => Err(format!("unknown trace method {other}")),
+        }
+    }
+}
+
+/// Settings of internal native transfer detection
+#[derive(Debug, Clone)]
+pub struct TraceConfig {
+    /// Networks whose blocks are traced, with the method of the network
+    pub networks: HashMap<EvmNetwork, TraceMethod>,
+    pub poll_interval: Duration,
+    /// Blocks traced per poll, older blocks of a larger gap are left to the snapshot update
+    pub max_blocks_per_poll: u64,
+}
@@ -0,0 +1,193 @@
+use alloy::transports::http::reqwest::Url;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Delivery settings of session webhooks
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Timeout of one request
+    pub timeout: Duration,
+    /// Attempts per event including the first request
+    pub max_attempts: u32,
+    pub retry_initial_delay: Duration,
+    pub retry_max_delay: Duration,
+    /// Hosts webhooks may be sent to, any public host if empty
+    pub allowed_hosts: HashSet<String>,
+    /// Loopback, private and link-local destinations are allowed (local development)
+    pub allow_private_hosts: bool,
+    /// Time a session with a webhook lives without SSE clients
+    pub session_ttl: Duration,
+}
+
+impl WebhookConfig {
+    // exponential backoff: initial_delay * 2^(attempt - 1) capped by max_delay
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        self.retry_initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.retry_max_delay)
+    }
+
+    // any API key can register a webhook, so internal destinations are rejected
+    // names resolving to internal addresses are rejected by the resolver of the webhook client
+    pub fn check_url(&self, url: &Url) -> Result<(), String> {
+        let Some(host) = url.host_str() else {
+            return Err("url has no host".to_string());
+        };
+
+        let host = host.to_lowercase();
+        if !self.allowed_hosts.is_empty() && !self.allowed_hosts.contains(&host) {
+            return Err(format!("host {host} is not allowed"));
+        }
+
+        if self.allow_private_hosts {
+            return Ok(());
+        }
+
+        // IPv6 hosts are in brackets
+        let is_public = match IpAddr::from_str(host.trim_start_matches('[').trim_end_matches(']')) {
+            Ok(ip) => is_public_ip(ip),
+            Err(_) => host != "localhost" && !host.ends_with(".localhost"),
+        };
+        if !is_public {
+            return Err(format!("host {host} is not public"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Address reachable from the internet (not loopback, private, link-local, shared or reserved)
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    // 100.64.0.0/10 - carrier-grade NAT
+    let is_shared = first == 100 && (second & 0xc0) == 64;
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || is_shared
+        || first == 0
+        || first >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    // fc00::/7 - unique local, fe80::/10 - link-local
+    let is_unique_local = (first & 0xfe00) == 0xfc00;
+    let is_link_local = (first & 0xffc0) == 0xfe80;
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || is_unique_local
+        || is_link_local)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> WebhookConfig {
+        WebhookConfig {
+            timeout: Duration::from_secs(5),
+            max_attempts: 5,
+            retry_initial_delay: Duration::from_millis(500),
+            retry_max_delay: Duration::from_secs(4),
+            allowed_hosts: HashSet::new(),
+            allow_private_hosts: false,
+            session_ttl: Duration::from_secs(60),
+        }
+    }
+
+    fn check(config: &WebhookConfig, url: &str) -> Result<(), String> {
+        config.check_url(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_max() {
+        let config = config();
+        let delays: Vec<u64> = (1..=6)
+            .map(|attempt| config.retry_delay(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, vec![500, 1000, 2000, 4000, 4000, 4000]);
+        assert_eq!(config.retry_delay(0), Duration::from_millis(500));
+        assert_eq!(config.retry_delay(u32::MAX), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn rejects_internal_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in [
+            "8.8.8.8",
+            "93.184.216.34",
+            "2606:4700::1111",
+            "::ffff:8.8.8.8",
+        ] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn checks_url_host() {
+        let config = config();
+        assert!(check(&config, "https://example.com/hook").is_ok());
+        assert!(check(&config, "https://8.8.8.8/hook").is_ok());
+        for url in [
+            "http://localhost:8080/hook",
+            "http://api.localhost/hook",
+            "http://127.0.0.1/hook",
+            "http://[::1]/hook",
+            "http://[::ffff:192.168.0.1]/hook",
+            "http://169.254.169.254/latest/meta-data",
+        ] {
+            assert!(check(&config, url).is_err(), "{url}");
+        }
+
+        let private = WebhookConfig {
+            allow_private_hosts: true,
+            ..config.clone()
+        };
+        assert!(check(&private, "http://127.0.0.1/hook").is_ok());
+
+        let allowed = WebhookConfig {
+            allowed_hosts: HashSet::from(["hooks.example.com".to_string()]),
+            ..config
+        };
+        assert!(check(&allowed, "https://hooks.example.com/hook").is_ok());
+        assert!(check(&allowed, "https://example.com/hook").is_err());
+    }
+}
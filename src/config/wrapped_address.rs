@@ -1,6 +1,7 @@
 use crate::domain::EvmNetwork;
 use alloy::primitives::{address, Address};
 
+// WETH9 of the network, wrapped tokens from WETH_CONTRACT_ADDRESSES are watched besides it
 pub fn get_wrapped_address(network: &EvmNetwork) -> Address {
     match network {
         EvmNetwork::Eth => address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
@@ -0,0 +1,320 @@
+use crate::domain::errors::AlertError;
+use alloy::primitives::utils::{parse_units, ParseUnits};
+use alloy::primitives::{Address, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// How the balance is compared with the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertComparator {
+    Below,
+    Above,
+}
+
+impl AlertComparator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertComparator::Below => "below",
+            AlertComparator::Above => "above",
+        }
+    }
+}
+
+/// Alert rule sent by clients
+/// thresholds are raw units, or decimal strings (e.g. "1500.5") if `decimals` is set
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRuleRequest {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub token: Address,
+    pub comparator: AlertComparator,
+    pub threshold: String,
+    /// The alert is resolved when the balance moves back past the threshold by more than this
+    #[serde(default)]
+    pub hysteresis: Option<String>,
+    #[serde(default)]
+    pub decimals: Option<u8>,
+}
+
+/// Alert rule of a session with its state, thresholds are raw units
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRule {
+    pub id: String,
+    pub token: Address,
+    pub comparator: AlertComparator,
+    pub threshold: U256,
+    pub hysteresis: U256,
+    #[serde(default)]
+    pub triggered: bool,
+}
+
+/// Payload of alert_triggered / alert_resolved events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertEvent {
+    pub rule_id: String,
+    pub token: Address,
+    pub comparator: AlertComparator,
+    pub threshold: String,
+    pub balance: String,
+    pub block_number: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertTransition {
+    Triggered,
+    Resolved,
+}
+
+impl AlertRule {
+    pub fn from_request(request: AlertRuleRequest) -> Result<Self, AlertError> {
+        let parse = |value: &str| -> Result<U256, AlertError> {
+            let parsed = match request.decimals {
+                // negative amounts are parsed as signed units
+                Some(decimals) => match parse_units(value, decimals) {
+                    Ok(ParseUnits::U256(units)) => Some(units),
+                    _ => None,
+                },
+                None => U256::from_str(value).ok(),
+            };
+            parsed.ok_or_else(|| AlertError::InvalidAmount(value.to_string()))
+        };
+
+        let threshold = parse(&request.threshold)?;
+        let hysteresis = match &request.hysteresis {
+            Some(hysteresis) => parse(hysteresis)?,
+            None => U256::ZERO,
+        };
+
+        let id = request.id.unwrap_or_else(|| {
+            format!(
+                "{}:{}:{}",
+                request.token,
+                request.comparator.as_str(),
+                threshold
+            )
+        });
+
+        Ok(Self {
+            id,
+            token: request.token,
+            comparator: request.comparator,
+            threshold,
+            hysteresis,
+            triggered: false,
+        })
+    }
+
+    // rules with unique ids, limited by max_rules
+    pub fn from_requests(
+        requests: Vec<AlertRuleRequest>,
+        max_rules: usize,
+    ) -> Result<Vec<Self>, AlertError> {
+        if requests.len() > max_rules {
+            return Err(AlertError::TooManyRules(max_rules));
+        }
+
+        let rules = requests
+            .into_iter()
+            .map(Self::from_request)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut ids = HashSet::new();
+        if let Some(rule) = rules.iter().find(|rule| !ids.insert(rule.id.as_str())) {
+            return Err(AlertError::DuplicateId(rule.id.clone()));
+        }
+
+        Ok(rules)
+    }
+
+    pub fn same_definition(&self, other: &AlertRule) -> bool {
+        self.id == other.id
+            && self.token == other.token
+            && self.comparator == other.comparator
+            && self.threshold == other.threshold
+            && self.hysteresis == other.hysteresis
+    }
+
+    // triggers when the balance crosses the threshold,
+    // resolves when it is back on the other side by more than hysteresis
+    pub fn evaluate(&mut self, balance: U256) -> Option<AlertTransition> {
+        let (crossed, recovered) = match self.comparator {
+            AlertComparator::Below => (
+                balance < self.threshold,
+                balance >= self.threshold.saturating_add(self.hysteresis),
+            ),
+            AlertComparator::Above => (
+                balance > self.threshold,
+                balance <= self.threshold.saturating_sub(self.hysteresis),
+            ),
+        };
+
+        match (self.triggered, crossed, recovered) {
+            (false, true, _) => {
+                self.triggered = true;
+                Some(AlertTransition::Triggered)
+            }
+            (true, _, true) => {
+                self.triggered = false;
+                Some(AlertTransition::Resolved)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn event(&self, balance: U256, block_number: U256) -> AlertEvent {
+        AlertEvent {
+            rule_id: self.id.clone(),
+            token: self.token,
+            comparator: self.comparator,
+            threshold: self.threshold.to_string(),
+            balance: balance.to_string(),
+            block_number: block_number.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(comparator: AlertComparator, threshold: u64, hysteresis: u64) -> AlertRule {
+        AlertRule {
+            id: "rule".to_string(),
+            token: Address::ZERO,
+            comparator,
+            threshold: U256::from(threshold),
+            hysteresis: U256::from(hysteresis),
+            triggered: false,
+        }
+    }
+
+    fn request(
+        threshold: &str,
+        hysteresis: Option<&str>,
+        decimals: Option<u8>,
+    ) -> AlertRuleRequest {
+        AlertRuleRequest {
+            id: None,
+            token: Address::ZERO,
+            comparator: AlertComparator::Below,
+            threshold: threshold.to_string(),
+            hysteresis: hysteresis.map(str::to_string),
+            decimals,
+        }
+    }
+
+    #[test]
+    fn below_triggers_once_and_resolves_past_hysteresis() {
+        let mut rule = rule(AlertComparator::Below, 100, 10);
+
+        assert_eq!(rule.evaluate(U256::from(100)), None);
+        assert_eq!(
+            rule.evaluate(U256::from(99)),
+            Some(AlertTransition::Triggered)
+        );
+        assert_eq!(rule.evaluate(U256::from(50)), None);
+        // back over the threshold, but within the hysteresis band
+        assert_eq!(rule.evaluate(U256::from(105)), None);
+        assert_eq!(rule.evaluate(U256::from(109)), None);
+        assert_eq!(
+            rule.evaluate(U256::from(110)),
+            Some(AlertTransition::Resolved)
+        );
+        assert_eq!(rule.evaluate(U256::from(110)), None);
+        assert_eq!(
+            rule.evaluate(U256::from(99)),
+            Some(AlertTransition::Triggered)
+        );
+    }
+
+    #[test]
+    fn above_triggers_once_and_resolves_past_hysteresis() {
+        let mut rule = rule(AlertComparator::Above, 100, 10);
+
+        assert_eq!(rule.evaluate(U256::from(100)), None);
+        assert_eq!(
+            rule.evaluate(U256::from(101)),
+            Some(AlertTransition::Triggered)
+        );
+        assert_eq!(rule.evaluate(U256::from(200)), None);
+        assert_eq!(rule.evaluate(U256::from(91)), None);
+        assert_eq!(
+            rule.evaluate(U256::from(90)),
+            Some(AlertTransition::Resolved)
+        );
+    }
+
+    #[test]
+    fn above_with_hysteresis_over_threshold_resolves_at_zero() {
+        let mut rule = rule(AlertComparator::Above, 10, 50);
+
+        assert_eq!(
+            rule.evaluate(U256::from(11)),
+            Some(AlertTransition::Triggered)
+        );
+        assert_eq!(rule.evaluate(U256::from(1)), None);
+        assert_eq!(rule.evaluate(U256::ZERO), Some(AlertTransition::Resolved));
+    }
+
+    #[test]
+    fn below_with_saturated_band_resolves_at_max() {
+        let mut rule = rule(AlertComparator::Below, 100, 0);
+        rule.hysteresis = U256::MAX;
+
+        assert_eq!(
+            rule.evaluate(U256::from(1)),
+            Some(AlertTransition::Triggered)
+        );
+        assert_eq!(rule.evaluate(U256::MAX - U256::from(1)), None);
+        assert_eq!(rule.evaluate(U256::MAX), Some(AlertTransition::Resolved));
+    }
+
+    #[test]
+    fn parses_raw_and_decimal_amounts() {
+        let rule = AlertRule::from_request(request("1000", None, None)).unwrap();
+        assert_eq!(rule.threshold, U256::from(1000));
+        assert_eq!(rule.hysteresis, U256::ZERO);
+
+        let rule = AlertRule::from_request(request("1500.5", Some("0.25"), Some(6))).unwrap();
+        assert_eq!(rule.threshold, U256::from(1_500_500_000u64));
+        assert_eq!(rule.hysteresis, U256::from(250_000));
+        assert_eq!(rule.id, format!("{}:below:1500500000", Address::ZERO));
+    }
+
+    #[test]
+    fn rejects_invalid_amounts() {
+        for (threshold, hysteresis, decimals) in [
+            ("-1", None, Some(6)),
+            ("-1", None, None),
+            ("1", Some("-0.5"), Some(6)),
+            ("1.5", None, None),
+            ("abc", None, Some(18)),
+        ] {
+            assert!(
+                matches!(
+                    AlertRule::from_request(request(threshold, hysteresis, decimals)),
+                    Err(AlertError::InvalidAmount(_))
+                ),
+                "{threshold} / {hysteresis:?} / {decimals:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_ids_and_too_many_rules() {
+        let requests = vec![request("1", None, None), request("1", None, None)];
+        assert!(matches!(
+            AlertRule::from_requests(requests.clone(), 10),
+            Err(AlertError::DuplicateId(_))
+        ));
+        assert!(matches!(
+            AlertRule::from_requests(requests, 1),
+            Err(AlertError::TooManyRules(1))
+        ));
+    }
+}
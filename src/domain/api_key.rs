@@ -0,0 +1,21 @@
+use serde::Deserialize;
+
+/// API key with its limits, loaded from API_KEYS / API_KEYS_PATH
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    /// Secret value sent by clients
+    pub key: String,
+    /// Name used in logs and metrics labels (the key itself is never exposed)
+    pub name: String,
+    #[serde(default)]
+    pub max_sessions: Option<usize>,
+    #[serde(default)]
+    pub max_tokens_per_session: Option<usize>,
+    /// Chain ids allowed for the key, all chains if empty
+    #[serde(default)]
+    pub allowed_chains: Vec<u64>,
+    /// Origins allowed for the key, any origin if empty
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+}
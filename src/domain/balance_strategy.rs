@@ -0,0 +1,57 @@
+use alloy::primitives::{keccak256, Address, B256};
+use serde::{Deserialize, Serialize};
+
+/// When the balance of a token is refreshed besides its Transfer logs and the periodic snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RefreshStrategy {
+    /// On every log of the event emitted by the contract (e.g. Lido `TokenRebased`)
+    RebaseEvent { emitter: Address, event: String },
+    /// On every new block
+    EveryBlock,
+    /// Every N seconds (interest bearing tokens)
+    IntervalSecs(u64),
+}
+
+impl RefreshStrategy {
+    // topic0 of the rebase event, the signature is `Name(type1,type2,...)`
+    pub fn rebase_topic(&self) -> Option<B256> {
+        match self {
+            RefreshStrategy::RebaseEvent { event, .. } => Some(keccak256(event.as_bytes())),
+            _ => None,
+        }
+    }
+}
+
+/// How shares of a share-based token are converted to the underlying amount
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareKind {
+    /// balanceOf is the underlying amount, shares are `sharesOf(owner)` (stETH)
+    Lido,
+    /// balanceOf is shares, the underlying is `convertToAssets(shares)` (wstETH-like vaults)
+    Erc4626,
+    /// balanceOf is shares, the underlying is `shares * exchangeRateStored() / 1e18` (cTokens)
+    Compound,
+}
+
+/// Balance change strategy of a token
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceStrategy {
+    pub chain_id: u64,
+    pub token: Address,
+    #[serde(default)]
+    pub refresh: Option<RefreshStrategy>,
+    #[serde(default)]
+    pub shares: Option<ShareKind>,
+}
+
+/// Balance of a share-based token in shares and in the underlying
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareBalance {
+    pub shares: String,
+    pub underlying: String,
+    pub block_number: String,
+}
@@ -8,3 +8,21 @@ pub enum EvmError {
     #[error("Network id should be integer")]
     InvalidNetworkId,
 }
+
+#[derive(Debug, Clone, Error)]
+pub enum AlertError {
+    #[error("Invalid alert amount {0}")]
+    InvalidAmount(String),
+
+    #[error("Alert rule id {0} is duplicated")]
+    DuplicateId(String),
+
+    #[error("Too many alert rules, max {0} per session")]
+    TooManyRules(usize),
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum FinalityError {
+    #[error("Too many confirmations, max {0}")]
+    TooManyConfirmations(u64),
+}
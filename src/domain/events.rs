@@ -1,6 +1,9 @@
-use crate::domain::EvmNetwork;
+use crate::domain::{
+    AlertEvent, Erc1155Update, EvmNetwork, Finality, NftUpdate, PendingBalanceEvent, ShareBalance,
+    Valuation,
+};
 use alloy::primitives::Address;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Display;
 
@@ -18,10 +21,50 @@ impl Display for SubscriptionKey {
 }
 
 /// Events sent to SSE clients
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BalanceEvent {
     /// Full balance snapshot (all tokens)
     BalanceUpdate(HashMap<Address, String>),
+    /// Full balance snapshot sent to a new client, with the last valuation if pricing is enabled
+    Snapshot {
+        balances: HashMap<Address, String>,
+        valuation: Option<Valuation>,
+    },
+    /// Balances at the finality level of the session (not `latest`)
+    ConfirmedUpdate {
+        finality: Finality,
+        balances: HashMap<Address, String>,
+        block_number: String,
+    },
+    /// Tokens are not watched anymore (removed from the session)
+    TokensRemoved(Vec<Address>),
     /// Error event
     Error { code: u16, message: String },
+    /// Log source of the network can't reconnect, balance updates are delayed
+    Degraded { message: String },
+    /// Log source is back after degradation, snapshot is resynced
+    Recovered { message: String },
+    /// Balance crossed the threshold of an alert rule
+    AlertTriggered(AlertEvent),
+    /// Balance is back past the threshold (and hysteresis)
+    AlertResolved(AlertEvent),
+    /// Balances projected from a pending transaction, or its outcome
+    PendingBalance(PendingBalanceEvent),
+    /// USD valuation of the session changed materially
+    PriceUpdate(Valuation),
+    /// Token IDs added to / removed from watched NFT collections
+    NftUpdate(NftUpdate),
+    /// Balances of watched ERC-1155 tokens which changed
+    Erc1155Update(Erc1155Update),
+    /// Shares and underlying amounts of share-based tokens whose balance changed
+    SharesUpdate(HashMap<Address, ShareBalance>),
+}
+
+impl BalanceEvent {
+    pub fn is_alert(&self) -> bool {
+        matches!(
+            self,
+            BalanceEvent::AlertTriggered(_) | BalanceEvent::AlertResolved(_)
+        )
+    }
 }
@@ -0,0 +1,98 @@
+use crate::domain::errors::FinalityError;
+use crate::domain::EvmNetwork;
+use serde::{Deserialize, Serialize};
+
+/// Confirmation level of balances: "latest", "safe", "finalized" or {"confirmations": N}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Finality {
+    /// Head of the chain, balances could be reorged
+    #[default]
+    Latest,
+    /// N blocks behind the head
+    Confirmations(u64),
+    Safe,
+    Finalized,
+}
+
+impl Finality {
+    // zero confirmations is the head
+    pub fn validate(self, max_confirmations: u64) -> Result<Self, FinalityError> {
+        match self {
+            Finality::Confirmations(0) => Ok(Finality::Latest),
+            Finality::Confirmations(confirmations) if confirmations > max_confirmations => {
+                Err(FinalityError::TooManyConfirmations(max_confirmations))
+            }
+            finality => Ok(finality),
+        }
+    }
+
+    pub fn is_latest(self) -> bool {
+        self == Finality::Latest
+    }
+
+    // rollups: the sequencer orders L2 blocks right away, they can't be reorged by L2 confirmations,
+    // only by the L1 batch, so confirmations wait for the batch posted to L1 (the `safe` tag of the node)
+    pub fn effective(self, network: EvmNetwork) -> Self {
+        match self {
+            Finality::Confirmations(_) if network.has_l1_batch_finality() => Finality::Safe,
+            finality => finality,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn validates_confirmations() {
+        assert_eq!(
+            Finality::Confirmations(0).validate(64).unwrap(),
+            Finality::Latest
+        );
+        assert_eq!(
+            Finality::Confirmations(64).validate(64).unwrap(),
+            Finality::Confirmations(64)
+        );
+        assert!(matches!(
+            Finality::Confirmations(65).validate(64),
+            Err(FinalityError::TooManyConfirmations(64))
+        ));
+        assert_eq!(
+            Finality::Finalized.validate(0).unwrap(),
+            Finality::Finalized
+        );
+    }
+
+    #[test]
+    fn rollup_confirmations_wait_for_l1_batch() {
+        assert_eq!(
+            Finality::Confirmations(12).effective(EvmNetwork::Arbitrum),
+            Finality::Safe
+        );
+        assert_eq!(
+            Finality::Confirmations(12).effective(EvmNetwork::Eth),
+            Finality::Confirmations(12)
+        );
+        assert_eq!(
+            Finality::Finalized.effective(EvmNetwork::Arbitrum),
+            Finality::Finalized
+        );
+    }
+
+    #[test]
+    fn parses_levels() {
+        assert_eq!(
+            serde_json::from_value::<Finality>(json!("safe")).unwrap(),
+            Finality::Safe
+        );
+        assert_eq!(
+            serde_json::from_value::<Finality>(json!({"confirmations": 12})).unwrap(),
+            Finality::Confirmations(12)
+        );
+        assert_eq!(json!(Finality::Latest), json!("latest"));
+        assert!(serde_json::from_value::<Finality>(json!("pending")).is_err());
+    }
+}
@@ -1,8 +1,22 @@
+pub mod alert;
+pub mod api_key;
+pub mod balance_strategy;
 pub mod errors;
 pub mod events;
+pub mod finality;
 pub mod network;
+pub mod nft;
+pub mod pending;
+pub mod price;
 pub mod token;
 
+pub use alert::*;
+pub use api_key::*;
+pub use balance_strategy::*;
 pub use events::*;
+pub use finality::*;
 pub use network::*;
+pub use nft::*;
+pub use pending::*;
+pub use price::*;
 pub use token::*;
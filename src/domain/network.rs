@@ -26,6 +26,11 @@ impl EvmNetwork {
     pub fn native_token_address(self) -> Address {
         NATIVE_ADDRESS
     }
+
+    // `safe` / `finalized` blocks of the node follow the L1 batches of the rollup
+    pub fn has_l1_batch_finality(self) -> bool {
+        matches!(self, EvmNetwork::Arbitrum)
+    }
 }
 
 impl TryFrom<u64> for EvmNetwork {
@@ -0,0 +1,221 @@
+use alloy::primitives::{Address, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Tokens of an ERC-721 collection held by the owner
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NftHolding {
+    /// balanceOf of the owner, larger than token_ids if the collection is not enumerable
+    /// and tokens were received before the session
+    pub balance: U256,
+    pub token_ids: BTreeSet<U256>,
+    pub block_number: U256,
+}
+
+/// Token of an ERC-1155 contract watched by a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Erc1155Token {
+    pub contract: Address,
+    pub id: U256,
+}
+
+/// Payload of erc1155_update events: changed balances by contract and token ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Erc1155Update {
+    pub balances: HashMap<Address, HashMap<String, String>>,
+    pub block_number: String,
+}
+
+impl Erc1155Update {
+    pub fn new(balances: &HashMap<Erc1155Token, U256>, block_number: U256) -> Self {
+        let mut by_contract: HashMap<Address, HashMap<String, String>> = HashMap::new();
+        for (token, amount) in balances {
+            by_contract
+                .entry(token.contract)
+                .or_default()
+                .insert(token.id.to_string(), amount.to_string());
+        }
+
+        Self {
+            balances: by_contract,
+            block_number: block_number.to_string(),
+        }
+    }
+
+    // balances of the update keyed by token, entries which can't be parsed are skipped
+    pub fn token_balances(&self) -> HashMap<Erc1155Token, U256> {
+        self.balances
+            .iter()
+            .flat_map(|(contract, balances)| {
+                balances.iter().filter_map(|(id, amount)| {
+                    let token = Erc1155Token {
+                        contract: *contract,
+                        id: id.parse().ok()?,
+                    };
+                    Some((token, amount.parse().ok()?))
+                })
+            })
+            .collect()
+    }
+}
+
+/// Changes of a collection in nft_update events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NftCollectionUpdate {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub balance: String,
+}
+
+/// Payload of nft_update events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NftUpdate {
+    pub collections: HashMap<Address, NftCollectionUpdate>,
+    pub block_number: String,
+}
+
+impl NftHolding {
+    // the update from the previous holding, None if nothing changed
+    pub fn update_from(&self, previous: Option<&NftHolding>) -> Option<NftCollectionUpdate> {
+        let empty = BTreeSet::new();
+        let previous_ids = previous.map_or(&empty, |previous| &previous.token_ids);
+
+        let added: Vec<String> = self
+            .token_ids
+            .difference(previous_ids)
+            .map(|token_id| token_id.to_string())
+            .collect();
+        let removed: Vec<String> = previous_ids
+            .difference(&self.token_ids)
+            .map(|token_id| token_id.to_string())
+            .collect();
+
+        let balance_changed = previous.is_none_or(|previous| previous.balance != self.balance);
+        if added.is_empty() && removed.is_empty() && !balance_changed {
+            return None;
+        }
+
+        Some(NftCollectionUpdate {
+            added,
+            removed,
+            balance: self.balance.to_string(),
+        })
+    }
+
+    // apply an update published by another replica
+    pub fn apply(&mut self, update: &NftCollectionUpdate, block_number: U256) {
+        for token_id in &update.removed {
+            if let Ok(token_id) = token_id.parse::<U256>() {
+                self.token_ids.remove(&token_id);
+            }
+        }
+        for token_id in &update.added {
+            if let Ok(token_id) = token_id.parse::<U256>() {
+                self.token_ids.insert(token_id);
+            }
+        }
+        if let Ok(balance) = update.balance.parse::<U256>() {
+            self.balance = balance;
+        }
+        self.block_number = self.block_number.max(block_number);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holding(balance: u64, token_ids: &[u64], block_number: u64) -> NftHolding {
+        NftHolding {
+            balance: U256::from(balance),
+            token_ids: token_ids.iter().map(|id| U256::from(*id)).collect(),
+            block_number: U256::from(block_number),
+        }
+    }
+
+    #[test]
+    fn erc1155_update_round_trips_balances() {
+        let token = |id: u64| Erc1155Token {
+            contract: Address::repeat_byte(0x11),
+            id: U256::from(id),
+        };
+        let balances = HashMap::from([(token(1), U256::from(5)), (token(2), U256::ZERO)]);
+
+        let update = Erc1155Update::new(&balances, U256::from(100));
+
+        assert_eq!(update.block_number, "100");
+        assert_eq!(update.balances[&Address::repeat_byte(0x11)]["1"], "5");
+        assert_eq!(update.token_balances(), balances);
+    }
+
+    #[test]
+    fn erc1155_update_skips_invalid_entries() {
+        let contract = Address::repeat_byte(0x11);
+        let update = Erc1155Update {
+            balances: HashMap::from([(
+                contract,
+                HashMap::from([
+                    ("1".to_string(), "5".to_string()),
+                    ("x".to_string(), "5".to_string()),
+                    ("2".to_string(), "-1".to_string()),
+                ]),
+            )]),
+            block_number: "100".to_string(),
+        };
+
+        let token = Erc1155Token {
+            contract,
+            id: U256::from(1),
+        };
+        assert_eq!(
+            update.token_balances(),
+            HashMap::from([(token, U256::from(5))])
+        );
+    }
+
+    #[test]
+    fn update_lists_added_and_removed_tokens() {
+        let previous = holding(2, &[1, 2], 100);
+        let current = holding(2, &[2, 3], 101);
+
+        let update = current.update_from(Some(&previous)).unwrap();
+
+        assert_eq!(update.added, vec!["3".to_string()]);
+        assert_eq!(update.removed, vec!["1".to_string()]);
+        assert_eq!(update.balance, "2");
+    }
+
+    #[test]
+    fn update_of_unchanged_holding_is_none() {
+        let previous = holding(3, &[1, 2], 100);
+
+        assert!(holding(3, &[1, 2], 101)
+            .update_from(Some(&previous))
+            .is_none());
+        // balance of a non enumerable collection changes without known tokens
+        assert_eq!(
+            holding(4, &[1, 2], 101)
+                .update_from(Some(&previous))
+                .unwrap()
+                .balance,
+            "4"
+        );
+        // first holding is always published
+        assert!(holding(0, &[], 100).update_from(None).is_some());
+    }
+
+    #[test]
+    fn applied_update_reproduces_holding() {
+        let mut replica = holding(2, &[1, 2], 100);
+        let current = holding(3, &[2, 5, 7], 105);
+        let update = current.update_from(Some(&replica)).unwrap();
+
+        replica.apply(&update, current.block_number);
+
+        assert_eq!(replica, current);
+    }
+}
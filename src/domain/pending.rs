@@ -0,0 +1,38 @@
+use alloy::primitives::{Address, B256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// State of a pending transaction of the owner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PendingStatus {
+    /// Broadcast, not mined yet
+    Pending,
+    /// Mined successfully
+    Confirmed,
+    /// Mined and failed
+    Reverted,
+    /// Replaced by another transaction with the same nonce or not mined in time
+    Dropped,
+}
+
+impl PendingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingStatus::Pending => "pending",
+            PendingStatus::Confirmed => "confirmed",
+            PendingStatus::Reverted => "reverted",
+            PendingStatus::Dropped => "dropped",
+        }
+    }
+}
+
+/// Payload of pending_balance events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingBalanceEvent {
+    pub tx_hash: B256,
+    pub status: PendingStatus,
+    /// Balances of tokens touched by the transaction projected with all still pending transactions
+    pub balances: HashMap<Address, String>,
+}
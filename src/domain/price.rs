@@ -0,0 +1,109 @@
+use alloy::primitives::Address;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Uniswap V3 pool used as the TWAP fallback of a feed
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UniswapV3Source {
+    pub pool: Address,
+    /// The other token of the pool, it should have its own feed
+    pub quote_token: Address,
+    /// Token of the pool priced by the TWAP, the feed token by default
+    /// (the wrapped token for the native one)
+    #[serde(default)]
+    pub base_token: Option<Address>,
+}
+
+/// USD price sources of a token
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceFeed {
+    pub chain_id: u64,
+    pub token: Address,
+    pub decimals: u8,
+    /// Chainlink <TOKEN>/USD aggregator
+    #[serde(default)]
+    pub chainlink: Option<Address>,
+    #[serde(default)]
+    pub uniswap_v3: Option<UniswapV3Source>,
+}
+
+/// USD valuation of the session balances at a block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Valuation {
+    /// USD value per token, tokens without a price are omitted
+    pub values: HashMap<Address, f64>,
+    /// USD price of one token
+    pub prices: HashMap<Address, f64>,
+    pub total_usd: f64,
+    pub block_number: String,
+}
+
+impl Valuation {
+    // the total moved by more than threshold_bps, or other tokens are priced
+    pub fn changed_materially(&self, previous: &Valuation, threshold_bps: u32) -> bool {
+        if self.values.len() != previous.values.len()
+            || self
+                .values
+                .keys()
+                .any(|token| !previous.values.contains_key(token))
+        {
+            return true;
+        }
+
+        if previous.total_usd == 0.0 {
+            return self.total_usd != 0.0;
+        }
+
+        let change_bps =
+            ((self.total_usd - previous.total_usd) / previous.total_usd).abs() * 10_000.0;
+        change_bps > f64::from(threshold_bps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::address;
+
+    const USDC: Address = address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const WETH: Address = address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+
+    fn valuation(values: &[(Address, f64)]) -> Valuation {
+        let values: HashMap<Address, f64> = values.iter().copied().collect();
+        Valuation {
+            total_usd: values.values().sum(),
+            prices: values.clone(),
+            values,
+            block_number: "1".to_string(),
+        }
+    }
+
+    #[test]
+    fn total_change_over_threshold_is_material() {
+        let previous = valuation(&[(USDC, 1_000.0)]);
+
+        assert!(!valuation(&[(USDC, 1_004.0)]).changed_materially(&previous, 50));
+        assert!(valuation(&[(USDC, 1_006.0)]).changed_materially(&previous, 50));
+        assert!(valuation(&[(USDC, 994.0)]).changed_materially(&previous, 50));
+    }
+
+    #[test]
+    fn other_priced_tokens_are_material() {
+        let previous = valuation(&[(USDC, 1_000.0)]);
+
+        assert!(valuation(&[(USDC, 1_000.0), (WETH, 0.0)]).changed_materially(&previous, 50));
+        assert!(valuation(&[(WETH, 1_000.0)]).changed_materially(&previous, 50));
+        assert!(valuation(&[]).changed_materially(&previous, 50));
+    }
+
+    #[test]
+    fn change_from_zero_total() {
+        let previous = valuation(&[(USDC, 0.0)]);
+
+        assert!(!valuation(&[(USDC, 0.0)]).changed_materially(&previous, 50));
+        assert!(valuation(&[(USDC, 1.0)]).changed_materially(&previous, 50));
+    }
+}
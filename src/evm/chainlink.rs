@@ -0,0 +1,20 @@
+use alloy::sol;
+
+sol! {
+    // Chainlink AggregatorV3Interface
+    #[sol(rpc)]
+    contract ChainlinkAggregator {
+        function decimals() external view returns (uint8);
+
+        function latestRoundData()
+            external
+            view
+            returns (
+                uint80 roundId,
+                int256 answer,
+                uint256 startedAt,
+                uint256 updatedAt,
+                uint80 answeredInRound
+            );
+    }
+}
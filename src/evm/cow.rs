@@ -0,0 +1,39 @@
+use alloy::sol;
+
+sol! {
+   // GPv2Order.Data
+   #[derive(Debug)]
+   struct OrderData {
+        address sellToken;
+        address buyToken;
+        address receiver;
+        uint256 sellAmount;
+        uint256 buyAmount;
+        uint32 validTo;
+        bytes32 appData;
+        uint256 feeAmount;
+        bytes32 kind;
+        bool partiallyFillable;
+        bytes32 sellTokenBalance;
+        bytes32 buyTokenBalance;
+   }
+
+   // ICoWSwapOnchainOrders.OnchainSignature
+   #[derive(Debug)]
+   struct OnchainSignature {
+        uint8 scheme;
+        bytes data;
+   }
+
+   contract GPv2Settlement {
+        // owner is topic1, native buy token is 0xEeee...EEeE
+        #[derive(Debug)]
+        event Trade(address indexed owner, address sellToken, address buyToken, uint256 sellAmount, uint256 buyAmount, uint256 feeAmount, bytes orderUid);
+   }
+
+   contract CoWSwapEthFlow {
+        // the sender sends the native token with the order, it is sold as the wrapped one
+        #[derive(Debug)]
+        event OrderPlacement(address indexed sender, OrderData order, OnchainSignature signature, bytes data);
+   }
+}
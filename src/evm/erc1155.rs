@@ -0,0 +1,15 @@
+use alloy::sol;
+
+sol! {
+   #[sol(rpc)]
+   contract ERC1155 {
+        function balanceOf(address account, uint256 id) public view returns (uint256);
+
+        // the owner is in topic2 (from) or topic3 (to), topic1 is the operator
+        #[derive(Debug)]
+        event TransferSingle(address indexed operator, address indexed from, address indexed to, uint256 id, uint256 value);
+
+        #[derive(Debug)]
+        event TransferBatch(address indexed operator, address indexed from, address indexed to, uint256[] ids, uint256[] values);
+   }
+}
@@ -5,6 +5,10 @@ sol! {
    contract ERC20 {
         function balanceOf(address owner) public view returns (uint256);
 
+        function transfer(address to, uint256 amount) public returns (bool);
+
+        function transferFrom(address from, address to, uint256 amount) public returns (bool);
+
         #[derive(Debug)]
         event Transfer(address indexed from, address indexed to, uint256 value);
    }
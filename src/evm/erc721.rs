@@ -0,0 +1,15 @@
+use alloy::sol;
+
+sol! {
+   #[sol(rpc)]
+   contract ERC721 {
+        function balanceOf(address owner) public view returns (uint256);
+
+        // ERC721Enumerable extension, reverts on collections without it
+        function tokenOfOwnerByIndex(address owner, uint256 index) public view returns (uint256);
+
+        // same signature hash as the ERC20 Transfer, tokenId is the 4th topic
+        #[derive(Debug)]
+        event Transfer(address indexed from, address indexed to, uint256 indexed tokenId);
+   }
+}
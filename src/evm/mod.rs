@@ -1,3 +1,10 @@
+pub mod chainlink;
+pub mod cow;
+pub mod erc1155;
 pub mod erc20;
+pub mod erc721;
 pub mod multicall3;
+pub mod shares;
+pub mod smart_account;
+pub mod uniswap_v3;
 pub mod wrapped;
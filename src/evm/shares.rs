@@ -0,0 +1,21 @@
+use alloy::sol;
+
+sol! {
+    // Lido stETH, balances are rebased from shares
+    #[sol(rpc)]
+    contract LidoShares {
+        function sharesOf(address account) external view returns (uint256);
+    }
+
+    // ERC-4626 tokenized vault
+    #[sol(rpc)]
+    contract ERC4626 {
+        function convertToAssets(uint256 shares) external view returns (uint256);
+    }
+
+    // Compound V2 cToken
+    #[sol(rpc)]
+    contract CToken {
+        function exchangeRateStored() external view returns (uint256);
+    }
+}
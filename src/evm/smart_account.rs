@@ -0,0 +1,20 @@
+use alloy::sol;
+
+sol! {
+   // Safe emits both events itself, so the owner is the emitter of the log
+   contract Safe {
+        // execTransaction succeeded, native transfers inside it are internal calls
+        #[derive(Debug)]
+        event ExecutionSuccess(bytes32 txHash, uint256 payment);
+
+        // native token received by the fallback (no Transfer log)
+        #[derive(Debug)]
+        event SafeReceived(address indexed sender, uint256 value);
+   }
+
+   // ERC-4337 EntryPoint, the account is the sender in topic2
+   contract EntryPoint {
+        #[derive(Debug)]
+        event UserOperationEvent(bytes32 indexed userOpHash, address indexed sender, address indexed paymaster, uint256 nonce, bool success, uint256 actualGasCost, uint256 actualGasUsed);
+   }
+}
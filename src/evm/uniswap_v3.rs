@@ -0,0 +1,12 @@
+use alloy::sol;
+
+sol! {
+    #[sol(rpc)]
+    contract UniswapV3Pool {
+        // cumulative values at each `secondsAgos` from the current block timestamp
+        function observe(uint32[] calldata secondsAgos)
+            external
+            view
+            returns (int56[] memory tickCumulatives, uint160[] memory secondsPerLiquidityCumulativeX128s);
+    }
+}
@@ -3,6 +3,10 @@ use alloy::sol;
 sol! {
    #[sol(rpc)]
    contract WrappedToken {
+    function deposit() public payable;
+
+    function withdraw(uint256 wad) public;
+
     // when user wrap/unwrap token - it emits Deposit/Withdrawal event (not transfer as for erc20)
     #[derive(Debug)]
     event Deposit(address indexed dst, uint256 wad);
@@ -5,6 +5,7 @@ mod args;
 mod config;
 mod domain;
 mod evm;
+mod middleware;
 mod routes;
 mod services;
 mod tracing;
@@ -15,7 +16,13 @@ use crate::tracing::init_tracing::init_tracing;
 use app_state::AppState;
 use config::network_config::NetworkConfig;
 use metrics_exporter_prometheus::PrometheusBuilder;
+use services::api_keys::ApiKeyRegistry;
+use services::balance_strategies::BalanceStrategies;
+use services::price_oracle::PriceOracle;
+use services::webhook::WebhookClient;
 use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
 use tokio::net::TcpListener;
 
 #[tokio::main]
@@ -33,17 +40,86 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let network_cfg = NetworkConfig::init(&cfg);
 
+    let api_keys_path = Some(cfg.api_keys_path.trim())
+        .filter(|path| !path.is_empty())
+        .map(Path::new);
+    let api_keys = ApiKeyRegistry::load(&cfg.api_keys, api_keys_path)?;
+
+    let price_feeds_path = Some(cfg.price_feeds_path.trim())
+        .filter(|path| !path.is_empty())
+        .map(Path::new);
+    let price_oracle = PriceOracle::load(&cfg.price_feeds, price_feeds_path, network_cfg.pricing)?;
+
+    let balance_strategies_path = Some(cfg.balance_strategies_path.trim())
+        .filter(|path| !path.is_empty())
+        .map(Path::new);
+    let balance_strategies = BalanceStrategies::load(
+        &cfg.balance_strategies,
+        balance_strategies_path,
+        network_cfg.log_source.poll_interval,
+    )?;
+
+    let webhooks = WebhookClient::new(network_cfg.webhooks.clone())?;
+
     let metrics_handler = PrometheusBuilder::new().install_recorder()?;
 
     let allowed_origins = network_cfg.allowed_origins.clone();
-    let app_state = AppState::build(network_cfg).await;
+    let app_state = AppState::build(
+        network_cfg,
+        api_keys,
+        price_oracle,
+        balance_strategies,
+        webhooks,
+    )
+    .await?;
+    let shutdown_state = Arc::clone(&app_state);
     let app = create_router(app_state, metrics_handler, allowed_origins);
 
     let address: SocketAddr = cfg.bind.parse()?;
     ::tracing::info!("Listening to http://{}", address);
 
     let listener = TcpListener::bind(address).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(shutdown_state))
+    .await?;
 
     Ok(())
 }
+
+// wait for ctrl+c or SIGTERM, then save state and close SSE streams
+async fn shutdown_signal(app_state: Arc<AppState>) {
+    // the server keeps running without a handler, it can still be stopped by the other signal
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            ::tracing::error!(error = %err, "unable to install ctrl+c handler");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                ::tracing::error!(error = %err, "unable to install SIGTERM handler, only ctrl+c stops the server");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+
+    ::tracing::info!("shutdown signal received");
+    app_state.shutdown().await;
+}
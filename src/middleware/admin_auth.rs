@@ -0,0 +1,58 @@
+use crate::app_error::AppError;
+use crate::app_state::AppState;
+use axum::extract::{Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::middleware::Next;
+use axum::response::Response;
+use metrics::counter;
+use std::sync::Arc;
+
+// admin routes require `Authorization: Bearer <ADMIN_API_KEY>`
+// if ADMIN_API_KEY is not configured every request is rejected
+pub async fn admin_auth(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(admin_api_key) = state.network_config.admin_api_key() else {
+        counter!("admin_unauthorized_total").increment(1);
+        return Err(AppError::Unauthorized);
+    };
+
+    let provided = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(provided) if constant_time_eq(provided.as_bytes(), admin_api_key.as_bytes()) => {
+            Ok(next.run(request).await)
+        }
+        _ => {
+            counter!("admin_unauthorized_total").increment(1);
+            Err(AppError::Unauthorized)
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_keys() {
+        assert!(constant_time_eq(b"admin-key", b"admin-key"));
+        assert!(!constant_time_eq(b"admin-key", b"admin-kez"));
+        assert!(!constant_time_eq(b"admin-key", b"admin-key2"));
+        assert!(!constant_time_eq(b"", b"admin-key"));
+    }
+}
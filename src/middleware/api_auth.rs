@@ -0,0 +1,127 @@
+use crate::app_error::AppError;
+use crate::app_state::AppState;
+use crate::domain::{ApiKey, EvmNetwork};
+use crate::services::subscription_manager::SessionCreator;
+use axum::extract::{Query, Request, State};
+use axum::http::header::{AUTHORIZATION, ORIGIN};
+use axum::middleware::Next;
+use axum::response::Response;
+use metrics::counter;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const API_KEY_HEADER: &str = "x-api-key";
+// EventSource in browsers can't set headers, so SSE clients pass the key in query
+const API_KEY_QUERY_PARAM: &str = "apiKey";
+
+/// API key of the request, None if authentication is disabled
+#[derive(Clone)]
+pub struct ApiKeyContext(pub Option<Arc<ApiKey>>);
+
+impl ApiKeyContext {
+    pub fn ensure_chain_allowed(&self, network: EvmNetwork) -> Result<(), AppError> {
+        let Some(api_key) = &self.0 else {
+            return Ok(());
+        };
+
+        if api_key.allowed_chains.is_empty() || api_key.allowed_chains.contains(&network.chain_id())
+        {
+            return Ok(());
+        }
+
+        counter!("api_key_rejected_total", "key" => api_key.name.clone(), "reason" => "chain")
+            .increment(1);
+        Err(AppError::Forbidden(format!(
+            "network {network} is not allowed for the API key"
+        )))
+    }
+
+    pub fn max_tokens_per_session(&self, global_limit: usize) -> usize {
+        self.0
+            .as_ref()
+            .and_then(|api_key| api_key.max_tokens_per_session)
+            .map_or(global_limit, |limit| limit.min(global_limit))
+    }
+
+    pub fn key_name(&self) -> Option<&str> {
+        self.0.as_ref().map(|api_key| api_key.name.as_str())
+    }
+
+    pub fn session_creator(&self) -> Option<SessionCreator> {
+        self.0.as_ref().map(|api_key| SessionCreator {
+            key_name: api_key.name.clone(),
+            max_sessions: api_key.max_sessions,
+        })
+    }
+}
+
+// check API key (x-api-key header, bearer token or apiKey query param) and its allowed origins
+// the key is passed to handlers as ApiKeyContext extension to enforce the rest of limits
+pub async fn api_auth(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !state.api_keys.is_enabled() {
+        request.extensions_mut().insert(ApiKeyContext(None));
+        return Ok(next.run(request).await);
+    }
+
+    let Some(provided) = extract_key(&request) else {
+        counter!("api_key_rejected_total", "key" => "unknown", "reason" => "missing").increment(1);
+        return Err(AppError::Unauthorized);
+    };
+
+    let Some(api_key) = state.api_keys.get(&provided) else {
+        counter!("api_key_rejected_total", "key" => "unknown", "reason" => "invalid").increment(1);
+        return Err(AppError::Unauthorized);
+    };
+
+    if !api_key.allowed_origins.is_empty() {
+        let origin = request
+            .headers()
+            .get(ORIGIN)
+            .and_then(|value| value.to_str().ok());
+
+        let is_allowed =
+            origin.is_some_and(|origin| api_key.allowed_origins.iter().any(|a| a == origin));
+        if !is_allowed {
+            counter!("api_key_rejected_total", "key" => api_key.name.clone(), "reason" => "origin")
+                .increment(1);
+            return Err(AppError::Forbidden(
+                "origin is not allowed for the API key".to_string(),
+            ));
+        }
+    }
+
+    counter!("api_key_requests_total", "key" => api_key.name.clone()).increment(1);
+    request
+        .extensions_mut()
+        .insert(ApiKeyContext(Some(api_key)));
+
+    Ok(next.run(request).await)
+}
+
+fn extract_key(request: &Request) -> Option<String> {
+    let headers = request.headers();
+
+    if let Some(key) = headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        return Some(key.to_string());
+    }
+
+    if let Some(key) = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        return Some(key.to_string());
+    }
+
+    // the query is percent-decoded, keys may contain reserved characters
+    Query::<HashMap<String, String>>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(mut params)| params.remove(API_KEY_QUERY_PARAM))
+}
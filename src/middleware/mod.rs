@@ -0,0 +1,3 @@
+pub mod admin_auth;
+pub mod api_auth;
+pub mod rate_limit;
@@ -0,0 +1,118 @@
+use crate::app_error::AppError;
+use crate::app_state::AppState;
+use crate::middleware::api_auth::ApiKeyContext;
+use crate::services::rate_limiter::RouteClass;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use metrics::counter;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+// runs before authentication, so requests with invalid keys are limited too
+pub async fn rate_limit_by_ip(
+    State((state, class)): State<(Arc<AppState>, RouteClass)>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let client = client_ip(&state, &request);
+    rate_limit(&state, class, &client).await?;
+
+    Ok(next.run(request).await)
+}
+
+// runs after authentication, requests with an API key are also limited per key
+pub async fn rate_limit_by_key(
+    State((state, class)): State<(Arc<AppState>, RouteClass)>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let api_key = request
+        .extensions()
+        .get::<ApiKeyContext>()
+        .and_then(|ctx| ctx.0.as_ref());
+    if let Some(api_key) = api_key {
+        rate_limit(&state, class, &format!("key:{}", api_key.name)).await?;
+    }
+
+    Ok(next.run(request).await)
+}
+
+async fn rate_limit(state: &AppState, class: RouteClass, client: &str) -> Result<(), AppError> {
+    if let Err(retry_after) = state.rate_limiter.check(class, client).await {
+        counter!("rate_limited_total", "class" => class.as_str()).increment(1);
+        tracing::warn!(
+            client = %client,
+            class = class.as_str(),
+            "rate limit exceeded"
+        );
+        return Err(AppError::RateLimited(
+            retry_after.as_secs_f64().ceil().max(1.0) as u64,
+        ));
+    }
+
+    Ok(())
+}
+
+fn client_ip(state: &AppState, request: &Request) -> String {
+    // behind a reverse proxy the peer address is the proxy one
+    if state.network_config.trust_x_forwarded_for {
+        let forwarded = request
+            .headers()
+            .get(X_FORWARDED_FOR)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| forwarded_client(value, state.network_config.trusted_proxy_hops));
+        if let Some(ip) = forwarded {
+            return format!("ip:{ip}");
+        }
+    }
+
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| format!("ip:{}", addr.ip()))
+        .unwrap_or_else(|| "ip:unknown".to_string())
+}
+
+// entries on the left are sent by the client and could be anything,
+// every trusted proxy appends the address it got the request from
+fn forwarded_client(header: &str, hops: usize) -> Option<IpAddr> {
+    let entries: Vec<&str> = header.split(',').map(str::trim).collect();
+    let index = entries.len().checked_sub(hops)?;
+
+    IpAddr::from_str(entries[index]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(IpAddr::from_str(value).unwrap())
+    }
+
+    #[test]
+    fn takes_address_appended_by_trusted_proxy() {
+        assert_eq!(forwarded_client("203.0.113.7", 1), ip("203.0.113.7"));
+        // the client sent a fake address, the proxy appended the real one
+        assert_eq!(
+            forwarded_client("1.2.3.4, 203.0.113.7", 1),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            forwarded_client("1.2.3.4,203.0.113.7, 10.0.0.2", 2),
+            ip("203.0.113.7")
+        );
+        assert_eq!(forwarded_client("2001:db8::1", 1), ip("2001:db8::1"));
+    }
+
+    #[test]
+    fn rejects_missing_or_invalid_entries() {
+        assert_eq!(forwarded_client("203.0.113.7", 2), None);
+        assert_eq!(forwarded_client("", 1), None);
+        assert_eq!(forwarded_client("1.2.3.4, not-an-ip", 1), None);
+    }
+}
@@ -1,8 +1,20 @@
+use crate::api::admin::{
+    get_session, get_session_snapshot, list_sessions, resync_session, terminate_session,
+};
 use crate::api::create_sse_session::create_sse_session;
+use crate::api::remove_session_tokens::remove_session_tokens;
+use crate::api::remove_session_webhook::remove_session_webhook;
+use crate::api::replace_session_tokens::replace_session_tokens;
+use crate::api::session_alerts::{get_session_alerts, replace_session_alerts};
 use crate::api::update_session::update_session;
 use crate::api::{balance::get_token_balance, create_session::create_session};
 use crate::app_state::AppState;
-use axum::routing::put;
+use crate::middleware::admin_auth::admin_auth;
+use crate::middleware::api_auth::api_auth;
+use crate::middleware::rate_limit::{rate_limit_by_ip, rate_limit_by_key};
+use crate::services::rate_limiter::RouteClass;
+use axum::middleware::from_fn_with_state;
+use axum::routing::{delete, put};
 use axum::{
     routing::{get, post},
     Router,
@@ -38,18 +50,72 @@ pub fn create_router(
         .allow_methods(Any)
         .allow_headers(Any);
 
-    Router::new()
+    let admin = Router::new()
+        .route("/sessions", get(list_sessions))
         .route(
-            "/metrics",
-            get(move || async move { prometheus_handler.render() }),
+            "/sessions/{chain_id}/{owner}",
+            get(get_session).delete(terminate_session),
+        )
+        .route(
+            "/sessions/{chain_id}/{owner}/snapshot",
+            get(get_session_snapshot),
         )
-        .route("/sse/{chain_id}/balances/{owner}", get(create_sse_session))
+        .route("/sessions/{chain_id}/{owner}/resync", post(resync_session))
+        .route_layer(from_fn_with_state(Arc::clone(&app_state), admin_auth));
+
+    let sse = Router::new().route("/sse/{chain_id}/balances/{owner}", get(create_sse_session));
+    let sse = authenticated(sse, &app_state, RouteClass::SseConnect);
+
+    let sessions = Router::new()
         .route("/{chain_id}/sessions/{owner}", post(create_session))
         .route("/{chain_id}/sessions/{owner}", put(update_session))
         .route(
-            "/{chain_id}/balance/{owner}/{token}",
-            get(get_token_balance),
+            "/{chain_id}/sessions/{owner}/tokens",
+            put(replace_session_tokens).delete(remove_session_tokens),
         )
+        .route(
+            "/{chain_id}/sessions/{owner}/alerts",
+            get(get_session_alerts).put(replace_session_alerts),
+        )
+        .route(
+            "/{chain_id}/sessions/{owner}/webhook",
+            delete(remove_session_webhook),
+        );
+    let sessions = authenticated(sessions, &app_state, RouteClass::SessionMutation);
+
+    let balances = Router::new().route(
+        "/{chain_id}/balance/{owner}/{token}",
+        get(get_token_balance),
+    );
+    let balances = authenticated(balances, &app_state, RouteClass::BalanceRead);
+
+    let api = Router::new().merge(sse).merge(sessions).merge(balances);
+
+    Router::new()
+        .route(
+            "/metrics",
+            get(move || async move { prometheus_handler.render() }),
+        )
+        .nest("/admin", admin)
+        .merge(api)
         .layer(cors)
         .with_state(app_state)
 }
+
+// the last route layer runs first: per-IP limit, API key authentication, per-key limit
+fn authenticated(
+    router: Router<Arc<AppState>>,
+    app_state: &Arc<AppState>,
+    class: RouteClass,
+) -> Router<Arc<AppState>> {
+    router
+        .route_layer(from_fn_with_state(
+            (Arc::clone(app_state), class),
+            rate_limit_by_key,
+        ))
+        .route_layer(from_fn_with_state(Arc::clone(app_state), api_auth))
+        .route_layer(from_fn_with_state(
+            (Arc::clone(app_state), class),
+            rate_limit_by_ip,
+        ))
+}
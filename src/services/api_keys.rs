@@ -0,0 +1,131 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::domain::ApiKey;
+use crate::services::errors::ApiKeysError;
+
+#[derive(Debug, Deserialize)]
+struct ApiKeysFile {
+    keys: Vec<ApiKey>,
+}
+
+// registry of API keys, if there are no keys authentication is disabled
+pub struct ApiKeyRegistry {
+    keys: HashMap<String, Arc<ApiKey>>,
+}
+
+impl ApiKeyRegistry {
+    // keys are taken from inline json (API_KEYS) and from the file (API_KEYS_PATH)
+    // both have the same format: {"keys": [{"key": "...", "name": "...", ...}]}
+    pub fn load(inline: &str, path: Option<&Path>) -> Result<Self, ApiKeysError> {
+        let mut keys: Vec<ApiKey> = Vec::new();
+
+        if !inline.trim().is_empty() {
+            let file: ApiKeysFile = serde_json::from_str(inline)
+                .map_err(|err| ApiKeysError::Parse("API_KEYS".to_string(), err.to_string()))?;
+            keys.extend(file.keys);
+        }
+
+        if let Some(path) = path {
+            let source = path.display().to_string();
+            let content = std::fs::read(path)
+                .map_err(|err| ApiKeysError::Parse(source.clone(), err.to_string()))?;
+            let file: ApiKeysFile = serde_json::from_slice(&content)
+                .map_err(|err| ApiKeysError::Parse(source, err.to_string()))?;
+            keys.extend(file.keys);
+        }
+
+        // sessions are owned by the key name, so names are unique as well
+        let mut by_key: HashMap<String, Arc<ApiKey>> = HashMap::with_capacity(keys.len());
+        let mut names: HashSet<String> = HashSet::with_capacity(keys.len());
+        for api_key in keys {
+            if api_key.key.is_empty() {
+                return Err(ApiKeysError::EmptyKey(api_key.name));
+            }
+
+            if by_key.contains_key(&api_key.key) {
+                return Err(ApiKeysError::Duplicate(api_key.name));
+            }
+
+            if !names.insert(api_key.name.clone()) {
+                return Err(ApiKeysError::DuplicateName(api_key.name));
+            }
+
+            by_key.insert(api_key.key.clone(), Arc::new(api_key));
+        }
+
+        if by_key.is_empty() {
+            tracing::warn!("no API keys configured, API authentication is disabled");
+        } else {
+            let names: Vec<&String> = by_key.values().map(|k| &k.name).collect();
+            tracing::info!(keys = ?names, "API keys loaded");
+        }
+
+        Ok(Self { keys: by_key })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<ApiKey>> {
+        self.keys.get(key).cloned()
+    }
+
+    // sessions limit of the key by its name, sessions keep the name only
+    pub fn max_sessions_of(&self, name: &str) -> Option<usize> {
+        self.keys
+            .values()
+            .find(|api_key| api_key.name == name)
+            .and_then(|api_key| api_key.max_sessions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_keys() {
+        let registry = ApiKeyRegistry::load(
+            r#"{"keys": [{"key": "k1", "name": "a", "maxSessions": 2}, {"key": "k2", "name": "b"}]}"#,
+            None,
+        )
+        .unwrap();
+
+        assert!(registry.is_enabled());
+        assert_eq!(
+            registry.get("k2").map(|key| key.name.clone()),
+            Some("b".to_string())
+        );
+        assert_eq!(registry.max_sessions_of("a"), Some(2));
+        assert_eq!(registry.max_sessions_of("b"), None);
+    }
+
+    #[test]
+    fn rejects_duplicate_keys() {
+        let result = ApiKeyRegistry::load(
+            r#"{"keys": [{"key": "k1", "name": "a"}, {"key": "k1", "name": "b"}]}"#,
+            None,
+        );
+        assert!(matches!(result, Err(ApiKeysError::Duplicate(name)) if name == "b"));
+    }
+
+    #[test]
+    fn rejects_duplicate_names() {
+        let result = ApiKeyRegistry::load(
+            r#"{"keys": [{"key": "k1", "name": "a"}, {"key": "k2", "name": "a"}]}"#,
+            None,
+        );
+        assert!(matches!(result, Err(ApiKeysError::DuplicateName(name)) if name == "a"));
+    }
+
+    #[test]
+    fn rejects_empty_keys() {
+        let result = ApiKeyRegistry::load(r#"{"keys": [{"key": "", "name": "a"}]}"#, None);
+        assert!(matches!(result, Err(ApiKeysError::EmptyKey(_))));
+    }
+}
@@ -0,0 +1,478 @@
+use crate::domain::{BalanceStrategy, EvmNetwork, RefreshStrategy, ShareBalance, ShareKind};
+use crate::evm::multicall3::Multicall3;
+use crate::evm::shares::{CToken, LidoShares, ERC4626};
+use crate::services::errors::{ServiceError, StrategyError};
+use crate::services::fetch_balances_via_multicall::BalanceCallCtx;
+use alloy::eips::BlockId;
+use alloy::primitives::{Address, B256, U256};
+use alloy::providers::{DynProvider, Provider};
+use alloy::rpc::types::Filter;
+use alloy::sol_types::{SolCall, SolValue};
+use metrics::counter;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::interval;
+
+const TRIGGER_CHANNEL_CAPACITY: usize = 64;
+const SHARE_RATE_SCALE: u128 = 1_000_000_000_000_000_000;
+
+#[derive(Debug, Deserialize)]
+struct BalanceStrategiesFile {
+    strategies: Vec<BalanceStrategy>,
+}
+
+/// Tokens to refresh at a new block: refreshed on every block or rebased in the range
+#[derive(Debug, Clone)]
+pub struct RefreshTrigger {
+    pub block_number: u64,
+    pub tokens: Vec<Address>,
+}
+
+struct NetworkStrategies {
+    strategies: HashMap<Address, BalanceStrategy>,
+    triggers: broadcast::Sender<RefreshTrigger>,
+    started: AtomicBool,
+}
+
+impl NetworkStrategies {
+    // the block monitor is needed only for every block and rebase event strategies
+    fn needs_monitor(&self) -> bool {
+        self.strategies.values().any(|strategy| {
+            matches!(
+                strategy.refresh,
+                Some(RefreshStrategy::EveryBlock | RefreshStrategy::RebaseEvent { .. })
+            )
+        })
+    }
+}
+
+// registry of tokens changing balanceOf without a Transfer to the holder (rebasing, interest bearing)
+// and share-based tokens, one block monitor per network triggers refreshes of all sessions
+pub struct BalanceStrategies {
+    networks: HashMap<EvmNetwork, NetworkStrategies>,
+    poll_interval: Duration,
+}
+
+impl BalanceStrategies {
+    // strategies are taken from inline json (BALANCE_STRATEGIES) and from the file (BALANCE_STRATEGIES_PATH)
+    // both have the same format: {"strategies": [{"chainId": 1, "token": "0x...", ...}]}
+    // the registry is disabled (None) if there are no strategies
+    pub fn load(
+        inline: &str,
+        path: Option<&Path>,
+        poll_interval: Duration,
+    ) -> Result<Option<Self>, StrategyError> {
+        let mut all_strategies: Vec<BalanceStrategy> = Vec::new();
+
+        if !inline.trim().is_empty() {
+            let file: BalanceStrategiesFile = serde_json::from_str(inline).map_err(|err| {
+                StrategyError::Parse("BALANCE_STRATEGIES".to_string(), err.to_string())
+            })?;
+            all_strategies.extend(file.strategies);
+        }
+
+        if let Some(path) = path {
+            let source = path.display().to_string();
+            let content = std::fs::read(path)
+                .map_err(|err| StrategyError::Parse(source.clone(), err.to_string()))?;
+            let file: BalanceStrategiesFile = serde_json::from_slice(&content)
+                .map_err(|err| StrategyError::Parse(source, err.to_string()))?;
+            all_strategies.extend(file.strategies);
+        }
+
+        if all_strategies.is_empty() {
+            tracing::info!("no balance strategies configured");
+            return Ok(None);
+        }
+
+        let mut strategies: HashMap<EvmNetwork, HashMap<Address, BalanceStrategy>> = HashMap::new();
+        for strategy in all_strategies {
+            let token = strategy.token.to_string();
+            let network = EvmNetwork::try_from(strategy.chain_id)
+                .map_err(|_| StrategyError::UnknownChain(strategy.chain_id, token.clone()))?;
+
+            match &strategy.refresh {
+                None if strategy.shares.is_none() => return Err(StrategyError::Empty(token)),
+                Some(RefreshStrategy::IntervalSecs(0)) => {
+                    return Err(StrategyError::InvalidInterval(token))
+                }
+                Some(RefreshStrategy::RebaseEvent { event, .. })
+                    if !event.ends_with(')') || !event.contains('(') || event.contains(' ') =>
+                {
+                    return Err(StrategyError::InvalidEvent(token, event.clone()))
+                }
+                _ => {}
+            }
+
+            let network_strategies = strategies.entry(network).or_default();
+            if network_strategies.contains_key(&strategy.token) {
+                return Err(StrategyError::Duplicate(token));
+            }
+            network_strategies.insert(strategy.token, strategy);
+        }
+
+        let networks = strategies
+            .into_iter()
+            .map(|(network, strategies)| {
+                tracing::info!(
+                    network = %network,
+                    strategies = strategies.len(),
+                    "balance strategies loaded"
+                );
+
+                let (triggers, _) = broadcast::channel(TRIGGER_CHANNEL_CAPACITY);
+                let network_strategies = NetworkStrategies {
+                    strategies,
+                    triggers,
+                    started: AtomicBool::new(false),
+                };
+                (network, network_strategies)
+            })
+            .collect();
+
+        Ok(Some(Self {
+            networks,
+            poll_interval,
+        }))
+    }
+
+    pub fn strategies(&self, network: EvmNetwork) -> Option<&HashMap<Address, BalanceStrategy>> {
+        self.networks
+            .get(&network)
+            .map(|network_strategies| &network_strategies.strategies)
+    }
+
+    // the block monitor of the network is started with the first subscriber
+    // None if no token of the network is refreshed on blocks or rebase events
+    pub fn subscribe(
+        self: &Arc<Self>,
+        network: EvmNetwork,
+        provider: &DynProvider,
+    ) -> Option<broadcast::Receiver<RefreshTrigger>> {
+        let network_strategies = self.networks.get(&network)?;
+        if !network_strategies.needs_monitor() {
+            return None;
+        }
+
+        let receiver = network_strategies.triggers.subscribe();
+        if !network_strategies.started.swap(true, Ordering::SeqCst) {
+            tokio::spawn(Arc::clone(self).run_monitor(network, provider.clone()));
+        }
+
+        Some(receiver)
+    }
+
+    // poll the head, every block strategies are triggered on each new block,
+    // rebase strategies when their event is emitted in the new range
+    async fn run_monitor(self: Arc<Self>, network: EvmNetwork, provider: DynProvider) {
+        let Some(network_strategies) = self.networks.get(&network) else {
+            return;
+        };
+        tracing::info!(network = %network, "start balance strategies monitor");
+
+        let every_block: Vec<Address> = network_strategies
+            .strategies
+            .values()
+            .filter(|strategy| strategy.refresh == Some(RefreshStrategy::EveryBlock))
+            .map(|strategy| strategy.token)
+            .collect();
+
+        // (emitter, topic0) -> rebased tokens
+        let mut rebase_events: HashMap<(Address, B256), Vec<Address>> = HashMap::new();
+        for strategy in network_strategies.strategies.values() {
+            let Some(refresh @ RefreshStrategy::RebaseEvent { emitter, .. }) = &strategy.refresh
+            else {
+                continue;
+            };
+            if let Some(topic) = refresh.rebase_topic() {
+                rebase_events
+                    .entry((*emitter, topic))
+                    .or_default()
+                    .push(strategy.token);
+            }
+        }
+
+        let filter = Filter::new()
+            .address(
+                rebase_events
+                    .keys()
+                    .map(|(emitter, _)| *emitter)
+                    .collect::<Vec<_>>(),
+            )
+            .event_signature(
+                rebase_events
+                    .keys()
+                    .map(|(_, topic)| *topic)
+                    .collect::<Vec<_>>(),
+            );
+
+        let mut last_block: Option<u64> = None;
+        let mut interval = interval(self.poll_interval);
+
+        loop {
+            interval.tick().await;
+
+            let head = match provider.get_block_number().await {
+                Ok(head) => head,
+                Err(err) => {
+                    counter!("strategy_monitor_errors_total").increment(1);
+                    tracing::warn!(error = %err, network = %network, "unable to get block number for balance strategies");
+                    continue;
+                }
+            };
+
+            let Some(from_block) = last_block.map(|block| block + 1) else {
+                last_block = Some(head);
+                continue;
+            };
+            if head < from_block {
+                continue;
+            }
+
+            let mut tokens: HashSet<Address> = every_block.iter().copied().collect();
+
+            if !rebase_events.is_empty() {
+                let range_filter = filter.clone().from_block(from_block).to_block(head);
+                match provider.get_logs(&range_filter).await {
+                    Ok(logs) => {
+                        for log in logs.iter().filter(|log| !log.removed) {
+                            let Some(topic0) = log.topic0() else {
+                                continue;
+                            };
+                            if let Some(rebased) = rebase_events.get(&(log.address(), *topic0)) {
+                                counter!("strategy_rebase_events_total").increment(1);
+                                tokens.extend(rebased.iter().copied());
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        // the range is retried on the next tick
+                        counter!("strategy_monitor_errors_total").increment(1);
+                        tracing::warn!(
+                            error = %err,
+                            network = %network,
+                            from = from_block,
+                            to = head,
+                            "unable to get rebase logs"
+                        );
+                        continue;
+                    }
+                }
+            }
+
+            last_block = Some(head);
+            if !tokens.is_empty() {
+                let _ = network_strategies.triggers.send(RefreshTrigger {
+                    block_number: head,
+                    tokens: tokens.into_iter().collect(),
+                });
+            }
+        }
+    }
+
+    // shares and underlying amounts of share-based tokens from their balances at the block,
+    // tokens whose subcall fails are skipped
+    pub async fn share_balances(
+        &self,
+        ctx: &BalanceCallCtx,
+        balances: &HashMap<Address, U256>,
+        block_number: U256,
+    ) -> Result<HashMap<Address, ShareBalance>, ServiceError> {
+        let Some(strategies) = self.strategies(ctx.network) else {
+            return Ok(HashMap::new());
+        };
+
+        let share_tokens: Vec<(Address, ShareKind, U256)> = balances
+            .iter()
+            .filter_map(|(token, balance)| {
+                let kind = strategies.get(token)?.shares?;
+                Some((*token, kind, *balance))
+            })
+            .collect();
+        if share_tokens.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let calls: Vec<Multicall3::Call> = share_tokens
+            .iter()
+            .map(|(token, kind, balance)| {
+                let call_data = match kind {
+                    ShareKind::Lido => LidoShares::sharesOfCall { account: ctx.owner }.abi_encode(),
+                    ShareKind::Erc4626 => {
+                        ERC4626::convertToAssetsCall { shares: *balance }.abi_encode()
+                    }
+                    ShareKind::Compound => CToken::exchangeRateStoredCall {}.abi_encode(),
+                };
+                Multicall3::Call {
+                    target: *token,
+                    callData: call_data.into(),
+                }
+            })
+            .collect();
+
+        counter!("share_balances_fetch_total").increment(1);
+        let block_id = BlockId::number(block_number.saturating_to::<u64>());
+        let call_result = Multicall3::new(ctx.multicall3, ctx.provider.clone())
+            .tryBlockAndAggregate(false, calls)
+            .block(block_id)
+            .call()
+            .await
+            .map_err(|err| {
+                counter!("share_balances_fetch_failed_total").increment(1);
+                ServiceError::SharesMultiCall(err.to_string())
+            })?;
+
+        let mut share_balances = HashMap::with_capacity(share_tokens.len());
+        for ((token, kind, balance), result) in share_tokens.iter().zip(&call_result.returnData) {
+            let decoded = if result.success {
+                <U256 as SolValue>::abi_decode(&result.returnData).ok()
+            } else {
+                None
+            };
+            let Some(value) = decoded else {
+                tracing::warn!(token = %token, network = %ctx.network, "unable to get shares of token");
+                continue;
+            };
+
+            let (shares, underlying) = share_amounts(*kind, *balance, value);
+
+            share_balances.insert(
+                *token,
+                ShareBalance {
+                    shares: shares.to_string(),
+                    underlying: underlying.to_string(),
+                    block_number: call_result.blockNumber.to_string(),
+                },
+            );
+        }
+
+        Ok(share_balances)
+    }
+}
+
+// shares and underlying amount from balanceOf and the value of the share subcall
+fn share_amounts(kind: ShareKind, balance: U256, value: U256) -> (U256, U256) {
+    match kind {
+        ShareKind::Lido => (value, balance),
+        ShareKind::Erc4626 => (balance, value),
+        ShareKind::Compound => (
+            balance,
+            balance.saturating_mul(value) / U256::from(SHARE_RATE_SCALE),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::erc20::ERC20;
+    use alloy::primitives::address;
+    use alloy::sol_types::SolEvent;
+    use serde_json::json;
+
+    const STETH: Address = address!("0xae7ab96520DE3A18E5e111B5EaAb095312D7fE84");
+    const LIDO_ORACLE: Address = address!("0x852deD011285fe67063a08005c71a85690503Cee");
+    const CUSDC: Address = address!("0x39AA39c021dfbaE8faC545936693aC917d5E7563");
+
+    fn load(strategies: serde_json::Value) -> Result<Option<BalanceStrategies>, StrategyError> {
+        BalanceStrategies::load(&strategies.to_string(), None, Duration::from_secs(1))
+    }
+
+    fn rebase(token: Address, event: &str) -> serde_json::Value {
+        json!({"chainId": 1, "token": token,
+               "refresh": {"rebaseEvent": {"emitter": LIDO_ORACLE, "event": event}}})
+    }
+
+    #[test]
+    fn loads_strategies() {
+        assert!(load(json!({"strategies": []})).unwrap().is_none());
+
+        let strategies = load(json!({"strategies": [
+            rebase(STETH, "TokenRebased(uint256,uint256,uint256,uint256,uint256,uint256,uint256)"),
+            {"chainId": 1, "token": CUSDC, "refresh": {"intervalSecs": 60}, "shares": "compound"},
+        ]}))
+        .unwrap()
+        .unwrap();
+
+        let eth = strategies.strategies(EvmNetwork::Eth).unwrap();
+        assert_eq!(eth[&CUSDC].refresh, Some(RefreshStrategy::IntervalSecs(60)));
+        assert_eq!(eth[&CUSDC].shares, Some(ShareKind::Compound));
+        assert!(strategies.networks[&EvmNetwork::Eth].needs_monitor());
+        assert!(strategies.strategies(EvmNetwork::Arbitrum).is_none());
+    }
+
+    #[test]
+    fn interval_strategies_need_no_monitor() {
+        let strategies = load(json!({"strategies": [
+            {"chainId": 1, "token": CUSDC, "refresh": {"intervalSecs": 60}},
+        ]}))
+        .unwrap()
+        .unwrap();
+
+        assert!(!strategies.networks[&EvmNetwork::Eth].needs_monitor());
+    }
+
+    #[test]
+    fn rejects_invalid_strategies() {
+        let empty = json!({"strategies": [{"chainId": 1, "token": CUSDC}]});
+        assert!(matches!(load(empty), Err(StrategyError::Empty(_))));
+
+        let zero =
+            json!({"strategies": [{"chainId": 1, "token": CUSDC, "refresh": {"intervalSecs": 0}}]});
+        assert!(matches!(load(zero), Err(StrategyError::InvalidInterval(_))));
+
+        for event in ["TokenRebased", "TokenRebased(uint256, uint256)"] {
+            let invalid = json!({"strategies": [rebase(STETH, event)]});
+            assert!(matches!(
+                load(invalid),
+                Err(StrategyError::InvalidEvent(_, _))
+            ));
+        }
+
+        let duplicate = json!({"strategies": [
+            {"chainId": 1, "token": CUSDC, "refresh": "everyBlock"},
+            {"chainId": 1, "token": CUSDC, "shares": "compound"},
+        ]});
+        assert!(matches!(load(duplicate), Err(StrategyError::Duplicate(_))));
+    }
+
+    #[test]
+    fn rebase_topic_is_event_signature_hash() {
+        let strategy = RefreshStrategy::RebaseEvent {
+            emitter: LIDO_ORACLE,
+            event: "Transfer(address,address,uint256)".to_string(),
+        };
+
+        assert_eq!(
+            strategy.rebase_topic(),
+            Some(ERC20::Transfer::SIGNATURE_HASH)
+        );
+        assert_eq!(RefreshStrategy::EveryBlock.rebase_topic(), None);
+    }
+
+    #[test]
+    fn converts_shares() {
+        let balance = U256::from(1_000u64);
+        let value = U256::from(900u64);
+
+        assert_eq!(
+            share_amounts(ShareKind::Lido, balance, value),
+            (value, balance)
+        );
+        assert_eq!(
+            share_amounts(ShareKind::Erc4626, balance, value),
+            (balance, value)
+        );
+        // exchange rate 0.02 scaled by 1e18
+        let rate = U256::from(20_000_000_000_000_000u64);
+        assert_eq!(
+            share_amounts(ShareKind::Compound, balance, rate),
+            (balance, U256::from(20u64))
+        );
+    }
+}
@@ -0,0 +1,480 @@
+use crate::config::cluster_config::ClusterConfig;
+use crate::domain::{BalanceEvent, Erc1155Token, EvmNetwork, NftHolding, SubscriptionKey};
+use crate::services::errors::ClusterError;
+use crate::services::session_store::StoredSession;
+use crate::services::subscription_manager::{
+    Balance, BalanceSnapshot, Subscription, SubscriptionManager,
+};
+use crate::services::watcher::{Watcher, WatcherContext};
+use alloy::primitives::Address;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Messages of all sessions as (session id, payload)
+pub type ClusterMessages = BoxStream<'static, (String, Vec<u8>)>;
+
+/// Storage and transport shared by replicas
+pub trait ClusterBackend: Send + Sync {
+    fn save_session(
+        &self,
+        id: String,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> BoxFuture<'_, Result<(), ClusterError>>;
+
+    fn load_session(&self, id: String) -> BoxFuture<'_, Result<Option<Vec<u8>>, ClusterError>>;
+
+    /// Remove the session together with the state of its leader
+    fn remove_session(&self, id: String) -> BoxFuture<'_, Result<(), ClusterError>>;
+
+    /// Save the state of the session leader and prolong the session
+    fn save_leader_state(
+        &self,
+        id: String,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> BoxFuture<'_, Result<(), ClusterError>>;
+
+    fn load_leader_state(&self, id: String)
+        -> BoxFuture<'_, Result<Option<Vec<u8>>, ClusterError>>;
+
+    fn publish(&self, id: String, payload: Vec<u8>) -> BoxFuture<'_, Result<(), ClusterError>>;
+
+    fn messages(&self) -> BoxFuture<'_, Result<ClusterMessages, ClusterError>>;
+
+    /// Take the lock of the session or prolong it if it is held by the replica
+    fn acquire_leadership(
+        &self,
+        id: String,
+        replica_id: String,
+        ttl: Duration,
+    ) -> BoxFuture<'_, Result<bool, ClusterError>>;
+
+    fn release_leadership(
+        &self,
+        id: String,
+        replica_id: String,
+    ) -> BoxFuture<'_, Result<(), ClusterError>>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ClusterMessage {
+    /// Balance event for clients of the session on every replica
+    Event { event: BalanceEvent },
+    /// Session state is changed, replicas reload it
+    SessionChanged,
+    /// Session is terminated
+    Terminated,
+}
+
+/// State produced by watchers of the session leader.
+/// It is saved apart from the session, so the leader never overwrites
+/// tokens, alerts or the webhook changed through the API on another replica
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderState {
+    pub balances: BalanceSnapshot,
+    pub last_block: Option<u64>,
+    pub nft_holdings: HashMap<Address, NftHolding>,
+    pub erc1155_balances: Vec<(Erc1155Token, Balance)>,
+    /// Ids of the alert rules in the triggered state
+    pub triggered_alerts: Vec<String>,
+}
+
+impl LeaderState {
+    // balances are taken unless the session has a newer snapshot,
+    // alert states are the leader's ones, the rest of the session is kept
+    pub fn apply_to(self, session: &mut StoredSession) {
+        for rule in &mut session.alerts {
+            rule.triggered = self.triggered_alerts.contains(&rule.id);
+        }
+
+        if self.last_block < session.last_block {
+            return;
+        }
+
+        session.balances = self.balances;
+        session.last_block = self.last_block;
+        session.nft_holdings = self.nft_holdings;
+        session.erc1155_balances = self.erc1155_balances;
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClusterEnvelope {
+    pub origin: String,
+    pub message: ClusterMessage,
+}
+
+pub fn session_id(key: &SubscriptionKey) -> String {
+    format!("{}:{}", key.network.chain_id(), key.owner)
+}
+
+fn parse_session_id(id: &str) -> Option<SubscriptionKey> {
+    let (chain_id, owner) = id.split_once(':')?;
+
+    Some(SubscriptionKey {
+        network: EvmNetwork::from_str(chain_id).ok()?,
+        owner: Address::from_str(owner).ok()?,
+    })
+}
+
+// coordination of replicas sharing sessions:
+// - session state is saved to the backend, any replica materializes it on demand
+// - balance events are published to the backend and delivered to clients of every replica
+// - only the leader of a session (lock in the backend) runs its watchers
+pub struct Cluster {
+    backend: Arc<dyn ClusterBackend>,
+    replica_id: String,
+    leader_ttl: Duration,
+    session_ttl: Duration,
+}
+
+impl Cluster {
+    pub fn new(backend: Arc<dyn ClusterBackend>, config: &ClusterConfig) -> Self {
+        tracing::info!(replica_id = %config.replica_id, "cluster mode is enabled");
+
+        Self {
+            backend,
+            replica_id: config.replica_id.clone(),
+            leader_ttl: config.leader_ttl,
+            session_ttl: config.session_ttl,
+        }
+    }
+
+    pub fn replica_id(&self) -> &str {
+        &self.replica_id
+    }
+
+    // shared session with the state of its leader applied
+    pub async fn load_session(&self, key: SubscriptionKey) -> Option<StoredSession> {
+        let value = self
+            .backend
+            .load_session(session_id(&key))
+            .await
+            .inspect_err(|err| {
+                counter!("cluster_errors_total", "op" => "load_session").increment(1);
+                tracing::error!(error = %err, sub = %key, "unable to load shared session");
+            })
+            .ok()??;
+
+        let mut session: StoredSession = serde_json::from_slice(&value)
+            .inspect_err(|err| {
+                tracing::error!(error = %err, sub = %key, "unable to parse shared session");
+            })
+            .ok()?;
+
+        if let Some(state) = self.load_leader_state(key).await {
+            state.apply_to(&mut session);
+        }
+
+        Some(session)
+    }
+
+    async fn load_leader_state(&self, key: SubscriptionKey) -> Option<LeaderState> {
+        let value = self
+            .backend
+            .load_leader_state(session_id(&key))
+            .await
+            .inspect_err(|err| {
+                counter!("cluster_errors_total", "op" => "load_leader_state").increment(1);
+                tracing::error!(error = %err, sub = %key, "unable to load leader state");
+            })
+            .ok()??;
+
+        serde_json::from_slice(&value)
+            .inspect_err(|err| {
+                tracing::error!(error = %err, sub = %key, "unable to parse leader state");
+            })
+            .ok()
+    }
+
+    async fn save_leader_state(&self, key: SubscriptionKey, state: &LeaderState) {
+        let value = match serde_json::to_vec(state) {
+            Ok(value) => value,
+            Err(err) => {
+                tracing::error!(error = %err, sub = %key, "unable to encode leader state");
+                return;
+            }
+        };
+
+        let _ = self
+            .backend
+            .save_leader_state(session_id(&key), value, self.session_ttl)
+            .await
+            .inspect_err(|err| {
+                counter!("cluster_errors_total", "op" => "save_leader_state").increment(1);
+                tracing::error!(error = %err, sub = %key, "unable to save leader state");
+            });
+    }
+
+    pub async fn save_session(&self, session: &StoredSession) {
+        let value = match serde_json::to_vec(session) {
+            Ok(value) => value,
+            Err(err) => {
+                tracing::error!(error = %err, owner = %session.owner, "unable to encode session");
+                return;
+            }
+        };
+
+        let id = format!("{}:{}", session.chain_id, session.owner);
+        let _ = self
+            .backend
+            .save_session(id, value, self.session_ttl)
+            .await
+            .inspect_err(|err| {
+                counter!("cluster_errors_total", "op" => "save_session").increment(1);
+                tracing::error!(error = %err, owner = %session.owner, "unable to save shared session");
+            });
+    }
+
+    pub async fn remove_session(&self, key: SubscriptionKey) {
+        let _ = self
+            .backend
+            .remove_session(session_id(&key))
+            .await
+            .inspect_err(|err| {
+                counter!("cluster_errors_total", "op" => "remove_session").increment(1);
+                tracing::error!(error = %err, sub = %key, "unable to remove shared session");
+            });
+    }
+
+    pub async fn publish(&self, key: SubscriptionKey, message: ClusterMessage) {
+        let envelope = ClusterEnvelope {
+            origin: self.replica_id.clone(),
+            message,
+        };
+
+        let result = match serde_json::to_vec(&envelope) {
+            Ok(payload) => self.backend.publish(session_id(&key), payload).await,
+            Err(err) => Err(ClusterError::Encode(err.to_string())),
+        };
+
+        match result {
+            Ok(()) => counter!("cluster_messages_published_total").increment(1),
+            Err(err) => {
+                counter!("cluster_errors_total", "op" => "publish").increment(1);
+                tracing::error!(error = %err, sub = %key, "unable to publish cluster message");
+            }
+        }
+    }
+
+    // events of a session are published one by one to keep their order
+    // the task ends when the subscription (and its sender) is dropped
+    pub fn spawn_publisher(
+        self: &Arc<Self>,
+        key: SubscriptionKey,
+    ) -> mpsc::UnboundedSender<BalanceEvent> {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<BalanceEvent>();
+        let cluster = Arc::clone(self);
+
+        tokio::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                cluster.publish(key, ClusterMessage::Event { event }).await;
+            }
+        });
+
+        sender
+    }
+
+    pub async fn messages(
+        &self,
+    ) -> Result<impl Stream<Item = (SubscriptionKey, ClusterEnvelope)>, ClusterError> {
+        let messages = self.backend.messages().await?;
+
+        Ok(messages.filter_map(|(id, payload)| async move {
+            let key = parse_session_id(&id)?;
+            let envelope = serde_json::from_slice::<ClusterEnvelope>(&payload)
+                .inspect_err(|err| {
+                    tracing::warn!(error = %err, session = %id, "unable to parse cluster message");
+                })
+                .ok()?;
+
+            Some((key, envelope))
+        }))
+    }
+
+    async fn acquire_leadership(&self, key: SubscriptionKey) -> bool {
+        self.backend
+            .acquire_leadership(session_id(&key), self.replica_id.clone(), self.leader_ttl)
+            .await
+            .inspect_err(|err| {
+                counter!("cluster_errors_total", "op" => "acquire_leadership").increment(1);
+                tracing::error!(error = %err, sub = %key, "unable to acquire leadership");
+            })
+            .unwrap_or(false)
+    }
+
+    async fn release_leadership(&self, key: SubscriptionKey) {
+        let _ = self
+            .backend
+            .release_leadership(session_id(&key), self.replica_id.clone())
+            .await
+            .inspect_err(|err| {
+                counter!("cluster_errors_total", "op" => "release_leadership").increment(1);
+                tracing::error!(error = %err, sub = %key, "unable to release leadership");
+            });
+    }
+
+    // every replica with clients of the session competes for its lock,
+    // the leader runs watchers and keeps the shared state actual,
+    // watchers are stopped when the lock is lost and the lock is released with the session
+    pub fn spawn_leader_election(
+        self: &Arc<Self>,
+        manager: Arc<SubscriptionManager>,
+        key: SubscriptionKey,
+        sub: Arc<Subscription>,
+        ctx: WatcherContext,
+        interval_secs: usize,
+    ) {
+        let cluster = Arc::clone(self);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(cluster.leader_ttl / 3);
+            let mut watchers: Option<CancellationToken> = None;
+
+            loop {
+                tokio::select! {
+                    _ = sub.cancel_token.cancelled() => { break; }
+                    _ = interval.tick() => {}
+                }
+
+                let is_leader = cluster.acquire_leadership(key).await;
+                match (is_leader, &watchers) {
+                    (true, None) => {
+                        counter!("cluster_leadership_acquired_total").increment(1);
+                        tracing::info!(sub = %key, replica_id = %cluster.replica_id, "leadership is acquired");
+
+                        // the previous leader could change the session
+                        manager.sync_from_cluster(key).await;
+
+                        let cancel = sub.cancel_token.child_token();
+                        Watcher::new(ctx.clone(), Arc::clone(&sub), cancel.clone())
+                            .spawn_watchers(interval_secs)
+                            .await;
+                        watchers = Some(cancel);
+                    }
+                    (true, Some(_)) => {
+                        // keep the snapshot for the next leader, the session itself is changed via the API only
+                        if let Some(state) = manager.export_leader_state(key).await {
+                            cluster.save_leader_state(key, &state).await;
+                        }
+                    }
+                    (false, Some(cancel)) => {
+                        counter!("cluster_leadership_lost_total").increment(1);
+                        tracing::warn!(sub = %key, replica_id = %cluster.replica_id, "leadership is lost");
+                        cancel.cancel();
+                        watchers = None;
+                    }
+                    (false, None) => {}
+                }
+            }
+
+            if watchers.is_some() {
+                cluster.release_leadership(key).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{AlertComparator, AlertRule};
+    use alloy::primitives::{address, U256};
+
+    const OWNER: Address = address!("0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045");
+    const USDC: Address = address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const DAI: Address = address!("0x6B175474E89094C44Da98b954EedeAC495271d0F");
+
+    fn balance(amount: u64, block: u64) -> Balance {
+        Balance {
+            amount: U256::from(amount),
+            block_number: U256::from(block),
+        }
+    }
+
+    fn rule(id: &str, triggered: bool) -> AlertRule {
+        AlertRule {
+            id: id.to_string(),
+            token: USDC,
+            comparator: AlertComparator::Below,
+            threshold: U256::from(10),
+            hysteresis: U256::ZERO,
+            triggered,
+        }
+    }
+
+    // session changed through the API on another replica
+    fn shared_session(last_block: u64) -> StoredSession {
+        StoredSession {
+            chain_id: 1,
+            owner: OWNER,
+            tokens: vec![USDC, DAI],
+            discovery_enabled: false,
+            api_key: None,
+            balances: HashMap::from([(USDC, balance(1, last_block))]),
+            last_block: Some(last_block),
+            webhook: None,
+            alerts: vec![rule("low-usdc", false), rule("new-rule", false)],
+            finality: Default::default(),
+            pending: false,
+            nft_collections: Vec::new(),
+            nft_holdings: HashMap::new(),
+            erc1155_tokens: Vec::new(),
+            erc1155_balances: Vec::new(),
+        }
+    }
+
+    fn leader_state(last_block: u64) -> LeaderState {
+        LeaderState {
+            balances: HashMap::from([(USDC, balance(5, last_block))]),
+            last_block: Some(last_block),
+            triggered_alerts: vec!["low-usdc".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn leader_state_keeps_session_changes() {
+        let mut session = shared_session(100);
+        leader_state(120).apply_to(&mut session);
+
+        assert_eq!(session.tokens, vec![USDC, DAI]);
+        assert_eq!(session.alerts.len(), 2);
+        assert_eq!(session.last_block, Some(120));
+        assert_eq!(session.balances[&USDC].amount, U256::from(5));
+    }
+
+    #[test]
+    fn leader_state_sets_alert_states() {
+        let mut session = shared_session(100);
+        leader_state(120).apply_to(&mut session);
+
+        let triggered: Vec<(&str, bool)> = session
+            .alerts
+            .iter()
+            .map(|rule| (rule.id.as_str(), rule.triggered))
+            .collect();
+        assert_eq!(triggered, vec![("low-usdc", true), ("new-rule", false)]);
+    }
+
+    #[test]
+    fn older_leader_state_keeps_session_snapshot() {
+        let mut session = shared_session(130);
+        leader_state(120).apply_to(&mut session);
+
+        assert_eq!(session.last_block, Some(130));
+        assert_eq!(session.balances[&USDC].amount, U256::from(1));
+    }
+}
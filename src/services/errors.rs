@@ -1,9 +1,16 @@
+use crate::domain::EvmNetwork;
 use thiserror::Error;
 
 #[derive(Debug, Clone, Error)]
 pub enum ServiceError {
     #[error("Error getting balances from multicall")]
     BalancesMultiCallError(String),
+
+    #[error("Error getting prices from multicall: {0}")]
+    PricesMultiCallError(String),
+
+    #[error("Error getting shares from multicall: {0}")]
+    SharesMultiCall(String),
 }
 
 #[derive(Debug, Clone, Error)]
@@ -16,10 +23,133 @@ pub enum SubscriptionError {
 
     #[error("There is no more clients")]
     ThereIsNoClients,
+
+    #[error("Session belongs to another API key")]
+    ForeignSession,
+
+    #[error("Sessions limit of the API key is exceeded")]
+    KeySessionsLimitExceeded,
+
+    #[error("Sessions limit of the owner is exceeded")]
+    OwnerSessionsLimitExceeded,
+
+    #[error("Global sessions limit is exceeded")]
+    SessionsLimitExceeded,
+
+    #[error("Global watched tokens limit is exceeded")]
+    TotalTokensLimitExceeded,
 }
 
 #[derive(Debug, Clone, Error)]
 pub enum FetcherError {
     #[error("Unable to load token list, url: {0}, error: {1}")]
     UnableToLoadList(String, String),
+
+    #[error("Unable to load local token list, path: {0}, error: {1}")]
+    UnableToLoadLocalList(String, String),
+
+    #[error("Local token list is not found: {0}")]
+    LocalListIsNotFound(String),
+
+    #[error("Local token list {0} has no tokens for chain {1}")]
+    LocalListIsEmpty(String, u64),
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum ApiKeysError {
+    #[error("Unable to parse API keys from {0}: {1}")]
+    Parse(String, String),
+
+    #[error("API key {0} is empty")]
+    EmptyKey(String),
+
+    #[error("API key {0} is duplicated")]
+    Duplicate(String),
+
+    #[error("API key name {0} is used by several keys")]
+    DuplicateName(String),
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum SessionStoreError {
+    #[error("Unable to open session store at {0}: {1}")]
+    Open(String, String),
+
+    #[error("Unable to save sessions: {0}")]
+    Save(String),
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum ClusterError {
+    #[error("Unable to connect to cluster backend: {0}")]
+    Connect(String),
+
+    #[error("Cluster backend error: {0}")]
+    Backend(String),
+
+    #[error("Unable to encode cluster message: {0}")]
+    Encode(String),
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum WebhookError {
+    #[error("Invalid webhook url {0}: {1}")]
+    InvalidUrl(String, String),
+
+    #[error("Webhook secret should not be empty")]
+    EmptySecret,
+
+    #[error("Unable to build webhook client: {0}")]
+    Client(String),
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum WatcherSetupError {
+    #[error("No provider for network {0}")]
+    Provider(EvmNetwork),
+
+    #[error("No log source for network {0}")]
+    LogSource(EvmNetwork),
+
+    #[error("No multicall3 for network {0}")]
+    Multicall(EvmNetwork),
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum StrategyError {
+    #[error("Unable to parse balance strategies from {0}: {1}")]
+    Parse(String, String),
+
+    #[error("Unknown chain id {0} of the balance strategy of {1}")]
+    UnknownChain(u64, String),
+
+    #[error("Balance strategy of {0} has no refresh or shares")]
+    Empty(String),
+
+    #[error("Refresh interval of {0} should be greater than 0")]
+    InvalidInterval(String),
+
+    #[error("Rebase event of {0} should be a signature like `Name(uint256,uint256)`: {1}")]
+    InvalidEvent(String, String),
+
+    #[error("Balance strategy of {0} is duplicated")]
+    Duplicate(String),
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum PricingError {
+    #[error("Unable to parse price feeds from {0}: {1}")]
+    Parse(String, String),
+
+    #[error("Unknown chain id {0} of the price feed of {1}")]
+    UnknownChain(u64, String),
+
+    #[error("Price feed of {0} has no chainlink or uniswapV3 source")]
+    NoSource(String),
+
+    #[error("Quote token {1} of the TWAP of {0} has no price feed")]
+    UnknownQuote(String, String),
+
+    #[error("Price feed of {0} is duplicated")]
+    Duplicate(String),
 }
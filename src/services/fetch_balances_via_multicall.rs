@@ -1,12 +1,12 @@
-use crate::domain::EvmNetwork;
-use crate::evm::{erc20::ERC20, multicall3::Multicall3};
+use crate::domain::{Erc1155Token, EvmNetwork};
+use crate::evm::{erc1155::ERC1155, erc20::ERC20, erc721::ERC721, multicall3::Multicall3};
 use crate::services::errors::ServiceError;
 use alloy::eips::BlockId;
 use alloy::primitives::{Address, U256};
 use alloy::providers::DynProvider;
 use alloy::sol_types::{SolCall, SolValue};
 use metrics::{counter, histogram};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -131,3 +131,178 @@ pub async fn fetch_balances_via_multicall(
 
     Ok((balances, call_result.blockNumber))
 }
+
+// request erc20 balances via multicall allowing subcalls to fail
+// tokens whose balanceOf reverts or returns malformed data are skipped instead of failing the whole request
+pub async fn fetch_erc20_balances_allow_failure(
+    ctx: Arc<BalanceCallCtx>,
+    tokens: &[Address],
+    block_id: BlockId,
+) -> Result<BalancesWithBlock, ServiceError> {
+    let multicall3 = Multicall3::new(ctx.multicall3, ctx.provider.clone());
+    let owner = ctx.owner;
+
+    let calls: Vec<Multicall3::Call> = tokens
+        .iter()
+        .map(|address| Multicall3::Call {
+            target: *address,
+            callData: ERC20::balanceOfCall { owner }.abi_encode().into(),
+        })
+        .collect();
+
+    let t0 = Instant::now();
+    counter!("multicall_total").increment(1);
+
+    let call_result = multicall3
+        .tryBlockAndAggregate(false, calls)
+        .block(block_id)
+        .call()
+        .await
+        .inspect(move |_| {
+            histogram!("multicall_duration_ms").record(t0.elapsed().as_millis() as f64);
+        })
+        .map_err(|e| {
+            counter!("multicall_failed_total").increment(1);
+            histogram!("multicall_duration_ms").record(t0.elapsed().as_millis() as f64);
+            ServiceError::BalancesMultiCallError(e.to_string())
+        })?;
+
+    let mut balances: HashMap<Address, U256> = HashMap::with_capacity(tokens.len());
+    for (token, resp) in tokens.iter().zip(call_result.returnData.iter()) {
+        if !resp.success {
+            continue;
+        }
+
+        if let Ok(balance) = <U256 as SolValue>::abi_decode(&resp.returnData) {
+            balances.insert(*token, balance);
+        }
+    }
+
+    Ok((balances, call_result.blockNumber))
+}
+
+// request token IDs of the owner via ERC721Enumerable (tokenOfOwnerByIndex for 0..count)
+// collections with a failed subcall (no Enumerable extension) are skipped
+pub async fn fetch_nft_token_ids(
+    ctx: Arc<BalanceCallCtx>,
+    collections: &[(Address, u64)],
+    block_id: BlockId,
+) -> Result<HashMap<Address, BTreeSet<U256>>, ServiceError> {
+    let multicall3 = Multicall3::new(ctx.multicall3, ctx.provider.clone());
+    let owner = ctx.owner;
+
+    let calls: Vec<Multicall3::Call> = collections
+        .iter()
+        .flat_map(|(collection, count)| {
+            (0..*count).map(move |index| Multicall3::Call {
+                target: *collection,
+                callData: ERC721::tokenOfOwnerByIndexCall {
+                    owner,
+                    index: U256::from(index),
+                }
+                .abi_encode()
+                .into(),
+            })
+        })
+        .collect();
+    if calls.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let t0 = Instant::now();
+    counter!("multicall_total").increment(1);
+
+    let call_result = multicall3
+        .tryBlockAndAggregate(false, calls)
+        .block(block_id)
+        .call()
+        .await
+        .inspect(move |_| {
+            histogram!("multicall_duration_ms").record(t0.elapsed().as_millis() as f64);
+        })
+        .map_err(|e| {
+            counter!("multicall_failed_total").increment(1);
+            histogram!("multicall_duration_ms").record(t0.elapsed().as_millis() as f64);
+            ServiceError::BalancesMultiCallError(e.to_string())
+        })?;
+
+    let mut responses = call_result.returnData.iter();
+    let mut token_ids: HashMap<Address, BTreeSet<U256>> = HashMap::with_capacity(collections.len());
+    for (collection, count) in collections {
+        // responses of the collection are taken in full, so the next one starts at its own calls
+        let ids: Vec<Option<U256>> = responses
+            .by_ref()
+            .take(*count as usize)
+            .map(|resp| {
+                resp.success
+                    .then(|| <U256 as SolValue>::abi_decode(&resp.returnData).ok())
+                    .flatten()
+            })
+            .collect();
+
+        match ids.into_iter().collect::<Option<BTreeSet<U256>>>() {
+            Some(ids) => {
+                token_ids.insert(*collection, ids);
+            }
+            None => {
+                tracing::debug!(collection = %collection, "unable to enumerate nft collection");
+            }
+        }
+    }
+
+    Ok(token_ids)
+}
+
+// request balanceOf(owner, id) of ERC-1155 tokens via multicall allowing subcalls to fail
+// pairs whose call reverts or returns malformed data are skipped
+pub async fn fetch_erc1155_balances(
+    ctx: Arc<BalanceCallCtx>,
+    tokens: &[Erc1155Token],
+    block_id: BlockId,
+) -> Result<(HashMap<Erc1155Token, U256>, U256), ServiceError> {
+    let multicall3 = Multicall3::new(ctx.multicall3, ctx.provider.clone());
+    let owner = ctx.owner;
+
+    let calls: Vec<Multicall3::Call> = tokens
+        .iter()
+        .map(|token| Multicall3::Call {
+            target: token.contract,
+            callData: ERC1155::balanceOfCall {
+                account: owner,
+                id: token.id,
+            }
+            .abi_encode()
+            .into(),
+        })
+        .collect();
+
+    let t0 = Instant::now();
+    counter!("multicall_total").increment(1);
+
+    let call_result = multicall3
+        .tryBlockAndAggregate(false, calls)
+        .block(block_id)
+        .call()
+        .await
+        .inspect(move |_| {
+            histogram!("multicall_duration_ms").record(t0.elapsed().as_millis() as f64);
+        })
+        .map_err(|e| {
+            counter!("multicall_failed_total").increment(1);
+            histogram!("multicall_duration_ms").record(t0.elapsed().as_millis() as f64);
+            ServiceError::BalancesMultiCallError(e.to_string())
+        })?;
+
+    let mut balances: HashMap<Erc1155Token, U256> = HashMap::with_capacity(tokens.len());
+    for (token, resp) in tokens.iter().zip(call_result.returnData.iter()) {
+        if !resp.success {
+            continue;
+        }
+
+        if let Ok(balance) = <U256 as SolValue>::abi_decode(&resp.returnData) {
+            balances.insert(*token, balance);
+        }
+    }
+
+    Ok((balances, call_result.blockNumber))
+}
@@ -0,0 +1,346 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use alloy::primitives::Address;
+use metrics::{counter, gauge};
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{EvmNetwork, Token},
+    services::errors::FetcherError,
+};
+
+/// Symbolic name resolving to the union of all locally loaded token lists
+pub const DEFAULT_LOCAL_LIST_NAME: &str = "default";
+
+/// Prefix of a symbolic name addressing a single local list by its file stem
+pub const LOCAL_LIST_PREFIX: &str = "local:";
+
+// shipped with the binary, `default` resolves to it while no local lists are loaded
+const BUNDLED_LIST: &str = include_str!("../../configs/tokens_list.json");
+
+struct LocalTokenList {
+    path: PathBuf,
+    modified: SystemTime,
+    list: HashMap<u64, HashSet<Address>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LocalListFile {
+    tokens: Vec<Token>,
+}
+
+// token lists loaded from files on disk (TOKEN_LIST_PATH)
+// every path could be a json file or a directory with json files,
+// each file is addressable as `local:<file_stem>`, `default` is the union of all of them
+pub struct LocalTokenLists {
+    paths: Vec<PathBuf>,
+    lists: RwLock<HashMap<String, LocalTokenList>>,
+    bundled: HashMap<u64, HashSet<Address>>,
+}
+
+impl LocalTokenLists {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let bundled = Self::parse_list(BUNDLED_LIST.as_bytes()).unwrap_or_else(|err| {
+            tracing::error!(error = %err, "unable to parse bundled token list");
+            HashMap::new()
+        });
+
+        Self {
+            paths,
+            lists: RwLock::new(HashMap::new()),
+            bundled,
+        }
+    }
+
+    pub fn is_local_name(name: &str) -> bool {
+        name == DEFAULT_LOCAL_LIST_NAME || name.starts_with(LOCAL_LIST_PREFIX)
+    }
+
+    pub async fn names(&self) -> Vec<String> {
+        let lists = self.lists.read().await;
+        lists.keys().cloned().collect()
+    }
+
+    pub async fn get_tokens(
+        &self,
+        names: &[String],
+        network: EvmNetwork,
+    ) -> Result<HashSet<Address>, FetcherError> {
+        let lists = self.lists.read().await;
+        let mut result: HashSet<Address> = HashSet::new();
+
+        for name in names {
+            let selected: Vec<&HashMap<u64, HashSet<Address>>> =
+                if name == DEFAULT_LOCAL_LIST_NAME && lists.is_empty() {
+                    vec![&self.bundled]
+                } else if name == DEFAULT_LOCAL_LIST_NAME {
+                    lists.values().map(|list| &list.list).collect()
+                } else {
+                    let list = lists
+                        .get(name)
+                        .ok_or_else(|| FetcherError::LocalListIsNotFound(name.clone()))?;
+                    vec![&list.list]
+                };
+
+            // a session must not silently start with nothing to watch
+            let tokens: Vec<Address> = selected
+                .into_iter()
+                .filter_map(|list| list.get(&network.chain_id()))
+                .flatten()
+                .copied()
+                .collect();
+            if tokens.is_empty() {
+                return Err(FetcherError::LocalListIsEmpty(
+                    name.clone(),
+                    network.chain_id(),
+                ));
+            }
+
+            result.extend(tokens);
+        }
+
+        Ok(result)
+    }
+
+    // re-scan configured paths, (re)load new and modified files and drop removed ones
+    // if a file can't be parsed the previously loaded version is kept
+    pub async fn reload(&self) {
+        let files = self.scan_files().await;
+
+        let mut seen: HashSet<String> = HashSet::with_capacity(files.len());
+        let mut lists = self.lists.write().await;
+
+        for (name, path, modified) in files {
+            if !seen.insert(name.clone()) {
+                tracing::warn!(
+                    name = %name,
+                    path = %path.display(),
+                    "local token list with the same name is already loaded, skip"
+                );
+                continue;
+            }
+
+            let is_actual = lists
+                .get(&name)
+                .is_some_and(|list| list.path == path && list.modified == modified);
+            if is_actual {
+                continue;
+            }
+
+            match Self::load_file(&path).await {
+                Ok(list) => {
+                    counter!("local_token_list_load_total").increment(1);
+                    tracing::info!(name = %name, path = %path.display(), "local token list loaded");
+                    lists.insert(
+                        name,
+                        LocalTokenList {
+                            path,
+                            modified,
+                            list,
+                        },
+                    );
+                }
+                Err(err) => {
+                    counter!("local_token_list_load_failed_total").increment(1);
+                    tracing::error!(error = %err, "unable to load local token list");
+                }
+            }
+        }
+
+        lists.retain(|name, _| {
+            let keep = seen.contains(name);
+            if !keep {
+                tracing::info!(name = %name, "local token list removed");
+            }
+            keep
+        });
+
+        gauge!("local_token_lists").set(lists.len() as f64);
+    }
+
+    pub fn spawn_reloader(self: Arc<Self>, every: Duration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            // first tick completes immediately, initial load is done by the caller
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                self.reload().await;
+            }
+        });
+    }
+
+    // collect (name, path, modified) for every json file in the configured paths
+    async fn scan_files(&self) -> Vec<(String, PathBuf, SystemTime)> {
+        let mut files: Vec<PathBuf> = Vec::new();
+
+        for path in &self.paths {
+            let metadata = match tokio::fs::metadata(path).await {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    tracing::debug!(
+                        error = %err,
+                        path = %path.display(),
+                        "local token list path is not available"
+                    );
+                    continue;
+                }
+            };
+
+            if metadata.is_file() {
+                files.push(path.clone());
+                continue;
+            }
+
+            let mut dir = match tokio::fs::read_dir(path).await {
+                Ok(dir) => dir,
+                Err(err) => {
+                    tracing::error!(
+                        error = %err,
+                        path = %path.display(),
+                        "unable to read token lists directory"
+                    );
+                    continue;
+                }
+            };
+
+            let mut dir_files: Vec<PathBuf> = Vec::new();
+            while let Ok(Some(entry)) = dir.next_entry().await {
+                let path = entry.path();
+                if path.extension().is_some_and(|ext| ext == "json") {
+                    dir_files.push(path);
+                }
+            }
+            // keep loading order stable between reloads
+            dir_files.sort();
+            files.extend(dir_files);
+        }
+
+        let mut result = Vec::with_capacity(files.len());
+        for path in files {
+            let Some(name) = Self::list_name(&path) else {
+                continue;
+            };
+
+            match tokio::fs::metadata(&path).await.and_then(|m| m.modified()) {
+                Ok(modified) => result.push((name, path, modified)),
+                Err(err) => {
+                    tracing::error!(
+                        error = %err,
+                        path = %path.display(),
+                        "unable to read local token list metadata"
+                    );
+                }
+            }
+        }
+
+        result
+    }
+
+    fn list_name(path: &Path) -> Option<String> {
+        path.file_stem()
+            .and_then(|stem| stem.to_str())
+            .map(|stem| format!("{LOCAL_LIST_PREFIX}{stem}"))
+    }
+
+    async fn load_file(path: &Path) -> Result<HashMap<u64, HashSet<Address>>, FetcherError> {
+        let to_err =
+            |err: String| FetcherError::UnableToLoadLocalList(path.display().to_string(), err);
+
+        let content = tokio::fs::read(path)
+            .await
+            .map_err(|err| to_err(err.to_string()))?;
+
+        Self::parse_list(&content).map_err(|err| to_err(err.to_string()))
+    }
+
+    fn parse_list(content: &[u8]) -> Result<HashMap<u64, HashSet<Address>>, serde_json::Error> {
+        let file: LocalListFile = serde_json::from_slice(content)?;
+
+        let mut map_by_chain: HashMap<u64, HashSet<Address>> = HashMap::new();
+        for token in file.tokens {
+            map_by_chain
+                .entry(token.chain_id)
+                .or_default()
+                .insert(token.address);
+        }
+
+        Ok(map_by_chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list_file(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("local-token-lists-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{name}.json"));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn bundled_list_covers_all_networks() {
+        let bundled = LocalTokenLists::parse_list(BUNDLED_LIST.as_bytes()).unwrap();
+
+        for network in [EvmNetwork::Eth, EvmNetwork::Arbitrum, EvmNetwork::Sepolia] {
+            assert!(
+                bundled
+                    .get(&network.chain_id())
+                    .is_some_and(|tokens| !tokens.is_empty()),
+                "no bundled tokens for {network:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn default_falls_back_to_bundled_list() {
+        let lists = LocalTokenLists::new(vec![PathBuf::from("/nonexistent/tokens.json")]);
+        lists.reload().await;
+
+        let tokens = lists
+            .get_tokens(&[DEFAULT_LOCAL_LIST_NAME.to_string()], EvmNetwork::Eth)
+            .await
+            .unwrap();
+        assert_eq!(tokens, lists.bundled[&EvmNetwork::Eth.chain_id()]);
+    }
+
+    #[tokio::test]
+    async fn rejects_list_without_tokens_for_network() {
+        let path = list_file(
+            "mainnet_only",
+            r#"{"tokens": [{"chainId": 1, "address": "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48", "name": "USD Coin", "decimals": 6}]}"#,
+        );
+        let lists = LocalTokenLists::new(vec![path]);
+        lists.reload().await;
+        let names = ["local:mainnet_only".to_string()];
+
+        assert_eq!(
+            lists
+                .get_tokens(&names, EvmNetwork::Eth)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(matches!(
+            lists.get_tokens(&names, EvmNetwork::Sepolia).await,
+            Err(FetcherError::LocalListIsEmpty(_, 11155111))
+        ));
+        assert!(matches!(
+            lists
+                .get_tokens(&["local:missing".to_string()], EvmNetwork::Eth)
+                .await,
+            Err(FetcherError::LocalListIsNotFound(_))
+        ));
+    }
+}
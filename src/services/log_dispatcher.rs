@@ -0,0 +1,878 @@
+use crate::config::cow_address::{COW_ETH_FLOW_ADDRESSES, COW_SETTLEMENT_ADDRESS};
+use crate::config::entry_point_address::ENTRY_POINT_ADDRESSES;
+use crate::config::log_source_config::{LogSourceConfig, LogSourceMode, ReconnectConfig};
+use crate::domain::EvmNetwork;
+use crate::evm::{
+    cow::{CoWSwapEthFlow, GPv2Settlement},
+    erc1155::ERC1155,
+    erc20::ERC20,
+    smart_account::{EntryPoint, Safe},
+    wrapped::WrappedToken,
+};
+use alloy::{
+    primitives::{Address, B256},
+    providers::{DynProvider, Provider, ProviderBuilder, WsConnect},
+    rpc::types::{Filter, Log},
+    sol_types::SolEvent,
+    transports::TransportResult,
+};
+use futures::StreamExt;
+use metrics::{counter, gauge};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+
+// one log source per network shared by all sessions
+// it listens to all Transfer/Deposit/Withdrawal/TransferSingle/TransferBatch logs and logs of contract
+// wallets (Safe ExecutionSuccess/SafeReceived, EntryPoint UserOperationEvent) and routes every log
+// to sessions of owners found in it (Transfer: from/to, Deposit/Withdrawal: dst/src,
+// TransferSingle/TransferBatch: from/to, Safe events: emitter, UserOperationEvent: sender),
+// networks with CoW tracking also listen to settlement Trade (owner) and EthFlow OrderPlacement (sender),
+// so the number of provider subscriptions doesn't depend on the number of sessions
+// logs come from a ws subscription or from eth_getLogs polling over http (see LogSourceMode)
+pub struct LogDispatcher {
+    network: EvmNetwork,
+    // used to recreate ws provider when its transport is dead
+    ws_url: String,
+    ws_provider: Option<DynProvider>,
+    http_provider: DynProvider,
+    mode: LogSourceMode,
+    config: LogSourceConfig,
+    reconnect: ReconnectConfig,
+    // std lock: routes are removed in Drop of LogRegistration
+    routes: RwLock<HashMap<Address, HashMap<u64, mpsc::UnboundedSender<SourceEvent>>>>,
+    next_id: AtomicU64,
+    started: AtomicBool,
+    degraded: AtomicBool,
+}
+
+/// Events routed to registered owners
+#[derive(Debug, Clone)]
+pub enum SourceEvent {
+    Log(Box<Log>),
+    /// Log source can't reconnect, events are not received until it recovers
+    Degraded,
+    /// Log source is back, events of the outage could be missed
+    Recovered,
+    /// Log source resubscribed before it became degraded, events of the gap could be missed
+    Resubscribed,
+}
+
+/// Events of one owner, the route is removed when the registration is dropped
+pub struct LogRegistration {
+    id: u64,
+    owner: Address,
+    receiver: mpsc::UnboundedReceiver<SourceEvent>,
+    dispatcher: Arc<LogDispatcher>,
+}
+
+impl LogRegistration {
+    pub async fn recv(&mut self) -> Option<SourceEvent> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for LogRegistration {
+    fn drop(&mut self) {
+        self.dispatcher.unregister(self.owner, self.id);
+    }
+}
+
+impl LogDispatcher {
+    pub fn new(
+        network: EvmNetwork,
+        ws_url: String,
+        ws_provider: Option<DynProvider>,
+        http_provider: DynProvider,
+        config: LogSourceConfig,
+    ) -> Self {
+        // auto mode polls if ws provider is not available on start,
+        // ws mode keeps reconnecting until it is
+        let mode = match (config.mode(network), &ws_provider) {
+            (LogSourceMode::Auto, None) => LogSourceMode::Polling,
+            (mode, _) => mode,
+        };
+        let reconnect = config.reconnect(network);
+
+        Self {
+            network,
+            ws_url,
+            ws_provider,
+            http_provider,
+            mode,
+            config,
+            reconnect,
+            routes: RwLock::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            started: AtomicBool::new(false),
+            degraded: AtomicBool::new(false),
+        }
+    }
+
+    // route logs of the owner to the registration
+    // the shared subscription is started with the first registration
+    pub fn register(self: &Arc<Self>, owner: Address) -> LogRegistration {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+
+        {
+            let mut routes = self.routes.write().unwrap_or_else(|e| e.into_inner());
+            // the session should know that the source is already degraded
+            if self.degraded.load(Ordering::SeqCst) {
+                let _ = sender.send(SourceEvent::Degraded);
+            }
+            routes.entry(owner).or_default().insert(id, sender);
+            gauge!("log_dispatcher_owners", "network" => self.network.to_string())
+                .set(routes.len() as f64);
+        }
+
+        if !self.started.swap(true, Ordering::SeqCst) {
+            tokio::spawn(Arc::clone(self).run());
+        }
+
+        LogRegistration {
+            id,
+            owner,
+            receiver,
+            dispatcher: Arc::clone(self),
+        }
+    }
+
+    fn unregister(&self, owner: Address, id: u64) {
+        let mut routes = self.routes.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(owner_routes) = routes.get_mut(&owner) {
+            owner_routes.remove(&id);
+            if owner_routes.is_empty() {
+                routes.remove(&owner);
+            }
+        }
+
+        gauge!("log_dispatcher_owners", "network" => self.network.to_string())
+            .set(routes.len() as f64);
+    }
+
+    async fn run(self: Arc<Self>) {
+        let cow = self.config.cow_networks.contains(&self.network);
+        let mut signatures = vec![
+            ERC20::Transfer::SIGNATURE_HASH,
+            WrappedToken::Deposit::SIGNATURE_HASH,
+            WrappedToken::Withdrawal::SIGNATURE_HASH,
+            ERC1155::TransferSingle::SIGNATURE_HASH,
+            ERC1155::TransferBatch::SIGNATURE_HASH,
+            Safe::ExecutionSuccess::SIGNATURE_HASH,
+            Safe::SafeReceived::SIGNATURE_HASH,
+            EntryPoint::UserOperationEvent::SIGNATURE_HASH,
+        ];
+        if cow {
+            signatures.push(GPv2Settlement::Trade::SIGNATURE_HASH);
+            signatures.push(CoWSwapEthFlow::OrderPlacement::SIGNATURE_HASH);
+        }
+        let filter = Filter::new().event_signature(signatures);
+
+        tracing::info!(network = %self.network, mode = self.mode.as_str(), cow, "start log source");
+
+        if matches!(self.mode, LogSourceMode::Ws | LogSourceMode::Auto) {
+            self.set_polling_gauge(false);
+            self.run_log_subscription_loop(&filter).await;
+            // polling works independently of ws transport and starts from the current head,
+            // logs since the ws subscription failed are missed
+            self.notify_gap();
+        }
+
+        self.set_polling_gauge(true);
+        self.run_polling_loop(filter).await;
+    }
+
+    // notify all sessions when the source becomes degraded or recovers
+    // returns false if the state is not changed
+    fn set_degraded(&self, degraded: bool) -> bool {
+        if self.degraded.swap(degraded, Ordering::SeqCst) == degraded {
+            return false;
+        }
+
+        gauge!("log_source_degraded", "network" => self.network.to_string()).set(if degraded {
+            1.0
+        } else {
+            0.0
+        });
+        if degraded {
+            tracing::error!(network = %self.network, "log source is degraded");
+        } else {
+            tracing::info!(network = %self.network, "log source is recovered");
+        }
+
+        self.broadcast(if degraded {
+            SourceEvent::Degraded
+        } else {
+            SourceEvent::Recovered
+        });
+        true
+    }
+
+    // logs between a failure and the new subscription are not received,
+    // sessions resync either on Recovered of a degraded source or on Resubscribed
+    fn notify_gap(&self) {
+        if self.set_degraded(false) {
+            return;
+        }
+
+        counter!("log_source_resubscribed_total", "network" => self.network.to_string())
+            .increment(1);
+        self.broadcast(SourceEvent::Resubscribed);
+    }
+
+    fn broadcast(&self, event: SourceEvent) {
+        let routes = self.routes.read().unwrap_or_else(|e| e.into_inner());
+        for sender in routes
+            .values()
+            .flat_map(|owner_routes| owner_routes.values())
+        {
+            let _ = sender.send(event.clone());
+        }
+    }
+
+    async fn connect_ws(&self) -> Result<DynProvider, String> {
+        counter!("ws_provider_connects_total", "network" => self.network.to_string()).increment(1);
+
+        ProviderBuilder::new()
+            .connect_ws(WsConnect::new(self.ws_url.clone()))
+            .await
+            .map(|provider| provider.erased())
+            .map_err(|err| err.to_string())
+    }
+
+    fn set_polling_gauge(&self, polling: bool) {
+        gauge!("log_source_polling", "network" => self.network.to_string()).set(if polling {
+            1.0
+        } else {
+            0.0
+        });
+    }
+
+    // create a subscription to ws provider and run a loop to listen to logs
+    // if log is received - route it to owners
+    // if ws provider disconnects - reconnect with backoff and continue listening,
+    // the provider is recreated when its transport is dead
+    // returns only in auto mode when subscribing keeps failing, so the caller switches to polling
+    async fn run_log_subscription_loop(&self, filter: &Filter) {
+        let mut ws_provider = self.ws_provider.clone();
+        let mut attempt: u32 = 0;
+        // a previous subscription failed or its stream ended
+        let mut interrupted = false;
+
+        loop {
+            let provider = match ws_provider.take() {
+                Some(provider) => Ok(provider),
+                None => self.connect_ws().await,
+            };
+
+            match provider {
+                Ok(provider) => match provider.subscribe_logs(filter).await {
+                    Ok(sub) => {
+                        tracing::info!(network = %self.network, "subscribed to logs");
+                        attempt = 0;
+                        if interrupted {
+                            self.notify_gap();
+                        }
+
+                        let mut stream = sub.into_stream();
+                        while let Some(log) = stream.next().await {
+                            counter!("events_received_total").increment(1);
+                            self.dispatch(log);
+                        }
+
+                        // stream ends when the transport gave up reconnecting
+                        counter!("ws_provider_disconnected_total").increment(1);
+                        tracing::warn!(
+                            network = %self.network,
+                            "ws stream ended (disconnect). will recreate provider"
+                        );
+                    }
+                    Err(err) => {
+                        counter!("ws_subscribe_errors_total").increment(1);
+                        tracing::error!(
+                            error = %err,
+                            network = %self.network,
+                            "error to subscribe on logs"
+                        );
+
+                        // keep the provider if only the request failed
+                        if !err.is_transport_error() {
+                            ws_provider = Some(provider);
+                        }
+                    }
+                },
+                Err(err) => {
+                    counter!("ws_connect_errors_total").increment(1);
+                    tracing::error!(
+                        error = %err,
+                        network = %self.network,
+                        "unable to connect ws provider"
+                    );
+                }
+            }
+
+            interrupted = true;
+            attempt = attempt.saturating_add(1);
+
+            let degraded_after = self.reconnect.degraded_after;
+            if degraded_after > 0 && attempt >= degraded_after {
+                self.set_degraded(true);
+            }
+
+            let threshold = self.config.ws_failures_before_polling;
+            if self.mode == LogSourceMode::Auto && threshold > 0 && attempt >= threshold {
+                counter!("log_source_fallbacks_total", "network" => self.network.to_string())
+                    .increment(1);
+                tracing::warn!(
+                    network = %self.network,
+                    attempt,
+                    "ws subscription keeps failing, switch to http polling"
+                );
+                return;
+            }
+
+            let delay = self.reconnect.delay(attempt);
+            tracing::info!(
+                network = %self.network,
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "reconnect ws log subscription"
+            );
+            tokio::time::sleep(delay).await;
+            counter!("ws_reconnect_attempts_total").increment(1);
+        }
+    }
+
+    // poll eth_blockNumber and fetch logs of every new block range via eth_getLogs
+    // polling starts from the current head, earlier state is covered by balance snapshots
+    // only logs of registered owners are requested unless there are too many of them
+    // a failed range is retried on the next tick, a range over the provider result cap is halved
+    async fn run_polling_loop(&self, filter: Filter) {
+        tracing::info!(network = %self.network, "polling logs over http");
+
+        let mut interval = tokio::time::interval(self.config.poll_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut last_block: Option<u64> = None;
+
+        loop {
+            interval.tick().await;
+
+            let latest = match self.http_provider.get_block_number().await {
+                Ok(latest) => latest,
+                Err(err) => {
+                    counter!("log_polling_errors_total", "network" => self.network.to_string())
+                        .increment(1);
+                    tracing::warn!(error = %err, network = %self.network, "unable to poll block number");
+                    continue;
+                }
+            };
+
+            let Some(mut from_block) = last_block.map(|block| block + 1) else {
+                last_block = Some(latest);
+                continue;
+            };
+
+            let filters = {
+                let routes = self.routes.read().unwrap_or_else(|e| e.into_inner());
+                let owners: Vec<Address> = routes.keys().copied().collect();
+                owner_filters(&filter, &owners, self.config.poll_max_filtered_owners)
+            };
+
+            // catch up in pages if polling fell behind
+            let mut block_range = self.config.poll_block_range;
+            while from_block <= latest {
+                let to_block = latest.min(from_block + block_range - 1);
+
+                match self.poll_range(&filters, from_block, to_block).await {
+                    Ok(logs) => {
+                        for log in logs.into_iter().filter(|log| !log.removed) {
+                            counter!("events_received_total").increment(1);
+                            self.dispatch(log);
+                        }
+
+                        last_block = Some(to_block);
+                        from_block = to_block + 1;
+                    }
+                    Err(err) if to_block > from_block && is_too_many_results(&err.to_string()) => {
+                        block_range = (to_block - from_block).div_ceil(2);
+                        counter!("log_polling_range_splits_total", "network" => self.network.to_string())
+                            .increment(1);
+                        tracing::debug!(
+                            error = %err,
+                            network = %self.network,
+                            from_block,
+                            to_block,
+                            block_range,
+                            "too many logs in range, retry with a smaller range"
+                        );
+                    }
+                    Err(err) => {
+                        counter!("log_polling_errors_total", "network" => self.network.to_string())
+                            .increment(1);
+                        tracing::warn!(
+                            error = %err,
+                            network = %self.network,
+                            from_block,
+                            to_block,
+                            "unable to poll logs"
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn poll_range(
+        &self,
+        filters: &[Filter],
+        from_block: u64,
+        to_block: u64,
+    ) -> TransportResult<Vec<Log>> {
+        let mut logs = Vec::new();
+        for filter in filters {
+            let range_filter = filter.clone().from_block(from_block).to_block(to_block);
+            logs.extend(self.http_provider.get_logs(&range_filter).await?);
+        }
+
+        Ok(merge_logs(logs))
+    }
+
+    fn dispatch(&self, log: Log) {
+        let owners = Self::log_owners(&log);
+        if owners.is_empty() {
+            return;
+        }
+
+        let routes = self.routes.read().unwrap_or_else(|e| e.into_inner());
+        for owner in owners {
+            let Some(owner_routes) = routes.get(&owner) else {
+                continue;
+            };
+
+            for sender in owner_routes.values() {
+                counter!("log_dispatcher_routed_total").increment(1);
+                let _ = sender.send(SourceEvent::Log(Box::new(log.clone())));
+            }
+        }
+    }
+
+    // Transfer(from, to) - both topics could be watched owners
+    // Deposit(dst)/Withdrawal(src) - topic1
+    // TransferSingle/TransferBatch(operator, from, to) - topic2 and topic3
+    // ExecutionSuccess/SafeReceived - the Safe emitting the log
+    // UserOperationEvent(userOpHash, sender) - topic2, only from known EntryPoints
+    // Trade(owner)/OrderPlacement(sender) - topic1, only from CoW contracts
+    fn log_owners(log: &Log) -> Vec<Address> {
+        let topics = log.topics();
+        let Some(topic0) = topics.first() else {
+            return vec![];
+        };
+
+        if *topic0 == Safe::ExecutionSuccess::SIGNATURE_HASH
+            || *topic0 == Safe::SafeReceived::SIGNATURE_HASH
+        {
+            return vec![log.address()];
+        }
+
+        let is_cow_log = (*topic0 == GPv2Settlement::Trade::SIGNATURE_HASH
+            && log.address() == COW_SETTLEMENT_ADDRESS)
+            || (*topic0 == CoWSwapEthFlow::OrderPlacement::SIGNATURE_HASH
+                && COW_ETH_FLOW_ADDRESSES.contains(&log.address()));
+        if is_cow_log {
+            return topics
+                .get(1)
+                .map(|topic| vec![Address::from_word(*topic)])
+                .unwrap_or_default();
+        }
+
+        if *topic0 == EntryPoint::UserOperationEvent::SIGNATURE_HASH {
+            if !ENTRY_POINT_ADDRESSES.contains(&log.address()) {
+                return vec![];
+            }
+            return topics
+                .get(2)
+                .map(|topic| vec![Address::from_word(*topic)])
+                .unwrap_or_default();
+        }
+
+        let indexed: &[B256] = if *topic0 == ERC20::Transfer::SIGNATURE_HASH {
+            topics.get(1..3).unwrap_or_default()
+        } else if *topic0 == ERC1155::TransferSingle::SIGNATURE_HASH
+            || *topic0 == ERC1155::TransferBatch::SIGNATURE_HASH
+        {
+            topics.get(2..4).unwrap_or_default()
+        } else {
+            topics.get(1..2).unwrap_or_default()
+        };
+
+        let mut owners: Vec<Address> = indexed
+            .iter()
+            .map(|topic| Address::from_word(*topic))
+            .collect();
+        // self transfer
+        owners.dedup();
+
+        owners
+    }
+}
+
+// eth_getLogs filters with registered owners as indexed topics, the positions are AND-ed
+// by the node, so every position of an owner is a separate filter:
+// topic1 - Transfer sender, Deposit/Withdrawal account, CoW trade/order owner,
+// topic2 - Transfer recipient, ERC-1155 sender, UserOperation sender,
+// topic3 - ERC-1155 recipient, and Safe events are emitted by the owner itself
+// above `max_owners` (or with 0) the network-wide filter is used
+fn owner_filters(filter: &Filter, owners: &[Address], max_owners: usize) -> Vec<Filter> {
+    if max_owners == 0 || owners.len() > max_owners {
+        return vec![filter.clone()];
+    }
+    // an empty set would match any topic
+    if owners.is_empty() {
+        return vec![];
+    }
+
+    let topics: Vec<B256> = owners.iter().map(|owner| owner.into_word()).collect();
+    vec![
+        filter.clone().topic1(topics.clone()),
+        filter.clone().topic2(topics.clone()),
+        filter
+            .clone()
+            .event_signature(vec![
+                ERC1155::TransferSingle::SIGNATURE_HASH,
+                ERC1155::TransferBatch::SIGNATURE_HASH,
+            ])
+            .topic3(topics),
+        filter
+            .clone()
+            .event_signature(vec![
+                Safe::ExecutionSuccess::SIGNATURE_HASH,
+                Safe::SafeReceived::SIGNATURE_HASH,
+            ])
+            .address(owners.to_vec()),
+    ]
+}
+
+// owner filters overlap (a self transfer matches topic1 and topic2),
+// logs are deduplicated and dispatched in chain order
+fn merge_logs(mut logs: Vec<Log>) -> Vec<Log> {
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    logs.dedup_by_key(|log| (log.block_number, log.log_index));
+    logs
+}
+
+// providers reject eth_getLogs over their result or range caps with different messages
+fn is_too_many_results(err: &str) -> bool {
+    const MARKERS: [&str; 7] = [
+        "more than",
+        "too many",
+        "response size",
+        "limit exceeded",
+        "limit is exceeded",
+        "block range",
+        "range is too large",
+    ];
+
+    let err = err.to_lowercase();
+    MARKERS.iter().any(|marker| err.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{address, Bytes, LogData};
+    use std::time::Duration;
+
+    const TOKEN: Address = address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const ALICE: Address = address!("0x00000000000000000000000000000000000000a1");
+    const BOB: Address = address!("0x00000000000000000000000000000000000000b0");
+
+    fn dispatcher() -> LogDispatcher {
+        let reconnect = ReconnectConfig {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            degraded_after: 0,
+        };
+        let config = LogSourceConfig {
+            mode: LogSourceMode::Ws,
+            polling_networks: Default::default(),
+            poll_interval: Duration::from_secs(1),
+            poll_block_range: 10,
+            poll_max_filtered_owners: 2,
+            ws_failures_before_polling: 0,
+            reconnect,
+            reconnect_overrides: Default::default(),
+            cow_networks: Default::default(),
+        };
+        let http_provider = ProviderBuilder::new()
+            .connect_http("http://127.0.0.1:8545".parse().unwrap())
+            .erased();
+
+        LogDispatcher::new(
+            EvmNetwork::Eth,
+            "ws://127.0.0.1:8546".to_string(),
+            None,
+            http_provider,
+            config,
+        )
+    }
+
+    // routes are added directly, register would start the log source
+    fn route(dispatcher: &LogDispatcher, owner: Address) -> mpsc::UnboundedReceiver<SourceEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut routes = dispatcher.routes.write().unwrap();
+        routes.entry(owner).or_default().insert(0, sender);
+        receiver
+    }
+
+    #[test]
+    fn gap_without_degraded_state_resubscribes() {
+        let dispatcher = dispatcher();
+        let mut receiver = route(&dispatcher, ALICE);
+
+        dispatcher.notify_gap();
+
+        assert!(matches!(receiver.try_recv(), Ok(SourceEvent::Resubscribed)));
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn gap_of_degraded_source_recovers() {
+        let dispatcher = dispatcher();
+        let mut receiver = route(&dispatcher, ALICE);
+
+        assert!(dispatcher.set_degraded(true));
+        assert!(!dispatcher.set_degraded(true));
+        dispatcher.notify_gap();
+
+        assert!(matches!(receiver.try_recv(), Ok(SourceEvent::Degraded)));
+        assert!(matches!(receiver.try_recv(), Ok(SourceEvent::Recovered)));
+        assert!(receiver.try_recv().is_err());
+        assert!(!dispatcher.degraded.load(Ordering::SeqCst));
+    }
+
+    fn log(address: Address, topics: Vec<B256>) -> Log {
+        Log {
+            inner: alloy::primitives::Log {
+                address,
+                data: LogData::new_unchecked(topics, Bytes::new()),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn routes_transfer_to_both_sides() {
+        let log = log(
+            TOKEN,
+            vec![
+                ERC20::Transfer::SIGNATURE_HASH,
+                ALICE.into_word(),
+                BOB.into_word(),
+            ],
+        );
+        assert_eq!(LogDispatcher::log_owners(&log), vec![ALICE, BOB]);
+    }
+
+    #[test]
+    fn routes_self_transfer_once() {
+        let log = log(
+            TOKEN,
+            vec![
+                ERC20::Transfer::SIGNATURE_HASH,
+                ALICE.into_word(),
+                ALICE.into_word(),
+            ],
+        );
+        assert_eq!(LogDispatcher::log_owners(&log), vec![ALICE]);
+    }
+
+    #[test]
+    fn routes_wrapped_token_events_to_account() {
+        let deposit = log(
+            TOKEN,
+            vec![WrappedToken::Deposit::SIGNATURE_HASH, ALICE.into_word()],
+        );
+        assert_eq!(LogDispatcher::log_owners(&deposit), vec![ALICE]);
+
+        let withdrawal = log(
+            TOKEN,
+            vec![WrappedToken::Withdrawal::SIGNATURE_HASH, BOB.into_word()],
+        );
+        assert_eq!(LogDispatcher::log_owners(&withdrawal), vec![BOB]);
+    }
+
+    #[test]
+    fn routes_erc1155_transfers_without_operator() {
+        let operator = address!("0x00000000000000000000000000000000000000c0");
+        let log = log(
+            TOKEN,
+            vec![
+                ERC1155::TransferSingle::SIGNATURE_HASH,
+                operator.into_word(),
+                ALICE.into_word(),
+                BOB.into_word(),
+            ],
+        );
+        assert_eq!(LogDispatcher::log_owners(&log), vec![ALICE, BOB]);
+    }
+
+    #[test]
+    fn routes_safe_events_to_emitter() {
+        let log = log(
+            ALICE,
+            vec![Safe::SafeReceived::SIGNATURE_HASH, BOB.into_word()],
+        );
+        assert_eq!(LogDispatcher::log_owners(&log), vec![ALICE]);
+    }
+
+    #[test]
+    fn routes_user_operations_of_known_entry_points_only() {
+        let topics = vec![
+            EntryPoint::UserOperationEvent::SIGNATURE_HASH,
+            B256::repeat_byte(1),
+            ALICE.into_word(),
+            BOB.into_word(),
+        ];
+        assert_eq!(
+            LogDispatcher::log_owners(&log(ENTRY_POINT_ADDRESSES[1], topics.clone())),
+            vec![ALICE]
+        );
+        assert!(LogDispatcher::log_owners(&log(TOKEN, topics)).is_empty());
+    }
+
+    #[test]
+    fn routes_cow_events_of_known_contracts_only() {
+        let topics = vec![GPv2Settlement::Trade::SIGNATURE_HASH, ALICE.into_word()];
+        assert_eq!(
+            LogDispatcher::log_owners(&log(COW_SETTLEMENT_ADDRESS, topics)),
+            vec![ALICE]
+        );
+
+        let placement = vec![
+            CoWSwapEthFlow::OrderPlacement::SIGNATURE_HASH,
+            BOB.into_word(),
+        ];
+        assert_eq!(
+            LogDispatcher::log_owners(&log(COW_ETH_FLOW_ADDRESSES[0], placement)),
+            vec![BOB]
+        );
+    }
+
+    #[test]
+    fn ignores_logs_without_topics() {
+        assert!(LogDispatcher::log_owners(&log(TOKEN, vec![])).is_empty());
+        let truncated = log(
+            TOKEN,
+            vec![ERC20::Transfer::SIGNATURE_HASH, ALICE.into_word()],
+        );
+        assert!(LogDispatcher::log_owners(&truncated).is_empty());
+    }
+
+    #[test]
+    fn detects_result_cap_errors() {
+        for err in [
+            "server returned an error response: error code -32005: query returned more than 10000 results",
+            "Log response size exceeded. You can make eth_getLogs requests with up to a 2K block range",
+            "eth_getLogs block range is too large",
+            "error code -32602: block range limit exceeded",
+        ] {
+            assert!(is_too_many_results(err), "{err}");
+        }
+
+        assert!(!is_too_many_results("error sending request for url"));
+        assert!(!is_too_many_results("connection reset by peer"));
+    }
+
+    fn base_filter() -> Filter {
+        Filter::new().event_signature(vec![
+            ERC20::Transfer::SIGNATURE_HASH,
+            ERC1155::TransferSingle::SIGNATURE_HASH,
+            Safe::SafeReceived::SIGNATURE_HASH,
+        ])
+    }
+
+    fn matched(filters: &[Filter], log: &Log) -> bool {
+        filters.iter().any(|filter| filter.matches(&log.inner))
+    }
+
+    #[test]
+    fn owner_filters_match_every_position_of_owner() {
+        let filters = owner_filters(&base_filter(), &[ALICE], 10);
+
+        let sent = log(
+            TOKEN,
+            vec![
+                ERC20::Transfer::SIGNATURE_HASH,
+                ALICE.into_word(),
+                BOB.into_word(),
+            ],
+        );
+        let received = log(
+            TOKEN,
+            vec![
+                ERC20::Transfer::SIGNATURE_HASH,
+                BOB.into_word(),
+                ALICE.into_word(),
+            ],
+        );
+        let erc1155_received = log(
+            TOKEN,
+            vec![
+                ERC1155::TransferSingle::SIGNATURE_HASH,
+                BOB.into_word(),
+                BOB.into_word(),
+                ALICE.into_word(),
+            ],
+        );
+        let safe_received = log(ALICE, vec![Safe::SafeReceived::SIGNATURE_HASH]);
+        for log in [&sent, &received, &erc1155_received, &safe_received] {
+            assert!(matched(&filters, log), "{log:?}");
+        }
+
+        let other = log(
+            TOKEN,
+            vec![
+                ERC20::Transfer::SIGNATURE_HASH,
+                BOB.into_word(),
+                TOKEN.into_word(),
+            ],
+        );
+        let other_safe = log(BOB, vec![Safe::SafeReceived::SIGNATURE_HASH]);
+        assert!(!matched(&filters, &other));
+        assert!(!matched(&filters, &other_safe));
+    }
+
+    #[test]
+    fn owner_filters_fall_back_to_network_filter() {
+        let filter = base_filter();
+        assert!(owner_filters(&filter, &[], 10).is_empty());
+        assert_eq!(
+            owner_filters(&filter, &[ALICE, BOB], 1),
+            vec![filter.clone()]
+        );
+        assert_eq!(owner_filters(&filter, &[ALICE], 0), vec![filter]);
+    }
+
+    #[test]
+    fn merges_overlapping_logs_in_chain_order() {
+        let at = |block: u64, index: u64| Log {
+            block_number: Some(block),
+            log_index: Some(index),
+            ..log(TOKEN, vec![ERC20::Transfer::SIGNATURE_HASH])
+        };
+
+        let merged = merge_logs(vec![at(2, 0), at(1, 5), at(2, 0), at(1, 1), at(1, 5)]);
+        let order: Vec<_> = merged
+            .iter()
+            .map(|log| (log.block_number, log.log_index))
+            .collect();
+        assert_eq!(
+            order,
+            vec![(Some(1), Some(1)), (Some(1), Some(5)), (Some(2), Some(0))]
+        );
+    }
+}
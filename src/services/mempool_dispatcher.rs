@@ -0,0 +1,463 @@
+use crate::config::log_source_config::ReconnectConfig;
+use crate::domain::EvmNetwork;
+use crate::evm::{erc20::ERC20, wrapped::WrappedToken};
+use alloy::consensus::Transaction as _;
+use alloy::network::TransactionResponse;
+use alloy::{
+    primitives::{Address, B256, U256},
+    providers::{Provider, ProviderBuilder, WsConnect},
+    rpc::types::Transaction,
+    sol_types::SolCall,
+};
+use futures::StreamExt;
+use metrics::{counter, gauge};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::{mpsc, Notify};
+
+/// Balance change of an account expected from a pending transaction
+#[derive(Debug, Clone, Copy)]
+pub struct PendingDelta {
+    pub account: Address,
+    pub token: Address,
+    pub amount: U256,
+    pub incoming: bool,
+}
+
+/// Pending transaction with the deltas of one owner
+#[derive(Debug, Clone)]
+pub struct PendingTx {
+    pub hash: B256,
+    pub from: Address,
+    pub nonce: u64,
+    pub deltas: Vec<PendingDelta>,
+}
+
+/// Pending transactions of one owner, the route is removed when the registration is dropped
+pub struct MempoolRegistration {
+    id: u64,
+    owner: Address,
+    receiver: mpsc::UnboundedReceiver<PendingTx>,
+    dispatcher: Arc<MempoolDispatcher>,
+}
+
+impl MempoolRegistration {
+    pub async fn recv(&mut self) -> Option<PendingTx> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for MempoolRegistration {
+    fn drop(&mut self) {
+        self.dispatcher.unregister(self.owner, self.id);
+    }
+}
+
+// one subscription to full pending transactions per network shared by all sessions
+// transactions are decoded (ERC20 transfer/transferFrom, wrapped token deposit/withdraw, native sends)
+// and routed to sessions of the accounts whose balances they change
+pub struct MempoolDispatcher {
+    network: EvmNetwork,
+    ws_url: String,
+    // wrapper -> underlying
+    wrapped_tokens: HashMap<Address, Address>,
+    reconnect: ReconnectConfig,
+    routes: RwLock<HashMap<Address, HashMap<u64, mpsc::UnboundedSender<PendingTx>>>>,
+    next_id: AtomicU64,
+    started: AtomicBool,
+    // the last registration is dropped, the subscription is closed
+    routes_emptied: Notify,
+}
+
+impl MempoolDispatcher {
+    pub fn new(
+        network: EvmNetwork,
+        ws_url: String,
+        wrapped_tokens: HashMap<Address, Address>,
+        reconnect: ReconnectConfig,
+    ) -> Self {
+        Self {
+            network,
+            ws_url,
+            wrapped_tokens,
+            reconnect,
+            routes: RwLock::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            started: AtomicBool::new(false),
+            routes_emptied: Notify::new(),
+        }
+    }
+
+    // the shared subscription is started with the first registration and closed with the last one
+    pub fn register(self: &Arc<Self>, owner: Address) -> MempoolRegistration {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+
+        {
+            let mut routes = self.routes.write().unwrap_or_else(|e| e.into_inner());
+            routes.entry(owner).or_default().insert(id, sender);
+            gauge!("mempool_dispatcher_owners", "network" => self.network.to_string())
+                .set(routes.len() as f64);
+        }
+
+        if !self.started.swap(true, Ordering::SeqCst) {
+            tokio::spawn(Arc::clone(self).run());
+        }
+
+        MempoolRegistration {
+            id,
+            owner,
+            receiver,
+            dispatcher: Arc::clone(self),
+        }
+    }
+
+    fn unregister(&self, owner: Address, id: u64) {
+        let mut routes = self.routes.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(owner_routes) = routes.get_mut(&owner) {
+            owner_routes.remove(&id);
+            if owner_routes.is_empty() {
+                routes.remove(&owner);
+            }
+        }
+
+        gauge!("mempool_dispatcher_owners", "network" => self.network.to_string())
+            .set(routes.len() as f64);
+        if routes.is_empty() {
+            self.routes_emptied.notify_one();
+        }
+    }
+
+    fn has_routes(&self) -> bool {
+        let routes = self.routes.read().unwrap_or_else(|e| e.into_inner());
+        !routes.is_empty()
+    }
+
+    // a registration added while the task is stopping keeps it running
+    fn should_stop(&self) -> bool {
+        if self.has_routes() {
+            return false;
+        }
+
+        self.started.store(false, Ordering::SeqCst);
+        !self.has_routes() || self.started.swap(true, Ordering::SeqCst)
+    }
+
+    // subscribe over ws and reconnect with backoff, pending transactions can't be polled
+    async fn run(self: Arc<Self>) {
+        tracing::info!(network = %self.network, "start mempool subscription");
+        let mut attempt: u32 = 0;
+
+        loop {
+            if self.should_stop() {
+                break;
+            }
+
+            let subscription = match ProviderBuilder::new()
+                .connect_ws(WsConnect::new(self.ws_url.clone()))
+                .await
+            {
+                Ok(provider) => provider
+                    .subscribe_full_pending_transactions()
+                    .await
+                    .map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+
+            match subscription {
+                Ok(subscription) => {
+                    tracing::info!(network = %self.network, "subscribed to pending transactions");
+                    attempt = 0;
+
+                    let mut stream = subscription.into_stream();
+                    loop {
+                        tokio::select! {
+                            tx = stream.next() => {
+                                let Some(tx) = tx else {
+                                    tracing::warn!(network = %self.network, "pending transactions stream ended");
+                                    break;
+                                };
+                                counter!("mempool_transactions_received_total").increment(1);
+                                self.dispatch(&tx);
+                            }
+                            _ = self.routes_emptied.notified() => {
+                                if !self.has_routes() {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+
+                    // closed without sessions, restarted by the next registration
+                    if !self.has_routes() {
+                        continue;
+                    }
+                }
+                Err(err) => {
+                    counter!("mempool_subscribe_errors_total").increment(1);
+                    tracing::error!(
+                        error = %err,
+                        network = %self.network,
+                        "unable to subscribe to pending transactions"
+                    );
+                }
+            }
+
+            attempt = attempt.saturating_add(1);
+            tokio::time::sleep(self.reconnect.delay(attempt)).await;
+        }
+
+        tracing::info!(network = %self.network, "stop mempool subscription");
+    }
+
+    fn dispatch(&self, tx: &Transaction) {
+        let deltas = self.decode(tx);
+        if deltas.is_empty() {
+            return;
+        }
+
+        let routes = self.routes.read().unwrap_or_else(|e| e.into_inner());
+        let mut owners: Vec<Address> = deltas.iter().map(|delta| delta.account).collect();
+        owners.sort();
+        owners.dedup();
+
+        for owner in owners {
+            let Some(owner_routes) = routes.get(&owner) else {
+                continue;
+            };
+
+            let pending_tx = PendingTx {
+                hash: tx.tx_hash(),
+                from: tx.from(),
+                nonce: tx.nonce(),
+                deltas: deltas
+                    .iter()
+                    .filter(|delta| delta.account == owner)
+                    .copied()
+                    .collect(),
+            };
+
+            for sender in owner_routes.values() {
+                counter!("mempool_dispatcher_routed_total").increment(1);
+                let _ = sender.send(pending_tx.clone());
+            }
+        }
+    }
+
+    fn decode(&self, tx: &Transaction) -> Vec<PendingDelta> {
+        let Some(target) = tx.to() else {
+            return vec![];
+        };
+
+        self.deltas(tx.from(), target, tx.input(), tx.value())
+    }
+
+    // balance changes expected from the value and the calldata, other calls are not projected
+    // plain sends and payable calls move the native value of the transaction
+    fn deltas(
+        &self,
+        from: Address,
+        target: Address,
+        input: &[u8],
+        value: U256,
+    ) -> Vec<PendingDelta> {
+        let native = self.network.native_token_address();
+        let mut deltas = self.call_deltas(from, target, input, value);
+        if !value.is_zero() {
+            deltas.push(delta(from, native, value, false));
+            deltas.push(delta(target, native, value, true));
+        }
+
+        deltas
+    }
+
+    fn call_deltas(
+        &self,
+        from: Address,
+        target: Address,
+        input: &[u8],
+        value: U256,
+    ) -> Vec<PendingDelta> {
+        if input.is_empty() {
+            return vec![];
+        }
+
+        if let Some(underlying) = self.wrapped_tokens.get(&target).copied() {
+            if let Ok(call) = WrappedToken::withdrawCall::abi_decode(input) {
+                return vec![
+                    delta(from, target, call.wad, false),
+                    delta(from, underlying, call.wad, true),
+                ];
+            }
+
+            // payable deposit() wraps the native token only, the native outflow is the value
+            if underlying == self.network.native_token_address()
+                && WrappedToken::depositCall::abi_decode(input).is_ok()
+            {
+                return vec![delta(from, target, value, true)];
+            }
+        }
+
+        if let Ok(call) = ERC20::transferCall::abi_decode(input) {
+            return vec![
+                delta(from, target, call.amount, false),
+                delta(call.to, target, call.amount, true),
+            ];
+        }
+
+        if let Ok(call) = ERC20::transferFromCall::abi_decode(input) {
+            return vec![
+                delta(call.from, target, call.amount, false),
+                delta(call.to, target, call.amount, true),
+            ];
+        }
+
+        vec![]
+    }
+}
+
+fn delta(account: Address, token: Address, amount: U256, incoming: bool) -> PendingDelta {
+    PendingDelta {
+        account,
+        token,
+        amount,
+        incoming,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::address;
+    use std::time::Duration;
+
+    const WETH: Address = address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+    const WSTETH: Address = address!("0x7f39C581F595B53c5cb19bD0b3f8dA6c935E2Ca0");
+    const STETH: Address = address!("0xae7ab96520DE3A18E5e111B5EaAb095312D7fE84");
+    const TOKEN: Address = address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const ALICE: Address = address!("0x00000000000000000000000000000000000000a1");
+    const BOB: Address = address!("0x00000000000000000000000000000000000000b0");
+
+    fn dispatcher() -> MempoolDispatcher {
+        MempoolDispatcher::new(
+            EvmNetwork::Eth,
+            String::new(),
+            HashMap::from([
+                (WETH, EvmNetwork::Eth.native_token_address()),
+                (WSTETH, STETH),
+            ]),
+            ReconnectConfig {
+                initial_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(1),
+                degraded_after: 0,
+            },
+        )
+    }
+
+    fn native() -> Address {
+        EvmNetwork::Eth.native_token_address()
+    }
+
+    fn pairs(deltas: &[PendingDelta]) -> Vec<(Address, Address, U256, bool)> {
+        deltas
+            .iter()
+            .map(|delta| (delta.account, delta.token, delta.amount, delta.incoming))
+            .collect()
+    }
+
+    #[test]
+    fn native_send_moves_value() {
+        let deltas = dispatcher().deltas(ALICE, BOB, &[], U256::from(5));
+        assert_eq!(
+            pairs(&deltas),
+            vec![
+                (ALICE, native(), U256::from(5), false),
+                (BOB, native(), U256::from(5), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_value_without_calldata_has_no_deltas() {
+        assert!(dispatcher().deltas(ALICE, BOB, &[], U256::ZERO).is_empty());
+    }
+
+    #[test]
+    fn erc20_transfers_are_projected() {
+        let input = ERC20::transferCall {
+            to: BOB,
+            amount: U256::from(7),
+        }
+        .abi_encode();
+        assert_eq!(
+            pairs(&dispatcher().deltas(ALICE, TOKEN, &input, U256::ZERO)),
+            vec![
+                (ALICE, TOKEN, U256::from(7), false),
+                (BOB, TOKEN, U256::from(7), true),
+            ]
+        );
+
+        let input = ERC20::transferFromCall {
+            from: BOB,
+            to: ALICE,
+            amount: U256::from(3),
+        }
+        .abi_encode();
+        assert_eq!(
+            pairs(&dispatcher().deltas(TOKEN, TOKEN, &input, U256::ZERO)),
+            vec![
+                (BOB, TOKEN, U256::from(3), false),
+                (ALICE, TOKEN, U256::from(3), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn native_deposit_wraps_value() {
+        let input = WrappedToken::depositCall {}.abi_encode();
+        assert_eq!(
+            pairs(&dispatcher().deltas(ALICE, WETH, &input, U256::from(9))),
+            vec![
+                (ALICE, WETH, U256::from(9), true),
+                (ALICE, native(), U256::from(9), false),
+                (WETH, native(), U256::from(9), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn withdraw_unwraps_to_underlying() {
+        let input = WrappedToken::withdrawCall { wad: U256::from(4) }.abi_encode();
+        assert_eq!(
+            pairs(&dispatcher().deltas(ALICE, WSTETH, &input, U256::ZERO)),
+            vec![
+                (ALICE, WSTETH, U256::from(4), false),
+                (ALICE, STETH, U256::from(4), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn deposit_of_non_native_wrapper_is_not_projected() {
+        let input = WrappedToken::depositCall {}.abi_encode();
+        assert!(dispatcher()
+            .deltas(ALICE, WSTETH, &input, U256::ZERO)
+            .is_empty());
+    }
+
+    #[test]
+    fn payable_unknown_call_moves_value_only() {
+        let input = [0x12, 0x34, 0x56, 0x78, 0x00];
+        assert_eq!(
+            pairs(&dispatcher().deltas(ALICE, BOB, &input, U256::from(2))),
+            vec![
+                (ALICE, native(), U256::from(2), false),
+                (BOB, native(), U256::from(2), true),
+            ]
+        );
+        assert!(dispatcher()
+            .deltas(ALICE, BOB, &input, U256::ZERO)
+            .is_empty());
+    }
+}
@@ -1,6 +1,18 @@
+pub mod api_keys;
+pub mod balance_strategies;
 pub mod cleanup_stream;
+pub mod cluster;
 pub mod errors;
 pub mod fetch_balances_via_multicall;
+pub mod local_token_lists;
+pub mod log_dispatcher;
+pub mod mempool_dispatcher;
+pub mod price_oracle;
+pub mod rate_limiter;
+pub mod redis_backend;
+pub mod session_store;
 pub mod subscription_manager;
 pub mod token_list_fetcher;
+pub mod trace_dispatcher;
 pub mod watcher;
+pub mod webhook;
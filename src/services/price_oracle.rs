@@ -0,0 +1,523 @@
+use crate::config::pricing_config::PricingConfig;
+use crate::domain::{EvmNetwork, PriceFeed, UniswapV3Source, Valuation};
+use crate::evm::chainlink::ChainlinkAggregator;
+use crate::evm::multicall3::Multicall3;
+use crate::evm::uniswap_v3::UniswapV3Pool;
+use crate::services::errors::{PricingError, ServiceError};
+use alloy::eips::BlockId;
+use alloy::primitives::{Address, Bytes, U256};
+use alloy::providers::DynProvider;
+use alloy::sol_types::SolCall;
+use metrics::{counter, histogram};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Deserialize)]
+struct PriceFeedsFile {
+    feeds: Vec<PriceFeed>,
+}
+
+/// USD prices of tokens at a block
+pub type Prices = HashMap<Address, f64>;
+
+struct CachedPrices {
+    block_number: U256,
+    prices: Arc<Prices>,
+}
+
+// position of a subcall in the price multicall
+enum PriceCall {
+    ChainlinkRound(Address),
+    ChainlinkDecimals(Address),
+    Observe(Address),
+}
+
+#[derive(Default)]
+struct ChainlinkAnswer {
+    answer: Option<U256>,
+    updated_at: u64,
+    decimals: Option<u8>,
+}
+
+// USD prices from Chainlink aggregators with Uniswap V3 TWAP fallback,
+// requested via multicall3 and cached per network for the last block
+pub struct PriceOracle {
+    feeds: HashMap<EvmNetwork, HashMap<Address, PriceFeed>>,
+    config: PricingConfig,
+    // locked while prices are requested, so sessions at the same block share one request
+    cache: HashMap<EvmNetwork, Mutex<Option<CachedPrices>>>,
+}
+
+impl PriceOracle {
+    // feeds are taken from inline json (PRICE_FEEDS) and from the file (PRICE_FEEDS_PATH)
+    // both have the same format: {"feeds": [{"chainId": 1, "token": "0x...", ...}]}
+    // pricing is disabled (None) if there are no feeds
+    pub fn load(
+        inline: &str,
+        path: Option<&Path>,
+        config: PricingConfig,
+    ) -> Result<Option<Self>, PricingError> {
+        let mut all_feeds: Vec<PriceFeed> = Vec::new();
+
+        if !inline.trim().is_empty() {
+            let file: PriceFeedsFile = serde_json::from_str(inline)
+                .map_err(|err| PricingError::Parse("PRICE_FEEDS".to_string(), err.to_string()))?;
+            all_feeds.extend(file.feeds);
+        }
+
+        if let Some(path) = path {
+            let source = path.display().to_string();
+            let content = std::fs::read(path)
+                .map_err(|err| PricingError::Parse(source.clone(), err.to_string()))?;
+            let file: PriceFeedsFile = serde_json::from_slice(&content)
+                .map_err(|err| PricingError::Parse(source, err.to_string()))?;
+            all_feeds.extend(file.feeds);
+        }
+
+        if all_feeds.is_empty() {
+            tracing::info!("no price feeds configured, USD valuation is disabled");
+            return Ok(None);
+        }
+
+        let mut feeds: HashMap<EvmNetwork, HashMap<Address, PriceFeed>> = HashMap::new();
+        for feed in all_feeds {
+            let network = EvmNetwork::try_from(feed.chain_id)
+                .map_err(|_| PricingError::UnknownChain(feed.chain_id, feed.token.to_string()))?;
+
+            if feed.chainlink.is_none() && feed.uniswap_v3.is_none() {
+                return Err(PricingError::NoSource(feed.token.to_string()));
+            }
+
+            let network_feeds = feeds.entry(network).or_default();
+            if network_feeds.contains_key(&feed.token) {
+                return Err(PricingError::Duplicate(feed.token.to_string()));
+            }
+            network_feeds.insert(feed.token, feed);
+        }
+
+        for network_feeds in feeds.values() {
+            for feed in network_feeds.values() {
+                if let Some(twap) = &feed.uniswap_v3 {
+                    if !network_feeds.contains_key(&twap.quote_token) {
+                        return Err(PricingError::UnknownQuote(
+                            feed.token.to_string(),
+                            twap.quote_token.to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (network, network_feeds) in &feeds {
+            tracing::info!(network = %network, feeds = network_feeds.len(), "price feeds loaded");
+        }
+
+        let cache = feeds
+            .keys()
+            .map(|network| (*network, Mutex::new(None)))
+            .collect();
+
+        Ok(Some(Self {
+            feeds,
+            config,
+            cache,
+        }))
+    }
+
+    pub fn update_threshold_bps(&self) -> u32 {
+        self.config.update_threshold_bps
+    }
+
+    pub fn has_feeds(&self, network: EvmNetwork) -> bool {
+        self.feeds.contains_key(&network)
+    }
+
+    // prices at the block, prices of a later block are reused as well
+    pub async fn prices(
+        &self,
+        network: EvmNetwork,
+        provider: &DynProvider,
+        multicall3: Address,
+        block_number: U256,
+    ) -> Result<Arc<Prices>, ServiceError> {
+        let (Some(feeds), Some(cache)) = (self.feeds.get(&network), self.cache.get(&network))
+        else {
+            return Ok(Arc::default());
+        };
+
+        let mut cached = cache.lock().await;
+        if let Some(cached) = cached.as_ref() {
+            if cached.block_number >= block_number {
+                counter!("price_cache_hits_total").increment(1);
+                return Ok(Arc::clone(&cached.prices));
+            }
+        }
+
+        let prices = Arc::new(
+            self.fetch_prices(feeds, provider, multicall3, block_number)
+                .await?,
+        );
+        *cached = Some(CachedPrices {
+            block_number,
+            prices: Arc::clone(&prices),
+        });
+
+        Ok(prices)
+    }
+
+    // USD value of every balance with a known price
+    pub fn valuation(
+        &self,
+        network: EvmNetwork,
+        balances: &HashMap<Address, U256>,
+        prices: &Prices,
+        block_number: U256,
+    ) -> Valuation {
+        let mut values = HashMap::new();
+        let mut held_prices = HashMap::new();
+
+        if let Some(feeds) = self.feeds.get(&network) {
+            for (token, amount) in balances {
+                let (Some(feed), Some(price)) = (feeds.get(token), prices.get(token)) else {
+                    continue;
+                };
+
+                values.insert(*token, to_units(*amount, feed.decimals) * price);
+                held_prices.insert(*token, *price);
+            }
+        }
+
+        Valuation {
+            total_usd: values.values().sum(),
+            values,
+            prices: held_prices,
+            block_number: block_number.to_string(),
+        }
+    }
+
+    async fn fetch_prices(
+        &self,
+        feeds: &HashMap<Address, PriceFeed>,
+        provider: &DynProvider,
+        multicall3: Address,
+        block_number: U256,
+    ) -> Result<Prices, ServiceError> {
+        let twap_window = u32::try_from(self.config.twap_window.as_secs()).unwrap_or(u32::MAX);
+
+        let mut kinds: Vec<PriceCall> = Vec::new();
+        let mut calls: Vec<Multicall3::Call> = Vec::new();
+        let mut push = |kind: PriceCall, target: Address, call_data: Vec<u8>| {
+            kinds.push(kind);
+            calls.push(Multicall3::Call {
+                target,
+                callData: call_data.into(),
+            });
+        };
+
+        for feed in feeds.values() {
+            if let Some(aggregator) = feed.chainlink {
+                push(
+                    PriceCall::ChainlinkRound(feed.token),
+                    aggregator,
+                    ChainlinkAggregator::latestRoundDataCall {}.abi_encode(),
+                );
+                push(
+                    PriceCall::ChainlinkDecimals(feed.token),
+                    aggregator,
+                    ChainlinkAggregator::decimalsCall {}.abi_encode(),
+                );
+            }
+
+            if let Some(twap) = &feed.uniswap_v3 {
+                push(
+                    PriceCall::Observe(feed.token),
+                    twap.pool,
+                    UniswapV3Pool::observeCall {
+                        secondsAgos: vec![twap_window, 0],
+                    }
+                    .abi_encode(),
+                );
+            }
+        }
+
+        // the last call, chainlink answers are checked for staleness against the block timestamp
+        calls.push(Multicall3::Call {
+            target: multicall3,
+            callData: Multicall3::getCurrentBlockTimestampCall {}
+                .abi_encode()
+                .into(),
+        });
+
+        let t0 = Instant::now();
+        counter!("price_fetch_total").increment(1);
+
+        let block_id = BlockId::number(block_number.saturating_to::<u64>());
+        let call_result = Multicall3::new(multicall3, provider.clone())
+            .tryBlockAndAggregate(false, calls)
+            .block(block_id)
+            .call()
+            .await
+            .map_err(|e| {
+                counter!("price_fetch_failed_total").increment(1);
+                ServiceError::PricesMultiCallError(e.to_string())
+            })?;
+
+        histogram!("price_fetch_duration_ms").record(t0.elapsed().as_millis() as f64);
+
+        let return_data: Vec<Option<&Bytes>> = call_result
+            .returnData
+            .iter()
+            .map(|result| result.success.then_some(&result.returnData))
+            .collect();
+
+        let block_timestamp = return_data
+            .get(kinds.len())
+            .copied()
+            .flatten()
+            .and_then(|data| {
+                Multicall3::getCurrentBlockTimestampCall::abi_decode_returns(data).ok()
+            })
+            .map(|timestamp| timestamp.saturating_to::<u64>())
+            .ok_or_else(|| {
+                counter!("price_fetch_failed_total").increment(1);
+                ServiceError::PricesMultiCallError("missing block timestamp".to_string())
+            })?;
+
+        let mut answers: HashMap<Address, ChainlinkAnswer> = HashMap::new();
+        let mut ticks: HashMap<Address, f64> = HashMap::new();
+
+        for (kind, data) in kinds.iter().zip(return_data) {
+            let Some(data) = data else {
+                continue;
+            };
+
+            match kind {
+                PriceCall::ChainlinkRound(token) => {
+                    if let Ok(round) =
+                        ChainlinkAggregator::latestRoundDataCall::abi_decode_returns(data)
+                    {
+                        let answer = answers.entry(*token).or_default();
+                        answer.answer = round.answer.is_positive().then(|| round.answer.into_raw());
+                        answer.updated_at = round.updatedAt.saturating_to::<u64>();
+                    }
+                }
+                PriceCall::ChainlinkDecimals(token) => {
+                    if let Ok(decimals) =
+                        ChainlinkAggregator::decimalsCall::abi_decode_returns(data)
+                    {
+                        answers.entry(*token).or_default().decimals = Some(decimals);
+                    }
+                }
+                PriceCall::Observe(token) => {
+                    if let Some(tick) = UniswapV3Pool::observeCall::abi_decode_returns(data)
+                        .ok()
+                        .and_then(|observed| average_tick(&observed.tickCumulatives, twap_window))
+                    {
+                        ticks.insert(*token, tick);
+                    }
+                }
+            }
+        }
+
+        // chainlink first, TWAPs are quoted in tokens priced by chainlink
+        let mut prices: Prices = HashMap::with_capacity(feeds.len());
+        for (token, answer) in &answers {
+            let age = block_timestamp.saturating_sub(answer.updated_at);
+            if age > self.config.max_price_age.as_secs() {
+                counter!("price_stale_total").increment(1);
+                tracing::debug!(token = %token, age_secs = age, "chainlink answer is stale");
+                continue;
+            }
+
+            if let (Some(value), Some(decimals)) = (answer.answer, answer.decimals) {
+                prices.insert(*token, to_units(value, decimals));
+            }
+        }
+
+        for feed in feeds.values() {
+            if prices.contains_key(&feed.token) {
+                continue;
+            }
+
+            let (Some(twap), Some(tick)) = (&feed.uniswap_v3, ticks.get(&feed.token)) else {
+                continue;
+            };
+
+            let (Some(quote_feed), Some(quote_price)) = (
+                feeds.get(&twap.quote_token),
+                prices.get(&twap.quote_token).copied(),
+            ) else {
+                continue;
+            };
+
+            counter!("price_twap_fallback_total").increment(1);
+            prices.insert(
+                feed.token,
+                twap_price(feed, twap, quote_feed.decimals, *tick) * quote_price,
+            );
+        }
+
+        tracing::debug!(
+            time_ms = t0.elapsed().as_millis(),
+            block_number = %block_number,
+            prices = prices.len(),
+            feeds = feeds.len(),
+            "prices are fetched"
+        );
+
+        Ok(prices)
+    }
+}
+
+// average tick over the window from cumulatives at [window, 0] seconds ago
+fn average_tick<T>(tick_cumulatives: &[T], window: u32) -> Option<f64>
+where
+    T: Copy,
+    i64: TryFrom<T>,
+{
+    let [from, to] = tick_cumulatives else {
+        return None;
+    };
+    let from = i64::try_from(*from).ok()?;
+    let to = i64::try_from(*to).ok()?;
+
+    Some((to - from) as f64 / f64::from(window))
+}
+
+// price of the base token in quote token units, token0 of the pool is the lower address
+// 1.0001^tick is the price of token0 in raw units of token1
+fn twap_price(feed: &PriceFeed, twap: &UniswapV3Source, quote_decimals: u8, tick: f64) -> f64 {
+    let base_token = twap.base_token.unwrap_or(feed.token);
+    let raw_price = if base_token < twap.quote_token {
+        1.0001f64.powf(tick)
+    } else {
+        1.0001f64.powf(-tick)
+    };
+
+    raw_price * 10f64.powi(i32::from(feed.decimals) - i32::from(quote_decimals))
+}
+
+fn to_units(amount: U256, decimals: u8) -> f64 {
+    f64::from(amount) / 10f64.powi(i32::from(decimals))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::address;
+    use std::time::Duration;
+
+    const USDC: Address = address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const WETH: Address = address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+    const DAI: Address = address!("0x6B175474E89094C44Da98b954EedeAC495271d0F");
+    const USDC_FEED: Address = address!("0x8fFfFfd4AfB6115b954Bd326cbe7B4BA576818f6");
+    const USDC_WETH_POOL: Address = address!("0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640");
+
+    fn config() -> PricingConfig {
+        PricingConfig {
+            twap_window: Duration::from_secs(1_800),
+            max_price_age: Duration::from_secs(3_600),
+            update_threshold_bps: 50,
+        }
+    }
+
+    fn feeds() -> serde_json::Value {
+        serde_json::json!({"feeds": [
+            {"chainId": 1, "token": USDC, "decimals": 6, "chainlink": USDC_FEED},
+            {"chainId": 1, "token": WETH, "decimals": 18,
+             "uniswapV3": {"pool": USDC_WETH_POOL, "quoteToken": USDC}},
+        ]})
+    }
+
+    fn load(feeds: serde_json::Value) -> Result<Option<PriceOracle>, PricingError> {
+        PriceOracle::load(&feeds.to_string(), None, config())
+    }
+
+    #[test]
+    fn loads_feeds() {
+        assert!(load(serde_json::json!({"feeds": []})).unwrap().is_none());
+
+        let oracle = load(feeds()).unwrap().unwrap();
+        assert!(oracle.has_feeds(EvmNetwork::Eth));
+        assert!(!oracle.has_feeds(EvmNetwork::Arbitrum));
+    }
+
+    #[test]
+    fn rejects_invalid_feeds() {
+        let mut duplicate = feeds();
+        let usdc = duplicate["feeds"][0].clone();
+        duplicate["feeds"].as_array_mut().unwrap().push(usdc);
+        assert!(matches!(load(duplicate), Err(PricingError::Duplicate(_))));
+
+        let no_source =
+            serde_json::json!({"feeds": [{"chainId": 1, "token": DAI, "decimals": 18}]});
+        assert!(matches!(load(no_source), Err(PricingError::NoSource(_))));
+
+        let mut unknown_quote = feeds();
+        unknown_quote["feeds"].as_array_mut().unwrap().remove(0);
+        assert!(matches!(
+            load(unknown_quote),
+            Err(PricingError::UnknownQuote(_, _))
+        ));
+
+        let mut unknown_chain = feeds();
+        unknown_chain["feeds"][0]["chainId"] = serde_json::json!(999_999);
+        assert!(matches!(
+            load(unknown_chain),
+            Err(PricingError::UnknownChain(999_999, _))
+        ));
+    }
+
+    #[test]
+    fn values_balances_with_prices() {
+        let oracle = load(feeds()).unwrap().unwrap();
+        let balances = HashMap::from([
+            (USDC, U256::from(2_500_000u64)),
+            (WETH, U256::from(10u64).pow(U256::from(18))),
+            (DAI, U256::from(1u64)),
+        ]);
+        let prices = HashMap::from([(USDC, 1.0), (WETH, 2_000.0)]);
+
+        let valuation = oracle.valuation(EvmNetwork::Eth, &balances, &prices, U256::from(100));
+
+        assert_eq!(
+            valuation.values,
+            HashMap::from([(USDC, 2.5), (WETH, 2_000.0)])
+        );
+        assert_eq!(valuation.prices, prices);
+        assert_eq!(valuation.total_usd, 2_002.5);
+        assert_eq!(valuation.block_number, "100");
+    }
+
+    #[test]
+    fn averages_tick_over_window() {
+        assert_eq!(average_tick(&[1_000i64, 4_600], 1_800), Some(2.0));
+        assert_eq!(average_tick(&[0i64, -3_600], 1_800), Some(-2.0));
+        assert_eq!(average_tick(&[0i64], 1_800), None);
+    }
+
+    #[test]
+    fn twap_price_respects_token_order_and_decimals() {
+        let feed = PriceFeed {
+            chain_id: 1,
+            token: WETH,
+            decimals: 18,
+            chainlink: None,
+            uniswap_v3: Some(UniswapV3Source {
+                pool: USDC_WETH_POOL,
+                quote_token: USDC,
+                base_token: None,
+            }),
+        };
+        let twap = feed.uniswap_v3.clone().unwrap();
+        // USDC is token0: the pool price is 1 / 2000 WETH per USDC in raw units
+        let tick = (1e12 / 2_000.0f64).ln() / 1.0001f64.ln();
+
+        let price = twap_price(&feed, &twap, 6, tick);
+        assert!((price - 2_000.0).abs() < 1e-6, "{price}");
+    }
+}
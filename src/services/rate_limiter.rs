@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use metrics::gauge;
+use tokio::sync::Mutex;
+
+// buckets which were not used for this time are dropped (they are full anyway)
+const BUCKET_IDLE_TTL: Duration = Duration::from_secs(600);
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Routes with separate rate limit budgets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteClass {
+    /// REST balance reads (one RPC call each)
+    BalanceRead,
+    /// Session creation and updates (token lists loading)
+    SessionMutation,
+    /// SSE connections
+    SseConnect,
+}
+
+impl RouteClass {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RouteClass::BalanceRead => "balance_read",
+            RouteClass::SessionMutation => "session_mutation",
+            RouteClass::SseConnect => "sse_connect",
+        }
+    }
+}
+
+/// Token bucket settings: `capacity` requests at once, refilled at `per_minute` rate
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub capacity: f64,
+    pub per_minute: f64,
+}
+
+impl RateLimit {
+    // the whole minute budget is available as a burst
+    pub fn per_minute(per_minute: u32) -> Self {
+        Self {
+            capacity: per_minute as f64,
+            per_minute: per_minute as f64,
+        }
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.per_minute / 60.0
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+// token bucket rate limiter keyed by route class and client (IP or API key)
+// route classes without a limit are not limited
+pub struct RateLimiter {
+    limits: HashMap<RouteClass, RateLimit>,
+    buckets: Mutex<HashMap<(RouteClass, String), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: HashMap<RouteClass, RateLimit>) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // take one token from the client bucket, return time to wait if the bucket is empty
+    pub async fn check(&self, class: RouteClass, client: &str) -> Result<(), Duration> {
+        self.check_at(class, client, Instant::now()).await
+    }
+
+    async fn check_at(
+        &self,
+        class: RouteClass,
+        client: &str,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let Some(limit) = self.limits.get(&class) else {
+            return Ok(());
+        };
+
+        let mut buckets = self.buckets.lock().await;
+        let bucket = buckets
+            .entry((class, client.to_string()))
+            .or_insert(Bucket {
+                tokens: limit.capacity,
+                updated_at: now,
+            });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.refill_per_sec()).min(limit.capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        let retry_after = (1.0 - bucket.tokens) / limit.refill_per_sec();
+        Err(Duration::from_secs_f64(retry_after))
+    }
+
+    pub fn spawn_cleanup(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.evict_idle(Instant::now()).await;
+            }
+        });
+    }
+
+    async fn evict_idle(&self, now: Instant) {
+        let mut buckets = self.buckets.lock().await;
+        buckets.retain(|_, bucket| now.duration_since(bucket.updated_at) < BUCKET_IDLE_TTL);
+        gauge!("rate_limit_buckets").set(buckets.len() as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_minute: u32) -> RateLimiter {
+        RateLimiter::new(HashMap::from([(
+            RouteClass::BalanceRead,
+            RateLimit::per_minute(per_minute),
+        )]))
+    }
+
+    #[tokio::test]
+    async fn burst_is_limited_by_capacity() {
+        let limiter = limiter(3);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter
+                .check_at(RouteClass::BalanceRead, "ip:1", now)
+                .await
+                .is_ok());
+        }
+        let retry_after = limiter
+            .check_at(RouteClass::BalanceRead, "ip:1", now)
+            .await
+            .unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(20));
+
+        // other clients and route classes have their own budgets
+        assert!(limiter
+            .check_at(RouteClass::BalanceRead, "ip:2", now)
+            .await
+            .is_ok());
+        assert!(limiter
+            .check_at(RouteClass::SseConnect, "ip:1", now)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn bucket_is_refilled_over_time() {
+        let limiter = limiter(60);
+        let start = Instant::now();
+
+        for _ in 0..60 {
+            assert!(limiter
+                .check_at(RouteClass::BalanceRead, "ip:1", start)
+                .await
+                .is_ok());
+        }
+        assert!(limiter
+            .check_at(RouteClass::BalanceRead, "ip:1", start)
+            .await
+            .is_err());
+
+        // one token per second
+        let later = start + Duration::from_secs(2);
+        assert!(limiter
+            .check_at(RouteClass::BalanceRead, "ip:1", later)
+            .await
+            .is_ok());
+        assert!(limiter
+            .check_at(RouteClass::BalanceRead, "ip:1", later)
+            .await
+            .is_ok());
+        assert!(limiter
+            .check_at(RouteClass::BalanceRead, "ip:1", later)
+            .await
+            .is_err());
+
+        // refill is capped by the capacity
+        let idle = later + Duration::from_secs(3_600);
+        for _ in 0..60 {
+            assert!(limiter
+                .check_at(RouteClass::BalanceRead, "ip:1", idle)
+                .await
+                .is_ok());
+        }
+        assert!(limiter
+            .check_at(RouteClass::BalanceRead, "ip:1", idle)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn idle_buckets_are_evicted() {
+        let limiter = limiter(60);
+        let start = Instant::now();
+        limiter
+            .check_at(RouteClass::BalanceRead, "ip:1", start)
+            .await
+            .unwrap();
+        let later = start + BUCKET_IDLE_TTL / 2;
+        limiter
+            .check_at(RouteClass::BalanceRead, "ip:2", later)
+            .await
+            .unwrap();
+
+        limiter.evict_idle(start + BUCKET_IDLE_TTL).await;
+
+        let buckets = limiter.buckets.lock().await;
+        assert!(!buckets.contains_key(&(RouteClass::BalanceRead, "ip:1".to_string())));
+        assert!(buckets.contains_key(&(RouteClass::BalanceRead, "ip:2".to_string())));
+    }
+}
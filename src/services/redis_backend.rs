@@ -0,0 +1,212 @@
+use crate::services::cluster::{ClusterBackend, ClusterMessages};
+use crate::services::errors::ClusterError;
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::{AsyncCommands, Script};
+use std::time::Duration;
+
+const SESSION_KEY_PREFIX: &str = "balances:session:";
+const LEADER_KEY_PREFIX: &str = "balances:leader:";
+const LEADER_STATE_KEY_PREFIX: &str = "balances:state:";
+const EVENTS_CHANNEL_PREFIX: &str = "balances:events:";
+
+// reconnects are bounded, so requests fail fast while redis is down
+const CONNECTION_RETRIES: usize = 3;
+const CONNECTION_MAX_DELAY_MS: u64 = 1_000;
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// take the lock or prolong it if it is already ours
+const ACQUIRE_SCRIPT: &str = r#"
+local current = redis.call('GET', KEYS[1])
+if current == false then
+    redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+    return 1
+end
+if current == ARGV[1] then
+    redis.call('PEXPIRE', KEYS[1], ARGV[2])
+    return 1
+end
+return 0
+"#;
+
+// drop the lock only if it is ours
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+// sessions are json values of `balances:session:<id>` keys with TTL,
+// messages go to `balances:events:<id>` channels, every replica listens to all of them,
+// leader locks are `balances:leader:<id>` keys holding the replica id,
+// snapshots saved by leaders are json values of `balances:state:<id>` keys with TTL
+pub struct RedisBackend {
+    client: redis::Client,
+    conn: ConnectionManager,
+}
+
+impl RedisBackend {
+    pub async fn connect(url: &str) -> Result<Self, ClusterError> {
+        let to_err = |err: redis::RedisError| ClusterError::Connect(err.to_string());
+
+        let client = redis::Client::open(url).map_err(to_err)?;
+        let config = ConnectionManagerConfig::new()
+            .set_number_of_retries(CONNECTION_RETRIES)
+            .set_max_delay(CONNECTION_MAX_DELAY_MS)
+            .set_connection_timeout(CONNECTION_TIMEOUT)
+            .set_response_timeout(RESPONSE_TIMEOUT);
+        let conn = ConnectionManager::new_with_config(client.clone(), config)
+            .await
+            .map_err(to_err)?;
+
+        Ok(Self { client, conn })
+    }
+}
+
+fn backend_err(err: redis::RedisError) -> ClusterError {
+    ClusterError::Backend(err.to_string())
+}
+
+impl ClusterBackend for RedisBackend {
+    fn save_session(
+        &self,
+        id: String,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> BoxFuture<'_, Result<(), ClusterError>> {
+        let mut conn = self.conn.clone();
+        Box::pin(async move {
+            conn.set_ex::<_, _, ()>(format!("{SESSION_KEY_PREFIX}{id}"), value, ttl.as_secs())
+                .await
+                .map_err(backend_err)
+        })
+    }
+
+    fn load_session(&self, id: String) -> BoxFuture<'_, Result<Option<Vec<u8>>, ClusterError>> {
+        let mut conn = self.conn.clone();
+        Box::pin(async move {
+            conn.get(format!("{SESSION_KEY_PREFIX}{id}"))
+                .await
+                .map_err(backend_err)
+        })
+    }
+
+    fn remove_session(&self, id: String) -> BoxFuture<'_, Result<(), ClusterError>> {
+        let mut conn = self.conn.clone();
+        Box::pin(async move {
+            conn.del::<_, ()>(&[
+                format!("{SESSION_KEY_PREFIX}{id}"),
+                format!("{LEADER_STATE_KEY_PREFIX}{id}"),
+            ])
+            .await
+            .map_err(backend_err)
+        })
+    }
+
+    fn save_leader_state(
+        &self,
+        id: String,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> BoxFuture<'_, Result<(), ClusterError>> {
+        let mut conn = self.conn.clone();
+        Box::pin(async move {
+            redis::pipe()
+                .set_ex(
+                    format!("{LEADER_STATE_KEY_PREFIX}{id}"),
+                    value,
+                    ttl.as_secs(),
+                )
+                .ignore()
+                .expire(format!("{SESSION_KEY_PREFIX}{id}"), ttl.as_secs() as i64)
+                .ignore()
+                .query_async::<()>(&mut conn)
+                .await
+                .map_err(backend_err)
+        })
+    }
+
+    fn load_leader_state(
+        &self,
+        id: String,
+    ) -> BoxFuture<'_, Result<Option<Vec<u8>>, ClusterError>> {
+        let mut conn = self.conn.clone();
+        Box::pin(async move {
+            conn.get(format!("{LEADER_STATE_KEY_PREFIX}{id}"))
+                .await
+                .map_err(backend_err)
+        })
+    }
+
+    fn publish(&self, id: String, payload: Vec<u8>) -> BoxFuture<'_, Result<(), ClusterError>> {
+        let mut conn = self.conn.clone();
+        Box::pin(async move {
+            conn.publish::<_, _, ()>(format!("{EVENTS_CHANNEL_PREFIX}{id}"), payload)
+                .await
+                .map_err(backend_err)
+        })
+    }
+
+    fn messages(&self) -> BoxFuture<'_, Result<ClusterMessages, ClusterError>> {
+        Box::pin(async move {
+            let mut pubsub = self.client.get_async_pubsub().await.map_err(backend_err)?;
+            pubsub
+                .psubscribe(format!("{EVENTS_CHANNEL_PREFIX}*"))
+                .await
+                .map_err(backend_err)?;
+
+            let messages = pubsub
+                .into_on_message()
+                .filter_map(|msg| async move {
+                    let id = msg
+                        .get_channel_name()
+                        .strip_prefix(EVENTS_CHANNEL_PREFIX)?
+                        .to_string();
+                    Some((id, msg.get_payload_bytes().to_vec()))
+                })
+                .boxed();
+
+            Ok(messages)
+        })
+    }
+
+    fn acquire_leadership(
+        &self,
+        id: String,
+        replica_id: String,
+        ttl: Duration,
+    ) -> BoxFuture<'_, Result<bool, ClusterError>> {
+        let mut conn = self.conn.clone();
+        Box::pin(async move {
+            let acquired: i32 = Script::new(ACQUIRE_SCRIPT)
+                .key(format!("{LEADER_KEY_PREFIX}{id}"))
+                .arg(replica_id)
+                .arg(ttl.as_millis() as u64)
+                .invoke_async(&mut conn)
+                .await
+                .map_err(backend_err)?;
+
+            Ok(acquired == 1)
+        })
+    }
+
+    fn release_leadership(
+        &self,
+        id: String,
+        replica_id: String,
+    ) -> BoxFuture<'_, Result<(), ClusterError>> {
+        let mut conn = self.conn.clone();
+        Box::pin(async move {
+            Script::new(RELEASE_SCRIPT)
+                .key(format!("{LEADER_KEY_PREFIX}{id}"))
+                .arg(replica_id)
+                .invoke_async::<i32>(&mut conn)
+                .await
+                .map(|_| ())
+                .map_err(backend_err)
+        })
+    }
+}
@@ -0,0 +1,244 @@
+use crate::domain::{AlertRule, Erc1155Token, Finality, NftHolding};
+use crate::services::errors::SessionStoreError;
+use crate::services::subscription_manager::{Balance, BalanceSnapshot, SubscriptionManager};
+use crate::services::webhook::WebhookTarget;
+use alloy::primitives::Address;
+use metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Session state saved to disk
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredSession {
+    pub chain_id: u64,
+    pub owner: Address,
+    pub tokens: Vec<Address>,
+    pub discovery_enabled: bool,
+    pub api_key: Option<String>,
+    pub balances: BalanceSnapshot,
+    /// Newest block of the snapshot, watchers of the restored session backfill from it
+    pub last_block: Option<u64>,
+    #[serde(default)]
+    pub webhook: Option<WebhookTarget>,
+    #[serde(default)]
+    pub alerts: Vec<AlertRule>,
+    #[serde(default)]
+    pub finality: Finality,
+    /// Pending transaction projections are enabled
+    #[serde(default)]
+    pub pending: bool,
+    #[serde(default)]
+    pub nft_collections: Vec<Address>,
+    /// Token IDs held in the watched collections, sent to clients before watchers enumerate them
+    #[serde(default)]
+    pub nft_holdings: HashMap<Address, NftHolding>,
+    #[serde(default)]
+    pub erc1155_tokens: Vec<Erc1155Token>,
+    /// Balances of the ERC-1155 pairs, a list since JSON keys can't be pairs
+    #[serde(default)]
+    pub erc1155_balances: Vec<(Erc1155Token, Balance)>,
+}
+
+impl StoredSession {
+    fn key(&self) -> String {
+        format!("{}:{}", self.chain_id, self.owner)
+    }
+}
+
+// embedded store (sled) keeping sessions between restarts
+// sessions are saved periodically and on shutdown, removed sessions are dropped on save
+pub struct SessionStore {
+    db: sled::Db,
+}
+
+impl SessionStore {
+    pub fn open(path: &Path) -> Result<Self, SessionStoreError> {
+        let db = sled::open(path)
+            .map_err(|err| SessionStoreError::Open(path.display().to_string(), err.to_string()))?;
+
+        tracing::info!(path = %path.display(), "session store is opened");
+        Ok(Self { db })
+    }
+
+    // entries which can't be parsed are skipped
+    pub fn load(&self) -> Vec<StoredSession> {
+        let mut sessions = Vec::new();
+
+        for entry in self.db.iter() {
+            let (key, value) = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    counter!("session_store_load_failed_total").increment(1);
+                    tracing::error!(error = %err, "unable to read session store");
+                    break;
+                }
+            };
+
+            match serde_json::from_slice::<StoredSession>(&value) {
+                Ok(session) => sessions.push(session),
+                Err(err) => {
+                    counter!("session_store_load_failed_total").increment(1);
+                    tracing::warn!(
+                        error = %err,
+                        key = %String::from_utf8_lossy(&key),
+                        "unable to parse stored session, skip"
+                    );
+                }
+            }
+        }
+
+        tracing::info!(sessions = sessions.len(), "sessions are loaded from store");
+        sessions
+    }
+
+    // replace stored sessions with the current ones
+    pub async fn save(&self, sessions: Vec<StoredSession>) -> Result<(), SessionStoreError> {
+        let to_err = |err: sled::Error| SessionStoreError::Save(err.to_string());
+
+        let mut batch = sled::Batch::default();
+        let mut keys: HashSet<String> = HashSet::with_capacity(sessions.len());
+
+        for session in &sessions {
+            let key = session.key();
+            let value = serde_json::to_vec(session)
+                .map_err(|err| SessionStoreError::Save(err.to_string()))?;
+            batch.insert(key.as_bytes(), value);
+            keys.insert(key);
+        }
+
+        for key in self.db.iter().keys() {
+            let key = key.map_err(to_err)?;
+            if !keys.contains(String::from_utf8_lossy(&key).as_ref()) {
+                batch.remove(key);
+            }
+        }
+
+        self.db.apply_batch(batch).map_err(to_err)?;
+        self.db.flush_async().await.map_err(to_err)?;
+
+        gauge!("session_store_sessions").set(sessions.len() as f64);
+        Ok(())
+    }
+
+    pub async fn save_sessions(&self, manager: &SubscriptionManager) {
+        let sessions = manager.export_sessions().await;
+        let sessions_len = sessions.len();
+
+        match self.save(sessions).await {
+            Ok(()) => {
+                counter!("session_store_saves_total").increment(1);
+                tracing::debug!(sessions = sessions_len, "sessions are saved");
+            }
+            Err(err) => {
+                counter!("session_store_save_failed_total").increment(1);
+                tracing::error!(error = %err, "unable to save sessions");
+            }
+        }
+    }
+
+    pub fn spawn_flusher(self: Arc<Self>, manager: Arc<SubscriptionManager>, every: Duration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(every);
+            // first tick completes immediately, sessions are just restored
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                self.save_sessions(&manager).await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{address, U256};
+    use std::path::PathBuf;
+
+    const OWNER: Address = address!("0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045");
+    const USDC: Address = address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "balances-watcher-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn session(chain_id: u64) -> StoredSession {
+        StoredSession {
+            chain_id,
+            owner: OWNER,
+            tokens: vec![USDC],
+            discovery_enabled: false,
+            api_key: None,
+            balances: HashMap::from([(
+                USDC,
+                Balance {
+                    amount: U256::from(5),
+                    block_number: U256::from(100),
+                },
+            )]),
+            last_block: Some(100),
+            webhook: None,
+            alerts: vec![],
+            finality: Finality::Latest,
+            pending: false,
+            nft_collections: vec![],
+            nft_holdings: HashMap::new(),
+            erc1155_tokens: vec![],
+            erc1155_balances: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn save_replaces_stored_sessions() {
+        let dir = TempDir::new("save");
+        let store = SessionStore::open(&dir.0).unwrap();
+
+        store.save(vec![session(1), session(42161)]).await.unwrap();
+        let mut loaded = store.load();
+        loaded.sort_by_key(|session| session.chain_id);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].tokens, vec![USDC]);
+        assert_eq!(loaded[0].last_block, Some(100));
+        assert_eq!(loaded[0].balances[&USDC].amount, U256::from(5));
+
+        // removed sessions are dropped
+        store.save(vec![session(42161)]).await.unwrap();
+        let loaded = store.load();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].chain_id, 42161);
+    }
+
+    #[tokio::test]
+    async fn load_skips_invalid_entries() {
+        let dir = TempDir::new("invalid");
+        let store = SessionStore::open(&dir.0).unwrap();
+
+        store.save(vec![session(1)]).await.unwrap();
+        store.db.insert("broken", b"{not json".to_vec()).unwrap();
+
+        let loaded = store.load();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].chain_id, 1);
+    }
+}
@@ -1,21 +1,38 @@
-use crate::config::constants::BROADCAST_CHANNEL_CAPACITY;
-use crate::domain::{BalanceEvent, SubscriptionKey};
+use crate::config::constants::{BROADCAST_CHANNEL_CAPACITY, CLUSTER_RESUBSCRIBE_DELAY_MS};
+use crate::config::session_limits::SessionLimits;
+use crate::domain::{
+    AlertRule, BalanceEvent, Erc1155Token, Erc1155Update, EvmNetwork, Finality,
+    NftCollectionUpdate, NftHolding, NftUpdate, ShareBalance, SubscriptionKey, Valuation,
+};
+use crate::services::cluster::{Cluster, ClusterMessage, LeaderState};
 use crate::services::errors::SubscriptionError;
+use crate::services::session_store::StoredSession;
+use crate::services::webhook::{Webhook, WebhookClient, WebhookStatus, WebhookTarget};
 use alloy::primitives::{Address, U256};
+use futures::StreamExt;
 use metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, mpsc, Mutex, Notify, RwLock};
 
 struct SubWithCounter {
     pub clients: u32,
     pub subscription: Arc<Subscription>,
     pub idle_since: Option<Instant>,
+    // name of the API key which created the session
+    pub created_by: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+/// API key creating a session, its sessions are limited by max_sessions
+pub struct SessionCreator {
+    pub key_name: String,
+    pub max_sessions: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Balance {
     pub amount: U256,
     pub block_number: U256,
@@ -23,36 +40,1129 @@ pub struct Balance {
 
 pub type BalanceSnapshot = HashMap<Address, Balance>;
 
-pub struct Subscription {
-    pub sender: broadcast::Sender<BalanceEvent>,
-    pub balances_snapshot: RwLock<BalanceSnapshot>,
-    pub cancel_token: tokio_util::sync::CancellationToken,
-    pub tokens: RwLock<HashSet<Address>>,
-    pub watchers_spawned: AtomicBool,
-}
+pub struct Subscription {
+    // delivers events to clients of this replica
+    pub sender: broadcast::Sender<BalanceEvent>,
+    // cluster mode: events are published to all replicas and come back via `sender`
+    outbox: Option<mpsc::UnboundedSender<BalanceEvent>>,
+    pub balances_snapshot: RwLock<BalanceSnapshot>,
+    pub cancel_token: tokio_util::sync::CancellationToken,
+    pub tokens: RwLock<HashSet<Address>>,
+    pub watchers_spawned: AtomicBool,
+    pub discovery_enabled: AtomicBool,
+    // discovery is turned on for a running session, watchers scan the transfer history
+    pub discovery_requested: Notify,
+    // tokens added after watchers are spawned, the snapshot updater fetches them right away
+    pub added_tokens: Mutex<HashSet<Address>>,
+    pub tokens_added: Notify,
+    // full snapshot update requested (admin resync)
+    pub resync: Notify,
+    pub snapshot_updated_at: RwLock<Option<Instant>>,
+    // block of the restored snapshot, watchers backfill the gap from it instead of the full update
+    pub resume_from_block: Mutex<Option<u64>>,
+    // events are also POSTed here, sessions with a webhook live without clients for the webhook TTL
+    webhook: std::sync::Mutex<Option<Webhook>>,
+    // evaluated on every snapshot update, locked while the snapshot is locked
+    pub alert_rules: std::sync::Mutex<Vec<AlertRule>>,
+    valuation: std::sync::Mutex<ValuationState>,
+    // confirmation level of the session, `confirmed_snapshot` follows it unless it is `latest`
+    finality: std::sync::Mutex<Finality>,
+    pub confirmed_snapshot: RwLock<BalanceSnapshot>,
+    pub finality_changed: Notify,
+    // projections of the owner's pending transactions, the pending tracker is idle while disabled
+    pub pending_enabled: AtomicBool,
+    pub pending_changed: Notify,
+    // shares and underlying amounts of share-based tokens, sent to new clients
+    share_balances: std::sync::Mutex<HashMap<Address, ShareBalance>>,
+    // watched ERC-721 collections, their holdings are sent to new clients
+    pub nft_collections: RwLock<HashSet<Address>>,
+    nft_holdings: std::sync::Mutex<HashMap<Address, NftHolding>>,
+    // collections added after watchers are spawned, the snapshot updater enumerates them right away
+    added_nft_collections: Mutex<HashSet<Address>>,
+    // watched ERC-1155 (contract, id) pairs and their balances, sent to new clients
+    pub erc1155_tokens: RwLock<HashSet<Erc1155Token>>,
+    erc1155_balances: std::sync::Mutex<HashMap<Erc1155Token, Balance>>,
+    // pairs added after watchers are spawned, the snapshot updater fetches them right away
+    added_erc1155_tokens: Mutex<HashSet<Erc1155Token>>,
+    // the owner has code (Safe, ERC-4337 account), set by watchers
+    pub contract_wallet: AtomicBool,
+}
+
+#[derive(Default)]
+struct ValuationState {
+    // sent with the snapshot to new clients
+    latest: Option<Valuation>,
+    // changes are measured from the last published valuation, so slow drifts are published too
+    published: Option<Valuation>,
+}
+
+/// Session state for introspection (admin API)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub chain_id: u64,
+    pub owner: Address,
+    pub clients: u32,
+    pub tokens: usize,
+    pub snapshot_tokens: usize,
+    pub snapshot_block_number: Option<String>,
+    pub snapshot_age_secs: Option<u64>,
+    pub idle_secs: Option<u64>,
+    pub watchers_spawned: bool,
+    pub discovery_enabled: bool,
+    pub api_key: Option<String>,
+    pub webhook: Option<WebhookStatus>,
+    pub finality: Finality,
+    pub confirmed_block_number: Option<String>,
+    pub pending_enabled: bool,
+    pub nft_collections: usize,
+    pub erc1155_tokens: usize,
+    pub contract_wallet: bool,
+}
+
+impl Subscription {
+    fn new(
+        tokens: HashSet<Address>,
+        discover_tokens: bool,
+        outbox: Option<mpsc::UnboundedSender<BalanceEvent>>,
+    ) -> Self {
+        let (sender, _) = broadcast::channel::<BalanceEvent>(BROADCAST_CHANNEL_CAPACITY);
+
+        Self {
+            sender,
+            outbox,
+            balances_snapshot: RwLock::new(HashMap::new()),
+            cancel_token: tokio_util::sync::CancellationToken::new(),
+            tokens: RwLock::new(tokens),
+            watchers_spawned: AtomicBool::new(false),
+            discovery_enabled: AtomicBool::new(discover_tokens),
+            discovery_requested: Notify::new(),
+            added_tokens: Mutex::new(HashSet::new()),
+            tokens_added: Notify::new(),
+            resync: Notify::new(),
+            snapshot_updated_at: RwLock::new(None),
+            resume_from_block: Mutex::new(None),
+            webhook: std::sync::Mutex::new(None),
+            alert_rules: std::sync::Mutex::new(Vec::new()),
+            valuation: std::sync::Mutex::new(ValuationState::default()),
+            finality: std::sync::Mutex::new(Finality::Latest),
+            confirmed_snapshot: RwLock::new(HashMap::new()),
+            finality_changed: Notify::new(),
+            pending_enabled: AtomicBool::new(false),
+            pending_changed: Notify::new(),
+            share_balances: std::sync::Mutex::new(HashMap::new()),
+            nft_collections: RwLock::new(HashSet::new()),
+            nft_holdings: std::sync::Mutex::new(HashMap::new()),
+            added_nft_collections: Mutex::new(HashSet::new()),
+            erc1155_tokens: RwLock::new(HashSet::new()),
+            erc1155_balances: std::sync::Mutex::new(HashMap::new()),
+            added_erc1155_tokens: Mutex::new(HashSet::new()),
+            contract_wallet: AtomicBool::new(false),
+        }
+    }
+
+    pub fn finality(&self) -> Finality {
+        self.finality
+            .lock()
+            .map(|finality| *finality)
+            .unwrap_or_default()
+    }
+
+    // balances of the previous level are dropped, the finality tracker fetches the new one
+    pub async fn set_finality(&self, finality: Finality) {
+        let changed = match self.finality.lock() {
+            Ok(mut current) => std::mem::replace(&mut *current, finality) != finality,
+            Err(_) => false,
+        };
+
+        if changed {
+            self.confirmed_snapshot.write().await.clear();
+            self.finality_changed.notify_one();
+        }
+    }
+
+    pub fn set_discovery_enabled(&self, enabled: bool) {
+        if !self.discovery_enabled.swap(enabled, Ordering::SeqCst) && enabled {
+            self.discovery_requested.notify_waiters();
+        }
+    }
+
+    pub fn set_pending_enabled(&self, enabled: bool) {
+        if self.pending_enabled.swap(enabled, Ordering::SeqCst) != enabled {
+            self.pending_changed.notify_one();
+        }
+    }
+
+    pub fn valuation(&self) -> Option<Valuation> {
+        self.valuation
+            .lock()
+            .ok()
+            .and_then(|state| state.latest.clone())
+    }
+
+    // keep the latest valuation, true if it should be published as price_update
+    pub fn update_valuation(&self, valuation: Valuation, threshold_bps: u32) -> bool {
+        let Ok(mut state) = self.valuation.lock() else {
+            return false;
+        };
+
+        let publish = match &state.published {
+            Some(published) => valuation.changed_materially(published, threshold_bps),
+            None => true,
+        };
+
+        if publish {
+            state.published = Some(valuation.clone());
+        }
+        state.latest = Some(valuation);
+
+        publish
+    }
+
+    pub fn share_balances(&self) -> HashMap<Address, ShareBalance> {
+        self.share_balances
+            .lock()
+            .map(|share_balances| share_balances.clone())
+            .unwrap_or_default()
+    }
+
+    // store share balances, return the ones which changed (the block alone is not a change)
+    pub fn update_share_balances(
+        &self,
+        share_balances: HashMap<Address, ShareBalance>,
+    ) -> HashMap<Address, ShareBalance> {
+        let Ok(mut current) = self.share_balances.lock() else {
+            return HashMap::new();
+        };
+
+        share_balances
+            .into_iter()
+            .filter(|(token, share_balance)| {
+                let changed = current.get(token).is_none_or(|previous| {
+                    previous.shares != share_balance.shares
+                        || previous.underlying != share_balance.underlying
+                });
+                current.insert(*token, share_balance.clone());
+                changed
+            })
+            .collect()
+    }
+
+    fn drop_share_balances(&self, tokens: &[Address]) {
+        if let Ok(mut share_balances) = self.share_balances.lock() {
+            for token in tokens {
+                share_balances.remove(token);
+            }
+        }
+    }
+
+    pub fn nft_holdings(&self) -> HashMap<Address, NftHolding> {
+        self.nft_holdings
+            .lock()
+            .map(|nft_holdings| nft_holdings.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn nft_holding(&self, collection: Address) -> Option<NftHolding> {
+        self.nft_holdings
+            .lock()
+            .ok()
+            .and_then(|nft_holdings| nft_holdings.get(&collection).cloned())
+    }
+
+    // store holdings, return the changes of collections (holdings of older blocks are skipped)
+    pub fn update_nft_holdings(
+        &self,
+        holdings: HashMap<Address, NftHolding>,
+    ) -> HashMap<Address, NftCollectionUpdate> {
+        let Ok(mut current) = self.nft_holdings.lock() else {
+            return HashMap::new();
+        };
+
+        let mut updates = HashMap::new();
+        for (collection, holding) in holdings {
+            let previous = current.get(&collection);
+            if previous.is_some_and(|previous| previous.block_number > holding.block_number) {
+                continue;
+            }
+
+            if let Some(update) = holding.update_from(previous) {
+                updates.insert(collection, update);
+            }
+            current.insert(collection, holding);
+        }
+
+        updates
+    }
+
+    // holdings of all collections as one update for new clients, None if nothing is held
+    pub fn nft_snapshot(&self) -> Option<NftUpdate> {
+        let holdings = self.nft_holdings();
+        let block_number = holdings
+            .values()
+            .map(|holding| holding.block_number)
+            .max()?;
+
+        Some(NftUpdate {
+            collections: holdings
+                .iter()
+                .filter_map(|(collection, holding)| Some((*collection, holding.update_from(None)?)))
+                .collect(),
+            block_number: block_number.to_string(),
+        })
+    }
+
+    // nft update published by another replica
+    fn apply_nft_update(&self, update: &NftUpdate) {
+        let Ok(block_number) = update.block_number.parse::<U256>() else {
+            return;
+        };
+        let Ok(mut nft_holdings) = self.nft_holdings.lock() else {
+            return;
+        };
+
+        for (collection, collection_update) in &update.collections {
+            let holding = nft_holdings.entry(*collection).or_default();
+            if holding.block_number <= block_number {
+                holding.apply(collection_update, block_number);
+            }
+        }
+    }
+
+    // add collections to the watched set, running watchers enumerate the new ones
+    pub async fn add_nft_collections(&self, collections: &[Address]) {
+        let added: Vec<Address> = {
+            let mut nft_collections = self.nft_collections.write().await;
+            collections
+                .iter()
+                .filter(|collection| nft_collections.insert(**collection))
+                .copied()
+                .collect()
+        };
+
+        self.notify_nft_collections_added(&added).await;
+    }
+
+    // apply the collections changed on another replica
+    async fn sync_nft_collections(&self, collections: HashSet<Address>) {
+        let (added, removed): (Vec<Address>, Vec<Address>) = {
+            let mut nft_collections = self.nft_collections.write().await;
+            let added = collections.difference(&nft_collections).copied().collect();
+            let removed = nft_collections.difference(&collections).copied().collect();
+            *nft_collections = collections;
+            (added, removed)
+        };
+
+        if let Ok(mut nft_holdings) = self.nft_holdings.lock() {
+            for collection in &removed {
+                nft_holdings.remove(collection);
+            }
+        }
+        self.notify_nft_collections_added(&added).await;
+    }
+
+    async fn notify_nft_collections_added(&self, collections: &[Address]) {
+        if collections.is_empty() {
+            return;
+        }
+
+        self.added_nft_collections
+            .lock()
+            .await
+            .extend(collections.iter().copied());
+        self.tokens_added.notify_one();
+    }
+
+    pub async fn take_added_nft_collections(&self) -> Vec<Address> {
+        let mut added_nft_collections = self.added_nft_collections.lock().await;
+        added_nft_collections.drain().collect()
+    }
+
+    pub fn erc1155_balances(&self) -> HashMap<Erc1155Token, Balance> {
+        self.erc1155_balances
+            .lock()
+            .map(|erc1155_balances| erc1155_balances.clone())
+            .unwrap_or_default()
+    }
+
+    // store balances of the block, return the changed ones (balances of older blocks are skipped)
+    pub fn update_erc1155_balances(
+        &self,
+        balances: HashMap<Erc1155Token, U256>,
+        block_number: U256,
+    ) -> HashMap<Erc1155Token, U256> {
+        let Ok(mut current) = self.erc1155_balances.lock() else {
+            return HashMap::new();
+        };
+
+        let mut diff = HashMap::new();
+        for (token, amount) in balances {
+            let previous = current.get(&token);
+            if previous.is_some_and(|previous| previous.block_number >= block_number) {
+                continue;
+            }
+
+            if previous.is_none_or(|previous| previous.amount != amount) {
+                diff.insert(token, amount);
+            }
+            current.insert(
+                token,
+                Balance {
+                    amount,
+                    block_number,
+                },
+            );
+        }
+
+        diff
+    }
+
+    // balances of all pairs as one update for new clients, None before the first fetch
+    pub fn erc1155_snapshot(&self) -> Option<Erc1155Update> {
+        let balances = self.erc1155_balances();
+        let block_number = balances
+            .values()
+            .map(|balance| balance.block_number)
+            .max()?;
+        let amounts = balances
+            .into_iter()
+            .map(|(token, balance)| (token, balance.amount))
+            .collect();
+
+        Some(Erc1155Update::new(&amounts, block_number))
+    }
+
+    // erc1155 update published by another replica
+    fn apply_erc1155_update(&self, update: &Erc1155Update) {
+        if let Ok(block_number) = update.block_number.parse::<U256>() {
+            self.update_erc1155_balances(update.token_balances(), block_number);
+        }
+    }
+
+    // add pairs to the watched set, running watchers fetch the new ones
+    pub async fn add_erc1155_tokens(&self, tokens: &[Erc1155Token]) {
+        let added: Vec<Erc1155Token> = {
+            let mut erc1155_tokens = self.erc1155_tokens.write().await;
+            tokens
+                .iter()
+                .filter(|token| erc1155_tokens.insert(**token))
+                .copied()
+                .collect()
+        };
+
+        self.notify_erc1155_tokens_added(&added).await;
+    }
+
+    // apply the pairs changed on another replica
+    async fn sync_erc1155_tokens(&self, tokens: HashSet<Erc1155Token>) {
+        let (added, removed): (Vec<Erc1155Token>, Vec<Erc1155Token>) = {
+            let mut erc1155_tokens = self.erc1155_tokens.write().await;
+            let added = tokens.difference(&erc1155_tokens).copied().collect();
+            let removed = erc1155_tokens.difference(&tokens).copied().collect();
+            *erc1155_tokens = tokens;
+            (added, removed)
+        };
+
+        if let Ok(mut erc1155_balances) = self.erc1155_balances.lock() {
+            for token in &removed {
+                erc1155_balances.remove(token);
+            }
+        }
+        self.notify_erc1155_tokens_added(&added).await;
+    }
+
+    async fn notify_erc1155_tokens_added(&self, tokens: &[Erc1155Token]) {
+        if tokens.is_empty() {
+            return;
+        }
+
+        self.added_erc1155_tokens
+            .lock()
+            .await
+            .extend(tokens.iter().copied());
+        self.tokens_added.notify_one();
+    }
+
+    pub async fn take_added_erc1155_tokens(&self) -> Vec<Erc1155Token> {
+        let mut added_erc1155_tokens = self.added_erc1155_tokens.lock().await;
+        added_erc1155_tokens.drain().collect()
+    }
+
+    // valuation published by the leader on another replica
+    fn set_valuation(&self, valuation: Valuation) {
+        if let Ok(mut state) = self.valuation.lock() {
+            state.published = Some(valuation.clone());
+            state.latest = Some(valuation);
+        }
+    }
+
+    pub fn alert_rules(&self) -> Vec<AlertRule> {
+        self.alert_rules
+            .lock()
+            .map(|rules| rules.clone())
+            .unwrap_or_default()
+    }
+
+    // ids of alert rules on tokens which wouldn't be watched with the given set,
+    // rules of the native token don't depend on the set
+    pub fn alert_rules_outside(&self, tokens: &HashSet<Address>, native: Address) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .alert_rules()
+            .into_iter()
+            .filter(|rule| rule.token != native && !tokens.contains(&rule.token))
+            .map(|rule| rule.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    // replace alert rules, unchanged rules keep their state
+    pub fn set_alert_rules(&self, mut rules: Vec<AlertRule>) {
+        let Ok(mut current) = self.alert_rules.lock() else {
+            return;
+        };
+
+        for rule in rules.iter_mut() {
+            if let Some(existing) = current
+                .iter()
+                .find(|existing| existing.same_definition(rule))
+            {
+                rule.triggered = existing.triggered;
+            }
+        }
+
+        *current = rules;
+    }
+
+    // send event to clients and the webhook of the session, false if nobody receives it
+    // in cluster mode only the replica producing the event delivers it to the webhook
+    pub fn publish(&self, event: BalanceEvent) -> bool {
+        let queued = match self.webhook.lock().as_deref() {
+            Ok(Some(webhook)) => webhook.enqueue(event.clone()),
+            _ => false,
+        };
+
+        let sent = match &self.outbox {
+            Some(outbox) => outbox.send(event).is_ok(),
+            None => self.sender.send(event).is_ok(),
+        };
+
+        sent || queued
+    }
+
+    pub fn has_webhook(&self) -> bool {
+        matches!(self.webhook.lock().as_deref(), Ok(Some(_)))
+    }
+
+    pub fn webhook_target(&self) -> Option<WebhookTarget> {
+        match self.webhook.lock().as_deref() {
+            Ok(Some(webhook)) => Some(webhook.target().clone()),
+            _ => None,
+        }
+    }
+
+    pub fn webhook_status(&self) -> Option<WebhookStatus> {
+        match self.webhook.lock().as_deref() {
+            Ok(Some(webhook)) => Some(webhook.status()),
+            _ => None,
+        }
+    }
+
+    // true - if watchers of the session should be spawned by the caller
+    pub fn claim_watchers(&self) -> bool {
+        self.watchers_spawned
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    pub async fn take_resume_block(&self) -> Option<u64> {
+        self.resume_from_block.lock().await.take()
+    }
+
+    // let running watchers know about new tokens
+    pub async fn notify_tokens_added(&self, tokens: &[Address]) {
+        if tokens.is_empty() {
+            return;
+        }
+
+        self.added_tokens
+            .lock()
+            .await
+            .extend(tokens.iter().copied());
+        self.tokens_added.notify_one();
+    }
+
+    pub async fn take_added_tokens(&self) -> Vec<Address> {
+        let mut added_tokens = self.added_tokens.lock().await;
+        added_tokens.drain().collect()
+    }
+
+    // remove tokens from the watched set and their balances from the snapshot
+    // clients are notified with TokensRemoved event, return removed tokens
+    pub async fn remove_tokens(&self, tokens: &HashSet<Address>) -> Vec<Address> {
+        let removed: Vec<Address> = {
+            let mut watched_tokens = self.tokens.write().await;
+            tokens
+                .iter()
+                .filter(|token| watched_tokens.remove(*token))
+                .copied()
+                .collect()
+        };
+
+        self.drop_removed(&removed).await;
+        removed
+    }
+
+    // replace the whole watched set, return removed tokens
+    pub async fn replace_tokens(&self, tokens: HashSet<Address>) -> Vec<Address> {
+        let (added, removed): (Vec<Address>, Vec<Address>) = {
+            let mut watched_tokens = self.tokens.write().await;
+            let added = tokens.difference(&watched_tokens).copied().collect();
+            let removed = watched_tokens.difference(&tokens).copied().collect();
+            *watched_tokens = tokens;
+            (added, removed)
+        };
+
+        self.drop_removed(&removed).await;
+        self.notify_tokens_added(&added).await;
+        removed
+    }
+
+    async fn drop_removed(&self, removed: &[Address]) {
+        if removed.is_empty() {
+            return;
+        }
+
+        {
+            let mut balance_snapshot = self.balances_snapshot.write().await;
+            let mut confirmed_snapshot = self.confirmed_snapshot.write().await;
+            for token in removed {
+                balance_snapshot.remove(token);
+                confirmed_snapshot.remove(token);
+            }
+        }
+
+        self.drop_share_balances(removed);
+
+        counter!("tokens_removed_total").increment(removed.len() as u64);
+        self.publish(BalanceEvent::TokensRemoved(removed.to_vec()));
+    }
+
+    // apply the watched set changed on another replica,
+    // clients are notified by the replica which changed it
+    async fn sync_tokens(&self, tokens: HashSet<Address>) {
+        let (added, removed): (Vec<Address>, Vec<Address>) = {
+            let mut watched_tokens = self.tokens.write().await;
+            let added = tokens.difference(&watched_tokens).copied().collect();
+            let removed = watched_tokens.difference(&tokens).copied().collect();
+            *watched_tokens = tokens;
+            (added, removed)
+        };
+
+        {
+            let mut balance_snapshot = self.balances_snapshot.write().await;
+            let mut confirmed_snapshot = self.confirmed_snapshot.write().await;
+            for token in &removed {
+                balance_snapshot.remove(token);
+                confirmed_snapshot.remove(token);
+            }
+        }
+
+        self.drop_share_balances(&removed);
+        self.notify_tokens_added(&added).await;
+    }
+}
+
+pub struct SubscriptionManager {
+    subscriptions: RwLock<HashMap<SubscriptionKey, SubWithCounter>>,
+    limits: SessionLimits,
+    cluster: Option<Arc<Cluster>>,
+    webhooks: Arc<WebhookClient>,
+}
+
+const SESSION_TTL: Duration = Duration::from_secs(60);
+
+impl SubscriptionManager {
+    pub fn new(
+        limits: SessionLimits,
+        cluster: Option<Arc<Cluster>>,
+        webhooks: Arc<WebhookClient>,
+    ) -> Self {
+        Self {
+            subscriptions: RwLock::new(HashMap::new()),
+            limits,
+            cluster,
+            webhooks,
+        }
+    }
+
+    // replace the webhook of the session, the delivery of the previous one stops
+    pub fn set_webhook(
+        &self,
+        key: SubscriptionKey,
+        subscription: &Subscription,
+        target: WebhookTarget,
+    ) {
+        let Ok(mut webhook) = subscription.webhook.lock() else {
+            return;
+        };
+
+        if webhook.as_ref().map(|webhook| webhook.target()) == Some(&target) {
+            return;
+        }
+
+        tracing::info!(sub = %key, url = %target.url, "webhook is registered");
+        counter!("webhooks_registered_total").increment(1);
+        *webhook = Some(
+            self.webhooks
+                .spawn(key, target, subscription.cancel_token.clone()),
+        );
+    }
+
+    // stop the delivery, the session expires as usual when it has no clients
+    pub fn remove_webhook(&self, key: SubscriptionKey, subscription: &Subscription) -> bool {
+        let Ok(mut webhook) = subscription.webhook.lock() else {
+            return false;
+        };
+
+        let removed = webhook.take().is_some();
+        if removed {
+            tracing::info!(sub = %key, "webhook is removed");
+            counter!("webhooks_removed_total").increment(1);
+        }
+
+        removed
+    }
+
+    // sessions to run watchers for without clients
+    pub async fn webhook_sessions(&self) -> Vec<(SubscriptionKey, Arc<Subscription>)> {
+        let subs = self.subscriptions.read().await;
+        subs.iter()
+            .filter(|(_, sub)| sub.subscription.has_webhook())
+            .map(|(key, sub)| (*key, Arc::clone(&sub.subscription)))
+            .collect()
+    }
+
+    pub fn cluster(&self) -> Option<&Arc<Cluster>> {
+        self.cluster.as_ref()
+    }
+
+    fn new_subscription(
+        &self,
+        key: SubscriptionKey,
+        tokens: HashSet<Address>,
+        discover_tokens: bool,
+    ) -> Subscription {
+        let outbox = self
+            .cluster
+            .as_ref()
+            .map(|cluster| cluster.spawn_publisher(key));
+
+        Subscription::new(tokens, discover_tokens, outbox)
+    }
+
+    fn stored_subscription(&self, key: SubscriptionKey, session: StoredSession) -> SubWithCounter {
+        let mut subscription = self.new_subscription(
+            key,
+            session.tokens.into_iter().collect(),
+            session.discovery_enabled,
+        );
+        *subscription.balances_snapshot.get_mut() = session.balances;
+        *subscription.resume_from_block.get_mut() = session.last_block;
+        if let Some(target) = session.webhook {
+            self.set_webhook(key, &subscription, target);
+        }
+        subscription.set_alert_rules(session.alerts);
+        if let Ok(finality) = subscription.finality.get_mut() {
+            *finality = session.finality;
+        }
+        *subscription.pending_enabled.get_mut() = session.pending;
+        *subscription.nft_collections.get_mut() = session.nft_collections.into_iter().collect();
+        if let Ok(nft_holdings) = subscription.nft_holdings.get_mut() {
+            *nft_holdings = session.nft_holdings;
+        }
+        *subscription.erc1155_tokens.get_mut() = session.erc1155_tokens.into_iter().collect();
+        if let Ok(erc1155_balances) = subscription.erc1155_balances.get_mut() {
+            *erc1155_balances = session.erc1155_balances.into_iter().collect();
+        }
+
+        SubWithCounter {
+            clients: 0,
+            subscription: Arc::new(subscription),
+            idle_since: Some(Instant::now()),
+            created_by: session.api_key,
+        }
+    }
+
+    // cluster mode: session created on another replica is loaded from the shared state
+    async fn ensure_local(&self, key: SubscriptionKey) {
+        let Some(cluster) = &self.cluster else {
+            return;
+        };
+
+        if self.subscriptions.read().await.contains_key(&key) {
+            return;
+        }
+
+        let Some(session) = cluster.load_session(key).await else {
+            return;
+        };
+
+        let mut subs = self.subscriptions.write().await;
+        if subs.contains_key(&key) {
+            return;
+        }
+
+        subs.insert(key, self.stored_subscription(key, session));
+        gauge!("active_sessions").increment(1);
+        counter!("cluster_sessions_materialized_total").increment(1);
+        tracing::info!(sub = %key, "shared session is materialized");
+    }
+
+    // cluster mode: save the session changed by this replica and let other replicas reload it
+    pub async fn share_session(&self, key: SubscriptionKey) {
+        let Some(cluster) = &self.cluster else {
+            return;
+        };
+
+        let Some(session) = self.export_session(key).await else {
+            return;
+        };
+
+        cluster.save_session(&session).await;
+        cluster.publish(key, ClusterMessage::SessionChanged).await;
+    }
+
+    // cluster mode: take the shared state of the session
+    // tokens are replaced, the snapshot is taken if the shared one is newer
+    pub async fn sync_from_cluster(&self, key: SubscriptionKey) {
+        let Some(cluster) = &self.cluster else {
+            return;
+        };
+        let Some(subscription) = self.local_subscription(key).await else {
+            return;
+        };
+        let Some(session) = cluster.load_session(key).await else {
+            return;
+        };
+
+        subscription
+            .sync_tokens(session.tokens.into_iter().collect())
+            .await;
+        subscription.set_discovery_enabled(session.discovery_enabled);
+        match session.webhook {
+            Some(target) => self.set_webhook(key, &subscription, target),
+            None => {
+                self.remove_webhook(key, &subscription);
+            }
+        }
+        // the leader keeps the state of alerts in the shared session
+        if let Ok(mut alert_rules) = subscription.alert_rules.lock() {
+            *alert_rules = session.alerts;
+        }
+        subscription.set_finality(session.finality).await;
+        subscription.set_pending_enabled(session.pending);
+        subscription
+            .sync_nft_collections(session.nft_collections.into_iter().collect())
+            .await;
+        subscription
+            .sync_erc1155_tokens(session.erc1155_tokens.into_iter().collect())
+            .await;
+
+        // watchers lock tokens before the snapshot, so tokens are copied before the snapshot is locked
+        let tokens = subscription.tokens.read().await.clone();
+        let mut balance_snapshot = subscription.balances_snapshot.write().await;
+        let local_block = balance_snapshot
+            .values()
+            .map(|balance| balance.block_number)
+            .max();
+        let shared_block = session
+            .balances
+            .values()
+            .map(|balance| balance.block_number)
+            .max();
+        if shared_block > local_block {
+            *balance_snapshot = session
+                .balances
+                .into_iter()
+                .filter(|(token, _)| {
+                    *token == key.network.native_token_address() || tokens.contains(token)
+                })
+                .collect();
+            *subscription.resume_from_block.lock().await = session.last_block;
+        }
+    }
+
+    // cluster mode: deliver messages of other replicas to local sessions
+    pub fn spawn_cluster_listener(self: Arc<Self>) {
+        let Some(cluster) = self.cluster.clone() else {
+            return;
+        };
+
+        tokio::spawn(async move {
+            loop {
+                match cluster.messages().await {
+                    Ok(messages) => {
+                        tracing::info!("listening to cluster messages");
+                        let mut messages = std::pin::pin!(messages);
+                        while let Some((key, envelope)) = messages.next().await {
+                            counter!("cluster_messages_received_total").increment(1);
+                            let is_own = envelope.origin == cluster.replica_id();
+                            self.handle_cluster_message(key, envelope.message, is_own)
+                                .await;
+                        }
+
+                        tracing::warn!("cluster messages stream ended, resubscribe");
+                    }
+                    Err(err) => {
+                        counter!("cluster_errors_total", "op" => "messages").increment(1);
+                        tracing::error!(error = %err, "unable to listen to cluster messages");
+                    }
+                }
+
+                tokio::time::sleep(Duration::from_millis(CLUSTER_RESUBSCRIBE_DELAY_MS)).await;
+            }
+        });
+    }
+
+    async fn handle_cluster_message(
+        &self,
+        key: SubscriptionKey,
+        message: ClusterMessage,
+        is_own: bool,
+    ) {
+        match message {
+            // own events come back this way too, so every replica delivers them the same way
+            ClusterMessage::Event { event } => {
+                if let Some(subscription) = self.local_subscription(key).await {
+                    match &event {
+                        BalanceEvent::PriceUpdate(valuation) => {
+                            subscription.set_valuation(valuation.clone());
+                        }
+                        BalanceEvent::SharesUpdate(share_balances) => {
+                            subscription.update_share_balances(share_balances.clone());
+                        }
+                        BalanceEvent::NftUpdate(update) => {
+                            subscription.apply_nft_update(update);
+                        }
+                        BalanceEvent::Erc1155Update(update) => {
+                            subscription.apply_erc1155_update(update);
+                        }
+                        _ => {}
+                    }
+                    let _ = subscription.sender.send(event);
+                }
+            }
+            ClusterMessage::SessionChanged if !is_own => self.sync_from_cluster(key).await,
+            ClusterMessage::Terminated if !is_own => {
+                let _ = self.terminate_local(key).await;
+            }
+            _ => {}
+        }
+    }
+
+    async fn local_subscription(&self, key: SubscriptionKey) -> Option<Arc<Subscription>> {
+        let subs = self.subscriptions.read().await;
+        subs.get(&key).map(|sub| Arc::clone(&sub.subscription))
+    }
+
+    // check global limits before a new session is created
+    async fn ensure_session_headroom(
+        &self,
+        subs: &HashMap<SubscriptionKey, SubWithCounter>,
+        key: &SubscriptionKey,
+        tokens_len: usize,
+    ) -> Result<(), SubscriptionError> {
+        let reject = |reason: &'static str, err: SubscriptionError| {
+            counter!("sessions_rejected_total", "reason" => reason).increment(1);
+            tracing::warn!(sub = %key, error = %err, "session is rejected");
+            Err(err)
+        };
+
+        if SessionLimits::is_exceeded(self.limits.max_sessions, subs.len() + 1) {
+            return reject("sessions", SubscriptionError::SessionsLimitExceeded);
+        }
+
+        let owner_sessions = subs.keys().filter(|k| k.owner == key.owner).count();
+        if SessionLimits::is_exceeded(self.limits.max_sessions_per_owner, owner_sessions + 1) {
+            return reject(
+                "owner_sessions",
+                SubscriptionError::OwnerSessionsLimitExceeded,
+            );
+        }
+
+        let total_tokens = Self::total_tokens(subs).await + tokens_len;
+        if SessionLimits::is_exceeded(self.limits.max_total_tokens, total_tokens) {
+            return reject("tokens", SubscriptionError::TotalTokensLimitExceeded);
+        }
+
+        Ok(())
+    }
+
+    // check sessions limit of the API key before it creates a new session
+    fn ensure_key_headroom(
+        subs: &HashMap<SubscriptionKey, SubWithCounter>,
+        creator: &SessionCreator,
+    ) -> Result<(), SubscriptionError> {
+        let Some(max_sessions) = creator.max_sessions else {
+            return Ok(());
+        };
+
+        let key_sessions = subs
+            .values()
+            .filter(|sub| sub.created_by.as_ref() == Some(&creator.key_name))
+            .count();
+
+        if key_sessions >= max_sessions {
+            counter!("api_key_rejected_total", "key" => creator.key_name.clone(), "reason" => "sessions")
+                .increment(1);
+            return Err(SubscriptionError::KeySessionsLimitExceeded);
+        }
+
+        Ok(())
+    }
+
+    // check global tokens limit before tokens are added to an existing session
+    pub async fn ensure_tokens_headroom(&self, additional: usize) -> Result<(), SubscriptionError> {
+        let subs = self.subscriptions.read().await;
+        let total_tokens = Self::total_tokens(&subs).await + additional;
+
+        if SessionLimits::is_exceeded(self.limits.max_total_tokens, total_tokens) {
+            counter!("tokens_rejected_total").increment(1);
+            return Err(SubscriptionError::TotalTokensLimitExceeded);
+        }
+
+        Ok(())
+    }
+
+    // tokens found by discovery are added while both the session limit and the global one allow
+    // return added tokens
+    pub async fn add_discovered_tokens(
+        &self,
+        key: SubscriptionKey,
+        subscription: &Subscription,
+        candidates: impl IntoIterator<Item = Address>,
+        max_session_tokens: usize,
+        wrapped: &HashSet<Address>,
+    ) -> Vec<Address> {
+        let subs = self.subscriptions.read().await;
+        let total_tokens = Self::total_tokens(&subs).await;
+
+        let mut tokens = subscription.tokens.write().await;
+        // wrapped tokens don't count against the session limit
+        let mut session_tokens = tokens.difference(wrapped).count();
+        let mut added: Vec<Address> = Vec::new();
+
+        for token in candidates {
+            if tokens.contains(&token) {
+                continue;
+            }
+
+            if session_tokens >= max_session_tokens {
+                counter!("tokens_limit_exceeded_total").increment(1);
+                tracing::warn!(
+                    sub = %key,
+                    tokens_len = session_tokens,
+                    "limit of watched tokens is reached, discovered tokens are skipped"
+                );
+                break;
+            }
+
+            if SessionLimits::is_exceeded(
+                self.limits.max_total_tokens,
+                total_tokens + added.len() + 1,
+            ) {
+                counter!("tokens_rejected_total").increment(1);
+                tracing::warn!(
+                    sub = %key,
+                    total_tokens = total_tokens + added.len(),
+                    "global limit of watched tokens is reached, discovered tokens are skipped"
+                );
+                break;
+            }
+
+            tokens.insert(token);
+            session_tokens += 1;
+            added.push(token);
+        }
+        drop(tokens);
 
-pub struct SubscriptionManager {
-    subscriptions: RwLock<HashMap<SubscriptionKey, SubWithCounter>>,
-}
+        if !added.is_empty() {
+            self.update_headroom_metrics(&subs).await;
+        }
 
-const SESSION_TTL: Duration = Duration::from_secs(60);
+        added
+    }
 
-impl SubscriptionManager {
-    pub fn new() -> Self {
-        Self {
-            subscriptions: RwLock::new(HashMap::new()),
+    async fn total_tokens(subs: &HashMap<SubscriptionKey, SubWithCounter>) -> usize {
+        let mut total = 0;
+        for sub in subs.values() {
+            total += sub.subscription.tokens.read().await.len();
         }
+
+        total
+    }
+
+    async fn update_headroom_metrics(&self, subs: &HashMap<SubscriptionKey, SubWithCounter>) {
+        gauge!("sessions_headroom").set(SessionLimits::headroom(
+            self.limits.max_sessions,
+            subs.len(),
+        ));
+
+        let total_tokens = Self::total_tokens(subs).await;
+        gauge!("watched_tokens_total").set(total_tokens as f64);
+        gauge!("watched_tokens_headroom").set(SessionLimits::headroom(
+            self.limits.max_total_tokens,
+            total_tokens,
+        ));
     }
 
     pub async fn create_or_update(
         &self,
         key: SubscriptionKey,
         tokens: HashSet<Address>,
-    ) -> Arc<Subscription> {
+        discover_tokens: bool,
+        creator: Option<SessionCreator>,
+    ) -> Result<Arc<Subscription>, SubscriptionError> {
+        self.ensure_local(key).await;
         let mut subs = self.subscriptions.write().await;
+
+        if let Some(existing) = subs.get(&key) {
+            Self::ensure_owner(
+                existing,
+                creator.as_ref().map(|creator| creator.key_name.as_str()),
+            )?;
+
+            let new_unique = {
+                let watched_tokens = existing.subscription.tokens.read().await;
+                tokens
+                    .iter()
+                    .filter(|t| !watched_tokens.contains(*t))
+                    .count()
+            };
+
+            let total_tokens = Self::total_tokens(&subs).await + new_unique;
+            if SessionLimits::is_exceeded(self.limits.max_total_tokens, total_tokens) {
+                counter!("tokens_rejected_total").increment(1);
+                return Err(SubscriptionError::TotalTokensLimitExceeded);
+            }
+        }
+
         if let Some(existing) = subs.get_mut(&key) {
+            // a session without clients is kept alive by its updates
+            if existing.clients == 0 {
+                existing.idle_since = Some(Instant::now());
+            }
+
             let mut watchet_tokens = existing.subscription.tokens.write().await;
-            watchet_tokens.extend(tokens);
+            let added: Vec<Address> = tokens
+                .into_iter()
+                .filter(|token| watchet_tokens.insert(*token))
+                .collect();
+
+            if discover_tokens {
+                existing.subscription.set_discovery_enabled(true);
+            }
 
             counter!("sessions_updated_total").increment(1);
             tracing::info!(
@@ -60,28 +1170,35 @@ impl SubscriptionManager {
                 tokens_len = watchet_tokens.len(),
                 "session is updated"
             );
+            drop(watchet_tokens);
 
-            return Arc::clone(&existing.subscription);
+            existing.subscription.notify_tokens_added(&added).await;
+            return Ok(Arc::clone(&existing.subscription));
         }
 
-        let (sender, _) = broadcast::channel::<BalanceEvent>(BROADCAST_CHANNEL_CAPACITY);
+        if let Some(creator) = &creator {
+            Self::ensure_key_headroom(&subs, creator)?;
+        }
+
+        self.ensure_session_headroom(&subs, &key, tokens.len())
+            .await?;
 
         let tokens_len = tokens.len();
-        let subscription = Arc::new(Subscription {
-            sender,
-            balances_snapshot: RwLock::new(HashMap::new()),
-            cancel_token: tokio_util::sync::CancellationToken::new(),
-            tokens: RwLock::new(tokens),
-            watchers_spawned: AtomicBool::new(false),
-        });
+        let subscription = Arc::new(self.new_subscription(key, tokens, discover_tokens));
 
         let sub_with_counter = SubWithCounter {
             clients: 0,
             subscription: Arc::clone(&subscription),
             idle_since: Some(Instant::now()),
+            created_by: creator.map(|creator| creator.key_name),
         };
 
+        if let Some(key_name) = &sub_with_counter.created_by {
+            counter!("api_key_sessions_created_total", "key" => key_name.clone()).increment(1);
+        }
+
         subs.insert(key, sub_with_counter);
+        self.update_headroom_metrics(&subs).await;
 
         counter!("sessions_created_total").increment(1);
         gauge!("active_sessions").increment(1);
@@ -91,21 +1208,306 @@ impl SubscriptionManager {
             "session is created"
         );
 
-        Arc::clone(&subscription)
+        Ok(Arc::clone(&subscription))
+    }
+
+    // state of all sessions for the session store
+    pub async fn export_sessions(&self) -> Vec<StoredSession> {
+        let subs = self.subscriptions.read().await;
+
+        let mut sessions = Vec::with_capacity(subs.len());
+        for (key, sub) in subs.iter() {
+            sessions.push(Self::stored_session(*key, sub).await);
+        }
+
+        sessions
+    }
+
+    pub async fn export_session(&self, key: SubscriptionKey) -> Option<StoredSession> {
+        let subs = self.subscriptions.read().await;
+        let sub = subs.get(&key)?;
+
+        Some(Self::stored_session(key, sub).await)
+    }
+
+    // cluster mode: state produced by watchers of the leader
+    pub async fn export_leader_state(&self, key: SubscriptionKey) -> Option<LeaderState> {
+        let subscription = self.local_subscription(key).await?;
+
+        let balances = subscription.balances_snapshot.read().await.clone();
+        let last_block = balances
+            .values()
+            .map(|balance| balance.block_number)
+            .max()
+            .and_then(|block| u64::try_from(block).ok());
+        let triggered_alerts = subscription
+            .alert_rules()
+            .into_iter()
+            .filter(|rule| rule.triggered)
+            .map(|rule| rule.id)
+            .collect();
+
+        Some(LeaderState {
+            balances,
+            last_block,
+            nft_holdings: subscription.nft_holdings(),
+            erc1155_balances: subscription.erc1155_balances().into_iter().collect(),
+            triggered_alerts,
+        })
+    }
+
+    async fn stored_session(key: SubscriptionKey, sub: &SubWithCounter) -> StoredSession {
+        let subscription = &sub.subscription;
+        let tokens = subscription.tokens.read().await.iter().copied().collect();
+        let balances = subscription.balances_snapshot.read().await.clone();
+        let last_block = balances
+            .values()
+            .map(|balance| balance.block_number)
+            .max()
+            .and_then(|block| u64::try_from(block).ok());
+
+        StoredSession {
+            chain_id: key.network.chain_id(),
+            owner: key.owner,
+            tokens,
+            discovery_enabled: subscription.discovery_enabled.load(Ordering::SeqCst),
+            api_key: sub.created_by.clone(),
+            balances,
+            last_block,
+            webhook: subscription.webhook_target(),
+            alerts: subscription.alert_rules(),
+            finality: subscription.finality(),
+            pending: subscription.pending_enabled.load(Ordering::SeqCst),
+            nft_collections: subscription
+                .nft_collections
+                .read()
+                .await
+                .iter()
+                .copied()
+                .collect(),
+            nft_holdings: subscription.nft_holdings(),
+            erc1155_tokens: subscription
+                .erc1155_tokens
+                .read()
+                .await
+                .iter()
+                .copied()
+                .collect(),
+            erc1155_balances: subscription.erc1155_balances().into_iter().collect(),
+        }
+    }
+
+    // restore sessions saved before restart, they expire as usual if clients don't come back
+    // watchers are spawned by the first client and backfill from the saved block
+    // limits may be lowered before restart, sessions over them are skipped
+    pub async fn restore_sessions(
+        &self,
+        sessions: Vec<StoredSession>,
+        key_max_sessions: impl Fn(&str) -> Option<usize>,
+    ) {
+        let mut subs = self.subscriptions.write().await;
+        let mut restored = 0;
+
+        for session in sessions {
+            let network = match EvmNetwork::try_from(session.chain_id) {
+                Ok(network) => network,
+                Err(err) => {
+                    tracing::warn!(error = %err, owner = %session.owner, "unable to restore session");
+                    continue;
+                }
+            };
+
+            let key = SubscriptionKey {
+                owner: session.owner,
+                network,
+            };
+
+            let creator = session.api_key.as_ref().map(|key_name| SessionCreator {
+                key_name: key_name.clone(),
+                max_sessions: key_max_sessions(key_name),
+            });
+            let key_headroom = creator
+                .as_ref()
+                .map_or(Ok(()), |creator| Self::ensure_key_headroom(&subs, creator));
+            let headroom = match key_headroom {
+                Ok(()) => {
+                    self.ensure_session_headroom(&subs, &key, session.tokens.len())
+                        .await
+                }
+                err => err,
+            };
+            if let Err(err) = headroom {
+                tracing::warn!(error = %err, sub = %key, "unable to restore session");
+                continue;
+            }
+
+            subs.insert(key, self.stored_subscription(key, session));
+            restored += 1;
+        }
+
+        counter!("sessions_restored_total").increment(restored as u64);
+        gauge!("active_sessions").set(subs.len() as f64);
+        self.update_headroom_metrics(&subs).await;
+        tracing::info!(sessions = restored, "sessions are restored");
+    }
+
+    // close streams of all clients on shutdown, sessions are kept for the store
+    pub async fn close_all(&self) {
+        let subs = self.subscriptions.read().await;
+        for sub in subs.values() {
+            sub.subscription.cancel_token.cancel();
+        }
+    }
+
+    pub async fn list_sessions(&self) -> Vec<SessionInfo> {
+        let subs = self.subscriptions.read().await;
+
+        let mut sessions = Vec::with_capacity(subs.len());
+        for (key, sub) in subs.iter() {
+            sessions.push(Self::session_info_of(key, sub).await);
+        }
+
+        sessions
+    }
+
+    pub async fn session_info(&self, key: SubscriptionKey) -> Option<SessionInfo> {
+        let subs = self.subscriptions.read().await;
+        match subs.get(&key) {
+            Some(sub) => Some(Self::session_info_of(&key, sub).await),
+            None => None,
+        }
+    }
+
+    async fn session_info_of(key: &SubscriptionKey, sub: &SubWithCounter) -> SessionInfo {
+        let subscription = &sub.subscription;
+        let tokens = subscription.tokens.read().await.len();
+
+        let (snapshot_tokens, snapshot_block_number) = {
+            let balance_snapshot = subscription.balances_snapshot.read().await;
+            let block_number = balance_snapshot
+                .values()
+                .map(|balance| balance.block_number)
+                .max();
+            (balance_snapshot.len(), block_number)
+        };
+
+        let confirmed_block_number = subscription
+            .confirmed_snapshot
+            .read()
+            .await
+            .values()
+            .map(|balance| balance.block_number)
+            .max();
+
+        let snapshot_age_secs = subscription
+            .snapshot_updated_at
+            .read()
+            .await
+            .map(|updated_at| updated_at.elapsed().as_secs());
+
+        SessionInfo {
+            chain_id: key.network.chain_id(),
+            owner: key.owner,
+            clients: sub.clients,
+            tokens,
+            snapshot_tokens,
+            snapshot_block_number: snapshot_block_number.map(|block| block.to_string()),
+            snapshot_age_secs,
+            idle_secs: sub
+                .idle_since
+                .map(|idle_since| idle_since.elapsed().as_secs()),
+            watchers_spawned: subscription.watchers_spawned.load(Ordering::SeqCst),
+            discovery_enabled: subscription.discovery_enabled.load(Ordering::SeqCst),
+            api_key: sub.created_by.clone(),
+            webhook: subscription.webhook_status(),
+            finality: subscription.finality(),
+            confirmed_block_number: confirmed_block_number.map(|block| block.to_string()),
+            pending_enabled: subscription.pending_enabled.load(Ordering::SeqCst),
+            nft_collections: subscription.nft_collections.read().await.len(),
+            erc1155_tokens: subscription.erc1155_tokens.read().await.len(),
+            contract_wallet: subscription.contract_wallet.load(Ordering::SeqCst),
+        }
+    }
+
+    // remove session, cancel its watchers and close client streams
+    // in cluster mode the session is removed on every replica
+    pub async fn terminate(&self, key: SubscriptionKey) -> Result<(), SubscriptionError> {
+        self.ensure_local(key).await;
+        self.terminate_local(key).await?;
+
+        if let Some(cluster) = &self.cluster {
+            cluster.remove_session(key).await;
+            cluster.publish(key, ClusterMessage::Terminated).await;
+        }
+
+        Ok(())
+    }
+
+    async fn terminate_local(&self, key: SubscriptionKey) -> Result<(), SubscriptionError> {
+        let mut subs = self.subscriptions.write().await;
+        let sub = subs.remove(&key).ok_or(SubscriptionError::NoSession)?;
+
+        sub.subscription.cancel_token.cancel();
+        self.update_headroom_metrics(&subs).await;
+
+        counter!("sessions_terminated_total").increment(1);
+        gauge!("active_sessions").decrement(1);
+        gauge!("sse_connections_active").decrement(sub.clients as f64);
+        tracing::info!(
+            sub = %key,
+            clients = sub.clients,
+            "session is terminated"
+        );
+
+        Ok(())
     }
 
     pub async fn get_subscription(&self, key: SubscriptionKey) -> Option<Arc<Subscription>> {
+        self.ensure_local(key).await;
         let subs = self.subscriptions.read().await;
         subs.get(&key).map(|sub| Arc::clone(&sub.subscription))
     }
 
+    // session-scoped API: None - there is no session, an error - it was created by another key
+    pub async fn get_owned_subscription(
+        &self,
+        key: SubscriptionKey,
+        caller: Option<&str>,
+    ) -> Result<Option<Arc<Subscription>>, SubscriptionError> {
+        self.ensure_local(key).await;
+        let subs = self.subscriptions.read().await;
+
+        let Some(sub) = subs.get(&key) else {
+            return Ok(None);
+        };
+        Self::ensure_owner(sub, caller)?;
+
+        Ok(Some(Arc::clone(&sub.subscription)))
+    }
+
+    // sessions created with an API key are accessible to that key only
+    // caller is None if authentication is disabled
+    fn ensure_owner(sub: &SubWithCounter, caller: Option<&str>) -> Result<(), SubscriptionError> {
+        match (sub.created_by.as_deref(), caller) {
+            (Some(created_by), Some(caller)) if created_by != caller => {
+                counter!("api_key_rejected_total", "key" => caller.to_string(), "reason" => "session")
+                    .increment(1);
+                Err(SubscriptionError::ForeignSession)
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub async fn subscribe(
         &self,
         key: SubscriptionKey,
+        caller: Option<&str>,
     ) -> Result<(broadcast::Receiver<BalanceEvent>, Arc<Subscription>), SubscriptionError> {
+        self.ensure_local(key).await;
         let mut subs = self.subscriptions.write().await;
 
         if let Some(existing) = subs.get_mut(&key) {
+            Self::ensure_owner(existing, caller)?;
             existing.clients = existing
                 .clients
                 .checked_add(1)
@@ -171,20 +1573,30 @@ impl SubscriptionManager {
         });
     }
 
+    // sessions without clients expire after SESSION_TTL, webhook sessions after the webhook TTL
+    fn is_expired(sub: &SubWithCounter, now: Instant, webhook_ttl: Duration) -> bool {
+        if sub.clients > 0 {
+            return false;
+        }
+
+        let ttl = if sub.subscription.has_webhook() {
+            webhook_ttl
+        } else {
+            SESSION_TTL
+        };
+
+        sub.idle_since
+            .is_some_and(|idle_since| now.duration_since(idle_since) > ttl)
+    }
+
     async fn cleanup_subs(&self) {
         let mut subs = self.subscriptions.write().await;
 
         let now = Instant::now();
+        let webhook_ttl = self.webhooks.session_ttl();
 
         subs.retain(|key, sub| {
-            let should_remove = if sub.clients == 0 {
-                match sub.idle_since {
-                    Some(idle_since) => now.duration_since(idle_since) > SESSION_TTL,
-                    None => false,
-                }
-            } else {
-                false
-            };
+            let should_remove = Self::is_expired(sub, now, webhook_ttl);
 
             if should_remove {
                 sub.subscription.cancel_token.cancel();
@@ -197,6 +1609,494 @@ impl SubscriptionManager {
             }
 
             !should_remove
-        })
+        });
+
+        // token sets are changed by session updates, so headroom is refreshed periodically
+        self.update_headroom_metrics(&subs).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::webhook_config::WebhookConfig;
+    use crate::domain::AlertComparator;
+    use alloy::primitives::address;
+
+    const OWNER: Address = address!("0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045");
+    const BOB: Address = address!("0x00000000000000000000000000000000000000b0");
+    const CAROL: Address = address!("0x00000000000000000000000000000000000000c0");
+    const USDC: Address = address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const DAI: Address = address!("0x6B175474E89094C44Da98b954EedeAC495271d0F");
+    const LINK: Address = address!("0x514910771AF9Ca656af840dff83E8264EcF986CA");
+    const WETH: Address = address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+
+    const WEBHOOK_TTL: Duration = Duration::from_secs(3_600);
+
+    fn webhook_config() -> WebhookConfig {
+        WebhookConfig {
+            timeout: Duration::from_secs(1),
+            max_attempts: 1,
+            retry_initial_delay: Duration::from_millis(10),
+            retry_max_delay: Duration::from_millis(10),
+            allowed_hosts: HashSet::new(),
+            allow_private_hosts: false,
+            session_ttl: WEBHOOK_TTL,
+        }
+    }
+
+    fn manager(limits: SessionLimits) -> SubscriptionManager {
+        let webhooks = WebhookClient::new(webhook_config()).unwrap();
+        SubscriptionManager::new(limits, None, Arc::new(webhooks))
+    }
+
+    fn no_limits() -> SessionLimits {
+        SessionLimits {
+            max_sessions: 0,
+            max_sessions_per_owner: 0,
+            max_total_tokens: 0,
+        }
+    }
+
+    fn rule(id: &str, token: Address) -> AlertRule {
+        AlertRule {
+            id: id.to_string(),
+            token,
+            comparator: AlertComparator::Below,
+            threshold: U256::from(10),
+            hysteresis: U256::ZERO,
+            triggered: false,
+        }
+    }
+
+    fn key() -> SubscriptionKey {
+        SubscriptionKey {
+            owner: OWNER,
+            network: EvmNetwork::Eth,
+        }
+    }
+
+    async fn idle_for(manager: &SubscriptionManager, idle: Duration) {
+        let mut subs = manager.subscriptions.write().await;
+        subs.get_mut(&key()).unwrap().idle_since = Some(Instant::now() - idle);
+    }
+
+    async fn with_webhook(manager: &SubscriptionManager) -> Arc<Subscription> {
+        let sub = manager
+            .create_or_update(key(), HashSet::from([USDC]), false, None)
+            .await
+            .unwrap();
+        let target = WebhookTarget::new(
+            "https://example.com/hook".to_string(),
+            "secret".to_string(),
+            false,
+            &webhook_config(),
+        )
+        .unwrap();
+        manager.set_webhook(key(), &sub, target);
+        sub
+    }
+
+    #[tokio::test]
+    async fn webhook_session_outlives_session_ttl() {
+        let manager = manager(no_limits());
+        with_webhook(&manager).await;
+
+        idle_for(&manager, SESSION_TTL * 2).await;
+        manager.cleanup_subs().await;
+
+        assert!(manager.local_subscription(key()).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn webhook_session_expires_after_webhook_ttl() {
+        let manager = manager(no_limits());
+        let sub = with_webhook(&manager).await;
+
+        idle_for(&manager, WEBHOOK_TTL + Duration::from_secs(1)).await;
+        manager.cleanup_subs().await;
+
+        assert!(manager.local_subscription(key()).await.is_none());
+        assert!(sub.cancel_token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn session_without_webhook_expires_after_session_ttl() {
+        let manager = manager(no_limits());
+        let sub = with_webhook(&manager).await;
+
+        assert!(manager.remove_webhook(key(), &sub));
+        assert!(!manager.remove_webhook(key(), &sub));
+
+        idle_for(&manager, SESSION_TTL * 2).await;
+        manager.cleanup_subs().await;
+
+        assert!(manager.local_subscription(key()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn update_notifies_only_new_tokens() {
+        let manager = manager(no_limits());
+        let sub = manager
+            .create_or_update(key(), HashSet::from([USDC]), false, None)
+            .await
+            .unwrap();
+        assert!(sub.take_added_tokens().await.is_empty());
+
+        manager
+            .create_or_update(key(), HashSet::from([USDC, DAI]), false, None)
+            .await
+            .unwrap();
+
+        assert_eq!(sub.take_added_tokens().await, vec![DAI]);
+        // drained by the updater
+        assert!(sub.take_added_tokens().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn replace_notifies_added_tokens() {
+        let sub = Subscription::new(HashSet::from([USDC]), false, None);
+
+        sub.replace_tokens(HashSet::from([DAI])).await;
+
+        assert_eq!(sub.take_added_tokens().await, vec![DAI]);
+    }
+
+    #[tokio::test]
+    async fn session_info_reports_snapshot() {
+        let manager = manager(no_limits());
+        let sub = manager
+            .create_or_update(key(), HashSet::from([USDC, DAI]), true, None)
+            .await
+            .unwrap();
+        *sub.balances_snapshot.write().await = HashMap::from([
+            (
+                USDC,
+                Balance {
+                    amount: U256::from(1),
+                    block_number: U256::from(100),
+                },
+            ),
+            (
+                DAI,
+                Balance {
+                    amount: U256::from(2),
+                    block_number: U256::from(101),
+                },
+            ),
+        ]);
+
+        let info = manager.session_info(key()).await.unwrap();
+
+        assert_eq!(info.chain_id, 1);
+        assert_eq!(info.owner, OWNER);
+        assert_eq!(info.tokens, 2);
+        assert_eq!(info.snapshot_tokens, 2);
+        assert_eq!(info.snapshot_block_number.as_deref(), Some("101"));
+        assert!(info.discovery_enabled);
+        assert_eq!(manager.list_sessions().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn terminate_cancels_session() {
+        let manager = manager(no_limits());
+        let sub = manager
+            .create_or_update(key(), HashSet::from([USDC]), false, None)
+            .await
+            .unwrap();
+
+        manager.terminate(key()).await.unwrap();
+
+        assert!(sub.cancel_token.is_cancelled());
+        assert!(manager.session_info(key()).await.is_none());
+        assert!(matches!(
+            manager.terminate(key()).await,
+            Err(SubscriptionError::NoSession)
+        ));
+    }
+
+    fn key_of(owner: Address, network: EvmNetwork) -> SubscriptionKey {
+        SubscriptionKey { owner, network }
+    }
+
+    #[tokio::test]
+    async fn rejects_sessions_over_limits() {
+        let manager = manager(SessionLimits {
+            max_sessions: 2,
+            max_sessions_per_owner: 1,
+            max_total_tokens: 3,
+        });
+        let tokens = || HashSet::from([USDC]);
+
+        manager
+            .create_or_update(key(), tokens(), false, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            manager
+                .create_or_update(key_of(OWNER, EvmNetwork::Arbitrum), tokens(), false, None)
+                .await,
+            Err(SubscriptionError::OwnerSessionsLimitExceeded)
+        ));
+        assert!(matches!(
+            manager
+                .create_or_update(
+                    key_of(BOB, EvmNetwork::Eth),
+                    HashSet::from([USDC, DAI, LINK]),
+                    false,
+                    None
+                )
+                .await,
+            Err(SubscriptionError::TotalTokensLimitExceeded)
+        ));
+        manager
+            .create_or_update(key_of(BOB, EvmNetwork::Eth), tokens(), false, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            manager
+                .create_or_update(key_of(CAROL, EvmNetwork::Eth), tokens(), false, None)
+                .await,
+            Err(SubscriptionError::SessionsLimitExceeded)
+        ));
+
+        // updating an existing session is not a new session
+        manager
+            .create_or_update(key(), tokens(), false, None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn restores_exported_sessions() {
+        let manager = manager(no_limits());
+        let sub = manager
+            .create_or_update(key(), HashSet::from([USDC, DAI]), true, None)
+            .await
+            .unwrap();
+        *sub.balances_snapshot.write().await = HashMap::from([(
+            USDC,
+            Balance {
+                amount: U256::from(7),
+                block_number: U256::from(120),
+            },
+        )]);
+
+        let sessions = manager.export_sessions().await;
+        assert_eq!(sessions[0].last_block, Some(120));
+
+        let restored = self::manager(no_limits());
+        restored.restore_sessions(sessions, |_| None).await;
+
+        let sub = restored.local_subscription(key()).await.unwrap();
+        assert_eq!(*sub.tokens.read().await, HashSet::from([USDC, DAI]));
+        assert_eq!(
+            sub.balances_snapshot.read().await[&USDC].amount,
+            U256::from(7)
+        );
+        assert!(sub.discovery_enabled.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn restore_skips_sessions_over_limits() {
+        let manager = manager(no_limits());
+        for owner in [OWNER, BOB] {
+            manager
+                .create_or_update(
+                    key_of(owner, EvmNetwork::Eth),
+                    HashSet::from([USDC]),
+                    false,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        let mut sessions = manager.export_sessions().await;
+        // unknown network
+        let mut unknown = manager.export_session(key()).await.unwrap();
+        unknown.chain_id = 999_999;
+        sessions.push(unknown);
+
+        let restored = self::manager(SessionLimits {
+            max_sessions: 1,
+            ..no_limits()
+        });
+        restored.restore_sessions(sessions, |_| None).await;
+
+        assert_eq!(restored.list_sessions().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn finality_change_drops_confirmed_balances() {
+        let sub = Subscription::new(HashSet::from([USDC]), false, None);
+        let balance = Balance {
+            amount: U256::from(1),
+            block_number: U256::from(100),
+        };
+        *sub.confirmed_snapshot.write().await = HashMap::from([(USDC, balance.clone())]);
+
+        sub.set_finality(Finality::Latest).await;
+        assert_eq!(sub.confirmed_snapshot.read().await.len(), 1);
+
+        sub.set_finality(Finality::Finalized).await;
+        assert_eq!(sub.finality(), Finality::Finalized);
+        assert!(sub.confirmed_snapshot.read().await.is_empty());
+    }
+
+    fn nft_holding(token_ids: &[u64], block_number: u64) -> NftHolding {
+        NftHolding {
+            balance: U256::from(token_ids.len()),
+            token_ids: token_ids.iter().map(|id| U256::from(*id)).collect(),
+            block_number: U256::from(block_number),
+        }
+    }
+
+    #[test]
+    fn nft_holdings_keep_newer_block() {
+        let sub = Subscription::new(HashSet::new(), false, None);
+
+        let updates = sub.update_nft_holdings(HashMap::from([(USDC, nft_holding(&[1], 100))]));
+        assert_eq!(updates[&USDC].added, vec!["1".to_string()]);
+
+        // a late fetch of an older block is skipped
+        let updates = sub.update_nft_holdings(HashMap::from([(USDC, nft_holding(&[], 99))]));
+        assert!(updates.is_empty());
+        assert_eq!(sub.nft_holding(USDC), Some(nft_holding(&[1], 100)));
+
+        // unchanged holding is stored without an update
+        let updates = sub.update_nft_holdings(HashMap::from([(USDC, nft_holding(&[1], 101))]));
+        assert!(updates.is_empty());
+        assert_eq!(sub.nft_holding(USDC).unwrap().block_number, U256::from(101));
+    }
+
+    #[test]
+    fn nft_snapshot_replays_holdings() {
+        let sub = Subscription::new(HashSet::new(), false, None);
+        assert!(sub.nft_snapshot().is_none());
+
+        sub.update_nft_holdings(HashMap::from([
+            (USDC, nft_holding(&[1, 2], 100)),
+            (DAI, nft_holding(&[], 102)),
+        ]));
+
+        let snapshot = sub.nft_snapshot().unwrap();
+        assert_eq!(snapshot.block_number, "102");
+        assert_eq!(
+            snapshot.collections[&USDC].added,
+            vec!["1".to_string(), "2".to_string()]
+        );
+
+        let replica = Subscription::new(HashSet::new(), false, None);
+        replica.apply_nft_update(&snapshot);
+        assert_eq!(
+            replica.nft_holding(USDC).unwrap().token_ids,
+            nft_holding(&[1, 2], 100).token_ids
+        );
+    }
+
+    #[test]
+    fn erc1155_balances_report_changes_of_newer_blocks() {
+        let sub = Subscription::new(HashSet::new(), false, None);
+        let token = |id: u64| Erc1155Token {
+            contract: USDC,
+            id: U256::from(id),
+        };
+
+        let diff = sub.update_erc1155_balances(
+            HashMap::from([(token(1), U256::from(5)), (token(2), U256::ZERO)]),
+            U256::from(100),
+        );
+        assert_eq!(diff.len(), 2);
+
+        // unchanged amounts and older blocks are not reported
+        let diff = sub.update_erc1155_balances(
+            HashMap::from([(token(1), U256::from(5)), (token(2), U256::from(3))]),
+            U256::from(101),
+        );
+        assert_eq!(diff, HashMap::from([(token(2), U256::from(3))]));
+        let diff =
+            sub.update_erc1155_balances(HashMap::from([(token(2), U256::ZERO)]), U256::from(99));
+        assert!(diff.is_empty());
+
+        let snapshot = sub.erc1155_snapshot().unwrap();
+        assert_eq!(snapshot.block_number, "101");
+        let replica = Subscription::new(HashSet::new(), false, None);
+        replica.apply_erc1155_update(&snapshot);
+        assert_eq!(replica.erc1155_balances()[&token(2)].amount, U256::from(3));
+    }
+
+    #[tokio::test]
+    async fn discovered_tokens_respect_session_limit() {
+        let manager = manager(no_limits());
+        let sub = manager
+            .create_or_update(key(), HashSet::from([USDC, WETH]), false, None)
+            .await
+            .unwrap();
+
+        // WETH is wrapped, USDC is already watched
+        let added = manager
+            .add_discovered_tokens(key(), &sub, [USDC, DAI, LINK], 2, &HashSet::from([WETH]))
+            .await;
+
+        assert_eq!(added, vec![DAI]);
+        assert_eq!(*sub.tokens.read().await, HashSet::from([USDC, WETH, DAI]));
+    }
+
+    #[tokio::test]
+    async fn discovered_tokens_respect_global_limit() {
+        let manager = manager(SessionLimits {
+            max_total_tokens: 2,
+            ..no_limits()
+        });
+        let sub = manager
+            .create_or_update(key(), HashSet::from([USDC]), false, None)
+            .await
+            .unwrap();
+
+        let added = manager
+            .add_discovered_tokens(key(), &sub, [DAI, LINK], 10, &HashSet::new())
+            .await;
+
+        assert_eq!(added, vec![DAI]);
+    }
+
+    #[test]
+    fn finds_alert_rules_outside_token_set() {
+        let native = EvmNetwork::Eth.native_token_address();
+        let sub = Subscription::new(HashSet::from([USDC, DAI]), false, None);
+        sub.set_alert_rules(vec![
+            rule("usdc-low", USDC),
+            rule("dai-low", DAI),
+            rule("eth-low", native),
+        ]);
+
+        assert_eq!(
+            sub.alert_rules_outside(&HashSet::new(), native),
+            vec!["dai-low".to_string(), "usdc-low".to_string()]
+        );
+        assert_eq!(
+            sub.alert_rules_outside(&HashSet::from([DAI]), native),
+            vec!["usdc-low".to_string()]
+        );
+        assert!(sub
+            .alert_rules_outside(&HashSet::from([USDC, DAI]), native)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn replace_drops_balances_of_removed_tokens() {
+        let sub = Subscription::new(HashSet::from([USDC, DAI]), false, None);
+        let balance = Balance {
+            amount: U256::from(1),
+            block_number: U256::from(100),
+        };
+        *sub.balances_snapshot.write().await =
+            HashMap::from([(USDC, balance.clone()), (DAI, balance)]);
+
+        let removed = sub.replace_tokens(HashSet::from([DAI])).await;
+
+        assert_eq!(removed, vec![USDC]);
+        assert_eq!(*sub.tokens.read().await, HashSet::from([DAI]));
+        assert!(!sub.balances_snapshot.read().await.contains_key(&USDC));
     }
 }
@@ -1,5 +1,7 @@
 use std::{
     collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -10,9 +12,9 @@ use serde::Deserialize;
 use tokio::sync::RwLock;
 
 use crate::{
-    config::constants::TOKEN_FETCH_CONCURRENCY,
+    config::constants::{LOCAL_TOKEN_LISTS_RELOAD_INTERVAL_SECS, TOKEN_FETCH_CONCURRENCY},
     domain::{EvmNetwork, Token},
-    services::errors::FetcherError,
+    services::{errors::FetcherError, local_token_lists::LocalTokenLists},
 };
 
 const CACHE_TTL: Duration = Duration::from_secs(3600 * 5); // 5 hours
@@ -27,6 +29,7 @@ pub struct TokenListFetcher {
     in_flight: RwLock<HashSet<String>>,
     client: Client,
     ttl: Duration,
+    local_lists: Arc<LocalTokenLists>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -35,20 +38,49 @@ struct ApiResponse {
 }
 
 impl TokenListFetcher {
-    pub fn new() -> Self {
+    pub fn new(local_list_paths: Vec<PathBuf>) -> Self {
         Self {
             cache: RwLock::new(HashMap::new()),
             client: Client::new(),
             ttl: CACHE_TTL,
             in_flight: RwLock::new(HashSet::new()),
+            local_lists: Arc::new(LocalTokenLists::new(local_list_paths)),
         }
     }
 
+    // load local token lists and watch them for changes on disk
+    pub async fn init_local_lists(&self) {
+        self.local_lists.reload().await;
+
+        let names = self.local_lists.names().await;
+        if names.is_empty() {
+            tracing::warn!("no local token lists loaded, `default` resolves to the bundled list");
+        } else {
+            tracing::info!(lists = ?names, "local token lists loaded");
+        }
+
+        Arc::clone(&self.local_lists)
+            .spawn_reloader(Duration::from_secs(LOCAL_TOKEN_LISTS_RELOAD_INTERVAL_SECS));
+    }
+
+    // urls could contain symbolic names of local lists (`default`, `local:<name>`),
+    // they are resolved from disk, the rest is fetched via http and cached
     pub async fn get_tokens(
         &self,
         urls: &[String],
         network: EvmNetwork,
     ) -> Result<HashSet<Address>, FetcherError> {
+        let (local_names, urls): (Vec<String>, Vec<String>) = urls
+            .iter()
+            .cloned()
+            .partition(|url| LocalTokenLists::is_local_name(url));
+
+        let mut tokens = self.local_lists.get_tokens(&local_names, network).await?;
+        if urls.is_empty() {
+            return Ok(tokens);
+        }
+
+        let urls = urls.as_slice();
         let uncached_urls = self.get_uncached_urls(urls).await;
 
         // fetch uncached lists
@@ -73,7 +105,9 @@ impl TokenListFetcher {
         }
 
         let from_cache = self.collect_from_cache(urls, network).await;
-        Ok(from_cache)
+        tokens.extend(from_cache);
+
+        Ok(tokens)
     }
 
     async fn fetch_and_cache(&self, urls: &[String]) -> Result<(), FetcherError> {
@@ -0,0 +1,461 @@
+use crate::config::trace_config::{TraceConfig, TraceMethod};
+use crate::domain::EvmNetwork;
+use alloy::eips::BlockNumberOrTag;
+use alloy::primitives::{Address, B256, U256};
+use alloy::providers::{DynProvider, Provider};
+use metrics::{counter, gauge};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{interval, MissedTickBehavior};
+
+// trace_block entry, fields depend on the type (call, create, suicide, reward)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BlockTrace {
+    #[serde(rename = "type")]
+    kind: String,
+    action: TraceAction,
+    result: Option<TraceResult>,
+    error: Option<String>,
+    // position in the call tree of the transaction, subtraces follow their parent
+    #[serde(default)]
+    trace_address: Vec<usize>,
+    transaction_hash: Option<B256>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceAction {
+    from: Option<Address>,
+    to: Option<Address>,
+    value: Option<U256>,
+    call_type: Option<String>,
+    // suicide
+    address: Option<Address>,
+    refund_address: Option<Address>,
+    balance: Option<U256>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraceResult {
+    // create
+    address: Option<Address>,
+}
+
+// debug_traceBlockByNumber entry with the callTracer
+#[derive(Debug, Deserialize)]
+struct TxTrace {
+    result: Option<CallFrame>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CallFrame {
+    #[serde(rename = "type")]
+    kind: String,
+    from: Address,
+    to: Option<Address>,
+    value: Option<U256>,
+    error: Option<String>,
+    #[serde(default)]
+    calls: Vec<CallFrame>,
+}
+
+/// Blocks with native transfers of one owner, the route is removed when the registration is dropped
+pub struct TraceRegistration {
+    id: u64,
+    owner: Address,
+    receiver: mpsc::UnboundedReceiver<u64>,
+    dispatcher: Arc<TraceDispatcher>,
+}
+
+impl TraceRegistration {
+    pub async fn recv(&mut self) -> Option<u64> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for TraceRegistration {
+    fn drop(&mut self) {
+        self.dispatcher.unregister(self.owner, self.id);
+    }
+}
+
+// native transfers by internal calls (DEX unwraps, bridge withdrawals) emit no log of the owner,
+// so every new block of the network is traced once for all sessions and owners touched
+// by value transfers are notified with the block number to refresh their native balance
+pub struct TraceDispatcher {
+    network: EvmNetwork,
+    provider: DynProvider,
+    method: TraceMethod,
+    poll_interval: Duration,
+    max_blocks_per_poll: u64,
+    routes: RwLock<HashMap<Address, HashMap<u64, mpsc::UnboundedSender<u64>>>>,
+    next_id: AtomicU64,
+    started: AtomicBool,
+}
+
+impl TraceDispatcher {
+    pub fn new(
+        network: EvmNetwork,
+        provider: DynProvider,
+        method: TraceMethod,
+        config: &TraceConfig,
+    ) -> Self {
+        Self {
+            network,
+            provider,
+            method,
+            poll_interval: config.poll_interval,
+            max_blocks_per_poll: config.max_blocks_per_poll,
+            routes: RwLock::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    // tracing is started with the first registration
+    pub fn register(self: &Arc<Self>, owner: Address) -> TraceRegistration {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+
+        {
+            let mut routes = self.routes.write().unwrap_or_else(|e| e.into_inner());
+            routes.entry(owner).or_default().insert(id, sender);
+            gauge!("trace_dispatcher_owners", "network" => self.network.to_string())
+                .set(routes.len() as f64);
+        }
+
+        if !self.started.swap(true, Ordering::SeqCst) {
+            tokio::spawn(Arc::clone(self).run());
+        }
+
+        TraceRegistration {
+            id,
+            owner,
+            receiver,
+            dispatcher: Arc::clone(self),
+        }
+    }
+
+    fn unregister(&self, owner: Address, id: u64) {
+        let mut routes = self.routes.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(owner_routes) = routes.get_mut(&owner) {
+            owner_routes.remove(&id);
+            if owner_routes.is_empty() {
+                routes.remove(&owner);
+            }
+        }
+
+        gauge!("trace_dispatcher_owners", "network" => self.network.to_string())
+            .set(routes.len() as f64);
+    }
+
+    fn has_routes(&self) -> bool {
+        let routes = self.routes.read().unwrap_or_else(|e| e.into_inner());
+        !routes.is_empty()
+    }
+
+    // poll the head and trace new blocks, a failed block is retried on the next tick
+    // blocks are not traced while no owner is registered
+    async fn run(self: Arc<Self>) {
+        tracing::info!(network = %self.network, method = self.method.as_str(), "start block tracing");
+
+        let mut interval = interval(self.poll_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut last_block: Option<u64> = None;
+
+        loop {
+            interval.tick().await;
+
+            if !self.has_routes() {
+                last_block = None;
+                continue;
+            }
+
+            let head = match self.provider.get_block_number().await {
+                Ok(head) => head,
+                Err(err) => {
+                    counter!("trace_errors_total", "op" => "block_number").increment(1);
+                    tracing::warn!(error = %err, network = %self.network, "unable to get block number for tracing");
+                    continue;
+                }
+            };
+
+            let next_block = last_block.map_or(head, |block| block + 1);
+            if head < next_block {
+                continue;
+            }
+
+            // the snapshot update covers blocks of a larger gap
+            let from_block = next_block.max(head.saturating_sub(self.max_blocks_per_poll - 1));
+            if from_block > next_block {
+                counter!("trace_blocks_skipped_total").increment(from_block - next_block);
+            }
+
+            for block in from_block..=head {
+                match self.touched_accounts(block).await {
+                    Ok(accounts) => {
+                        counter!("traced_blocks_total").increment(1);
+                        self.dispatch(block, &accounts);
+                        last_block = Some(block);
+                    }
+                    Err(err) => {
+                        counter!("trace_errors_total", "op" => "trace").increment(1);
+                        tracing::warn!(
+                            error = %err,
+                            network = %self.network,
+                            block,
+                            method = self.method.as_str(),
+                            "unable to trace block"
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn dispatch(&self, block: u64, accounts: &HashSet<Address>) {
+        let routes = self.routes.read().unwrap_or_else(|e| e.into_inner());
+
+        for account in accounts {
+            let Some(owner_routes) = routes.get(account) else {
+                continue;
+            };
+
+            for sender in owner_routes.values() {
+                counter!("trace_dispatcher_routed_total").increment(1);
+                let _ = sender.send(block);
+            }
+        }
+    }
+
+    // senders and receivers of value transfers in the block
+    async fn touched_accounts(&self, block: u64) -> Result<HashSet<Address>, String> {
+        let block_number = BlockNumberOrTag::Number(block);
+        let mut accounts = HashSet::new();
+
+        match self.method {
+            TraceMethod::TraceBlock => {
+                let traces: Vec<BlockTrace> = self
+                    .provider
+                    .raw_request("trace_block".into(), (block_number,))
+                    .await
+                    .map_err(|err| err.to_string())?;
+
+                Self::collect_traces(&traces, &mut accounts);
+            }
+            TraceMethod::DebugTraceBlock => {
+                let traces: Vec<TxTrace> = self
+                    .provider
+                    .raw_request(
+                        "debug_traceBlockByNumber".into(),
+                        (block_number, json!({ "tracer": "callTracer" })),
+                    )
+                    .await
+                    .map_err(|err| err.to_string())?;
+
+                for frame in traces.iter().filter_map(|trace| trace.result.as_ref()) {
+                    Self::collect_frame(frame, &mut accounts);
+                }
+            }
+        }
+
+        Ok(accounts)
+    }
+
+    // subtraces of a reverted call are reverted too, even without their own error
+    fn collect_traces(traces: &[BlockTrace], accounts: &mut HashSet<Address>) {
+        let mut reverted: Vec<(Option<B256>, &[usize])> = Vec::new();
+
+        for trace in traces {
+            let is_reverted = reverted.iter().any(|(tx_hash, trace_address)| {
+                *tx_hash == trace.transaction_hash && trace.trace_address.starts_with(trace_address)
+            });
+            if is_reverted {
+                continue;
+            }
+
+            if trace.error.is_some() {
+                reverted.push((trace.transaction_hash, &trace.trace_address));
+                continue;
+            }
+
+            Self::collect_trace(trace, accounts);
+        }
+    }
+
+    fn collect_trace(trace: &BlockTrace, accounts: &mut HashSet<Address>) {
+        let action = &trace.action;
+        let has_value = |value: Option<U256>| value.is_some_and(|value| !value.is_zero());
+
+        let (from, to) = match trace.kind.as_str() {
+            // delegatecall / staticcall don't move value
+            "call"
+                if has_value(action.value)
+                    && matches!(action.call_type.as_deref(), Some("call") | None) =>
+            {
+                (action.from, action.to)
+            }
+            "create" if has_value(action.value) => (
+                action.from,
+                trace.result.as_ref().and_then(|result| result.address),
+            ),
+            "suicide" if has_value(action.balance) => (action.address, action.refund_address),
+            _ => return,
+        };
+
+        accounts.extend(from);
+        accounts.extend(to);
+    }
+
+    // reverted frames move nothing, including their subcalls
+    fn collect_frame(frame: &CallFrame, accounts: &mut HashSet<Address>) {
+        if frame.error.is_some() {
+            return;
+        }
+
+        let moves_value = frame.value.is_some_and(|value| !value.is_zero())
+            && !matches!(frame.kind.as_str(), "DELEGATECALL" | "STATICCALL");
+        if moves_value {
+            accounts.insert(frame.from);
+            accounts.extend(frame.to);
+        }
+
+        for call in &frame.calls {
+            Self::collect_frame(call, accounts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::address;
+
+    const ALICE: Address = address!("0x00000000000000000000000000000000000000a1");
+    const BOB: Address = address!("0x00000000000000000000000000000000000000b0");
+    const CAROL: Address = address!("0x00000000000000000000000000000000000000c0");
+    const ROUTER: Address = address!("0x0000000000000000000000000000000000000f00");
+
+    fn traced(traces: serde_json::Value) -> HashSet<Address> {
+        let traces: Vec<BlockTrace> = serde_json::from_value(traces).unwrap();
+        let mut accounts = HashSet::new();
+        TraceDispatcher::collect_traces(&traces, &mut accounts);
+        accounts
+    }
+
+    fn framed(frame: serde_json::Value) -> HashSet<Address> {
+        let traces: Vec<TxTrace> = serde_json::from_value(json!([{ "result": frame }])).unwrap();
+        let mut accounts = HashSet::new();
+        for frame in traces.iter().filter_map(|trace| trace.result.as_ref()) {
+            TraceDispatcher::collect_frame(frame, &mut accounts);
+        }
+        accounts
+    }
+
+    fn call(
+        from: Address,
+        to: Address,
+        value: &str,
+        call_type: &str,
+        trace_address: &[usize],
+    ) -> serde_json::Value {
+        json!({
+            "type": "call",
+            "action": { "from": from, "to": to, "value": value, "callType": call_type },
+            "result": { "gasUsed": "0x0", "output": "0x" },
+            "traceAddress": trace_address,
+            "transactionHash": B256::repeat_byte(1),
+        })
+    }
+
+    #[test]
+    fn trace_collects_value_calls() {
+        let accounts = traced(json!([
+            call(ALICE, ROUTER, "0x0", "call", &[]),
+            call(ROUTER, BOB, "0x10", "call", &[0]),
+        ]));
+        assert_eq!(accounts, HashSet::from([ROUTER, BOB]));
+    }
+
+    #[test]
+    fn trace_skips_delegatecall_and_staticcall() {
+        let accounts = traced(json!([
+            call(ALICE, ROUTER, "0x0", "call", &[]),
+            call(ROUTER, BOB, "0x10", "delegatecall", &[0]),
+            call(ROUTER, CAROL, "0x10", "staticcall", &[1]),
+        ]));
+        assert!(accounts.is_empty());
+    }
+
+    #[test]
+    fn trace_skips_reverted_calls_with_subtraces() {
+        let mut reverted = call(ALICE, ROUTER, "0x5", "call", &[0]);
+        reverted["error"] = json!("Reverted");
+
+        let accounts = traced(json!([
+            call(ALICE, ROUTER, "0x0", "call", &[]),
+            reverted,
+            call(ROUTER, BOB, "0x10", "call", &[0, 0]),
+            call(ROUTER, CAROL, "0x10", "call", &[1]),
+        ]));
+        assert_eq!(accounts, HashSet::from([ROUTER, CAROL]));
+    }
+
+    #[test]
+    fn trace_collects_create_and_selfdestruct() {
+        let accounts = traced(json!([
+            {
+                "type": "create",
+                "action": { "from": ALICE, "value": "0x1", "gas": "0x0", "init": "0x" },
+                "result": { "address": ROUTER, "code": "0x", "gasUsed": "0x0" },
+                "traceAddress": [],
+            },
+            {
+                "type": "suicide",
+                "action": { "address": CAROL, "refundAddress": BOB, "balance": "0x2" },
+                "result": null,
+                "traceAddress": [0],
+            },
+            {
+                "type": "reward",
+                "action": { "author": ALICE, "rewardType": "block", "value": "0x3" },
+                "result": null,
+                "traceAddress": [],
+            },
+        ]));
+        assert_eq!(accounts, HashSet::from([ALICE, ROUTER, CAROL, BOB]));
+    }
+
+    #[test]
+    fn frames_collect_nested_value_calls() {
+        let accounts = framed(json!({
+            "type": "CALL", "from": ALICE, "to": ROUTER, "value": "0x0",
+            "calls": [
+                { "type": "CALL", "from": ROUTER, "to": BOB, "value": "0x10" },
+                { "type": "DELEGATECALL", "from": ROUTER, "to": CAROL, "value": "0x10",
+                  "calls": [{ "type": "CALL", "from": ROUTER, "to": CAROL, "value": "0x1" }] },
+            ],
+        }));
+        assert_eq!(accounts, HashSet::from([ROUTER, BOB, CAROL]));
+    }
+
+    #[test]
+    fn frames_skip_reverted_subtrees() {
+        let accounts = framed(json!({
+            "type": "CALL", "from": ALICE, "to": ROUTER, "value": "0x1",
+            "calls": [
+                { "type": "CALL", "from": ROUTER, "to": BOB, "value": "0x10", "error": "execution reverted",
+                  "calls": [{ "type": "CALL", "from": BOB, "to": CAROL, "value": "0x1" }] },
+                { "type": "STATICCALL", "from": ROUTER, "to": CAROL },
+            ],
+        }));
+        assert_eq!(accounts, HashSet::from([ALICE, ROUTER]));
+    }
+}
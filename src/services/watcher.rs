@@ -1,28 +1,55 @@
+use crate::config::constants::{
+    BACKFILL_PAGE_SIZE, MAX_ENUMERATED_NFTS_PER_COLLECTION, MAX_PENDING_TXS_PER_SESSION,
+};
+use crate::config::discovery_config::DiscoveryConfig;
+use crate::config::network_config::wrapped_token_set;
+use crate::evm::cow::{CoWSwapEthFlow, GPv2Settlement};
+use crate::evm::erc1155::ERC1155;
 use crate::evm::erc20::ERC20;
-use alloy::eips::BlockId;
+use crate::evm::erc721::ERC721;
+use crate::evm::smart_account::{EntryPoint, Safe};
+use alloy::eips::{BlockId, BlockNumberOrTag};
 use alloy::{
-    primitives::Address,
+    primitives::{Address, B256, U256},
     providers::{DynProvider, Provider},
     rpc::types::{Filter, Log, Topic},
     sol_types::SolEvent,
 };
-use futures::future::BoxFuture;
-use futures::StreamExt;
-use metrics::counter;
-use std::collections::HashMap;
-use std::{sync::Arc, time::Duration};
+use metrics::{counter, gauge};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::atomic::Ordering;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
-use tokio::sync::RwLockWriteGuard;
+use tokio::sync::{broadcast, RwLockWriteGuard};
 use tokio::time::interval;
+use tokio_util::sync::CancellationToken;
 
+use crate::services::balance_strategies::{BalanceStrategies, RefreshTrigger};
 use crate::services::fetch_balances_via_multicall::{BalanceCallCtx, BalancesWithBlock};
-use crate::services::subscription_manager::{Balance, BalanceSnapshot};
+use crate::services::log_dispatcher::{LogDispatcher, SourceEvent};
+use crate::services::mempool_dispatcher::{MempoolDispatcher, MempoolRegistration, PendingTx};
+use crate::services::price_oracle::PriceOracle;
+use crate::services::subscription_manager::{Balance, BalanceSnapshot, SubscriptionManager};
+use crate::services::trace_dispatcher::TraceDispatcher;
 use crate::{
-    domain::{BalanceEvent, EvmNetwork},
+    domain::{
+        AlertRule, AlertTransition, BalanceEvent, Erc1155Token, Erc1155Update, EvmNetwork,
+        Finality, NftHolding, NftUpdate, PendingBalanceEvent, PendingStatus, RefreshStrategy,
+        SubscriptionKey,
+    },
     evm::wrapped::WrappedToken,
     services::{fetch_balances_via_multicall, subscription_manager::Subscription},
 };
 
+// pending transaction of the owner tracked until it is mined or dropped
+struct TrackedTx {
+    tx: PendingTx,
+    seen_at: Instant,
+}
+
 enum WethEvents {
     Deposit(Option<BlockId>),
     Withdrawal(Option<BlockId>),
@@ -35,6 +62,9 @@ pub enum WatcherError {
 
     #[error("Parse log error for network: {1}, owner: {2}: {0}")]
     ParseLog(EvmNetwork, Address, String),
+
+    #[error("unable to get the finality block in network{0}: {1}")]
+    FinalityBlock(EvmNetwork, String),
 }
 
 #[derive(Error, Debug, Clone)]
@@ -46,75 +76,1295 @@ pub enum ParseWeb3LogsError {
     UnexpectedHashSignature,
 }
 
+#[derive(Clone)]
 pub struct WatcherContext {
     pub owner: Address,
     pub provider: DynProvider,
     pub network: EvmNetwork,
     pub multicall3: Address,
-    pub ws_provider: DynProvider,
-    pub weth9_address: Address,
+    pub log_dispatcher: Arc<LogDispatcher>,
+    // wrapper -> underlying (native token address for the native one)
+    pub wrapped_tokens: HashMap<Address, Address>,
+    pub discovery: DiscoveryConfig,
+    pub max_watched_tokens_limit: usize,
+    pub max_backfill_blocks: u64,
+    pub finality_poll_interval: Duration,
+    // None if pricing is disabled
+    pub price_oracle: Option<Arc<PriceOracle>>,
+    // None if the network has no mempool subscription
+    pub mempool_dispatcher: Option<Arc<MempoolDispatcher>>,
+    pub pending_check_interval: Duration,
+    pub pending_tx_timeout: Duration,
+    // None if blocks of the network are not traced
+    pub trace_dispatcher: Option<Arc<TraceDispatcher>>,
+    // None if no balance strategies are configured
+    pub balance_strategies: Option<Arc<BalanceStrategies>>,
+    // session and global token limits of discovered tokens
+    pub sub_manager: Arc<SubscriptionManager>,
 }
 
 pub struct Watcher {
     ctx: Arc<WatcherContext>,
     sub: Arc<Subscription>,
+    // session token, or its child when watchers are run by the cluster leader
+    cancel: CancellationToken,
 }
 
 impl Watcher {
-    pub fn new(ctx: WatcherContext, subscription: Arc<Subscription>) -> Self {
+    pub fn new(
+        ctx: WatcherContext,
+        subscription: Arc<Subscription>,
+        cancel: CancellationToken,
+    ) -> Self {
         Self {
             ctx: Arc::new(ctx),
             sub: subscription,
+            cancel,
         }
     }
 
     // create all necessary watchers to sync balances
-    // spawn_erc20_transfer_listeners - spawn listener for erc20 transfer events
-    // spawn_wrapped_events_listener - spawn listener for wrapped token events (deposit/withdrawal)
+    // spawn_log_listener - spawn listener for erc20 transfer and wrapped token events (deposit/withdrawal)
     // spawn_snapshot_updater - spawn listener for snapshot update (every interval_secs)
+    // spawn_finality_tracker - follow balances at the finality level of the session
+    // spawn_pending_tracker - project balances with pending transactions of the owner (if enabled)
+    // spawn_trace_listener - refresh native balance on internal transfers found in block traces (if enabled)
+    // spawn_strategy_tracker - refresh rebasing / interest bearing tokens and shares of share-based tokens
+    // spawn_token_discovery - scan transfer history for held tokens (if discovery is enabled or gets enabled)
+    // restored sessions already went through discovery before restart
     pub async fn spawn_watchers(&self, interval_secs: usize) {
-        self.spawn_snapshot_updater(interval_secs).await;
-        self.spawn_erc20_transfer_listeners().await;
-        self.spawn_weth9_events_listener().await;
+        let resume_from_block = self.sub.take_resume_block().await;
+
+        self.spawn_snapshot_updater(interval_secs, resume_from_block)
+            .await;
+        self.spawn_log_listener();
+        self.spawn_finality_tracker();
+        self.spawn_pending_tracker();
+        self.spawn_trace_listener();
+        self.spawn_strategy_tracker();
+
+        self.spawn_token_discovery(
+            resume_from_block.is_none() && self.sub.discovery_enabled.load(Ordering::SeqCst),
+        );
+    }
+
+    // scan incoming transfers of the owner over the configured block range
+    // and add tokens which are still held to the watched set
+    // the scan is repeated when discovery is turned on for the running session
+    fn spawn_token_discovery(&self, scan_on_start: bool) {
+        let ctx = Arc::clone(&self.ctx);
+        let sub = Arc::clone(&self.sub);
+        let cancel = self.cancel.clone();
+
+        tokio::spawn(async move {
+            let mut scan = scan_on_start;
+            loop {
+                // created before the scan, so a request during the scan isn't lost
+                let requested = sub.discovery_requested.notified();
+
+                if scan {
+                    tokio::select! {
+                        _ = cancel.cancelled() => { break; }
+                        _ = Self::discover_tokens_from_history(Arc::clone(&ctx), Arc::clone(&sub)) => {}
+                    }
+                }
+
+                tokio::select! {
+                    _ = cancel.cancelled() => { break; }
+                    _ = requested => { scan = true; }
+                }
+            }
+
+            tracing::info!("cancelled token discovery");
+        });
+    }
+
+    async fn discover_tokens_from_history(ctx: Arc<WatcherContext>, sub: Arc<Subscription>) {
+        counter!("token_discovery_runs_total").increment(1);
+
+        let latest = match ctx.provider.get_block_number().await {
+            Ok(block_number) => block_number,
+            Err(err) => {
+                counter!("token_discovery_failed_total").increment(1);
+                tracing::error!(
+                    error = %err,
+                    network = %ctx.network,
+                    owner = %ctx.owner,
+                    "unable to get block number for token discovery"
+                );
+                return;
+            }
+        };
+
+        let base = Filter::new()
+            .event_signature(ERC20::Transfer::SIGNATURE_HASH)
+            .topic2(Topic::from(ctx.owner));
+
+        // paged eth_getLogs, providers limit block range per request
+        let mut candidates: HashSet<Address> = HashSet::new();
+        let mut start = latest.saturating_sub(ctx.discovery.block_range);
+        while start <= latest {
+            let end = start
+                .saturating_add(ctx.discovery.page_size - 1)
+                .min(latest);
+            let filter = base.clone().from_block(start).to_block(end);
+
+            match ctx.provider.get_logs(&filter).await {
+                Ok(logs) => {
+                    for log in logs {
+                        let Ok(decoded) = log.log_decode::<ERC20::Transfer>() else {
+                            continue;
+                        };
+
+                        let token = decoded.address();
+                        if !ctx
+                            .discovery
+                            .is_spam_transfer(&token, decoded.inner.data.value)
+                        {
+                            candidates.insert(token);
+                        }
+                    }
+                }
+                Err(err) => {
+                    counter!("token_discovery_get_logs_failed_total").increment(1);
+                    tracing::warn!(
+                        error = %err,
+                        network = %ctx.network,
+                        owner = %ctx.owner,
+                        from = start,
+                        to = end,
+                        "unable to get transfer logs for token discovery"
+                    );
+                }
+            }
+
+            start = end + 1;
+        }
+
+        {
+            let tokens = sub.tokens.read().await;
+            candidates.retain(|token| !tokens.contains(token));
+        }
+
+        if candidates.is_empty() {
+            tracing::info!(owner = %ctx.owner, network = %ctx.network, "no new tokens discovered");
+            return;
+        }
+
+        let candidates: Vec<Address> = candidates.into_iter().collect();
+        let balance_call_ctx = Arc::new(BalanceCallCtx {
+            owner: ctx.owner,
+            network: ctx.network,
+            provider: Arc::new(ctx.provider.clone()),
+            multicall3: ctx.multicall3,
+        });
+
+        // tokens without working balanceOf or with zero balance are dropped
+        let (balances, block_number) =
+            match fetch_balances_via_multicall::fetch_erc20_balances_allow_failure(
+                balance_call_ctx,
+                &candidates,
+                BlockId::number(latest),
+            )
+            .await
+            {
+                Ok(result) => result,
+                Err(err) => {
+                    counter!("token_discovery_failed_total").increment(1);
+                    tracing::error!(
+                        error = %err,
+                        owner = %ctx.owner,
+                        network = %ctx.network,
+                        "unable to get balances of discovered tokens"
+                    );
+                    return;
+                }
+            };
+
+        let held: HashMap<Address, U256> = balances
+            .into_iter()
+            .filter(|(_, balance)| !balance.is_zero())
+            .collect();
+
+        let added = Self::add_discovered_tokens(&ctx, &sub, held.keys().copied()).await;
+        if added.is_empty() {
+            return;
+        }
+
+        let new_balances: HashMap<Address, U256> = held
+            .into_iter()
+            .filter(|(token, _)| added.contains(token))
+            .collect();
+
+        let diff = Self::update_watched_balances_and_take_diff(
+            &sub,
+            ctx.network,
+            (new_balances, block_number),
+        )
+        .await;
+
+        if !diff.is_empty() && sub.publish(BalanceEvent::BalanceUpdate(diff)) {
+            counter!("balance_updates_sent_total").increment(1);
+        }
+    }
+
+    // add token from an incoming transfer to the watched set if discovery is enabled for the session
+    // the token should pass the spam filter and have a non zero balance
+    async fn discover_token_from_transfer(
+        ctx: &WatcherContext,
+        sub: &Subscription,
+        log: &Log,
+        balances: &HashMap<Address, U256>,
+    ) {
+        if !sub.discovery_enabled.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let Ok(decoded) = log.log_decode::<ERC20::Transfer>() else {
+            return;
+        };
+
+        let token = decoded.address();
+        let data = decoded.inner.data;
+        if data.to != ctx.owner || ctx.discovery.is_spam_transfer(&token, data.value) {
+            return;
+        }
+
+        if balances.get(&token).is_none_or(|balance| balance.is_zero()) {
+            return;
+        }
+
+        Self::add_discovered_tokens(ctx, sub, [token]).await;
+    }
+
+    // add tokens to the watched set while the session and global limits allow, return added tokens
+    async fn add_discovered_tokens(
+        ctx: &WatcherContext,
+        sub: &Subscription,
+        candidates: impl IntoIterator<Item = Address>,
+    ) -> Vec<Address> {
+        let key = SubscriptionKey {
+            owner: ctx.owner,
+            network: ctx.network,
+        };
+        let added = ctx
+            .sub_manager
+            .add_discovered_tokens(
+                key,
+                sub,
+                candidates,
+                ctx.max_watched_tokens_limit,
+                &wrapped_token_set(&ctx.wrapped_tokens, ctx.network),
+            )
+            .await;
+
+        if !added.is_empty() {
+            counter!("tokens_discovered_total").increment(added.len() as u64);
+            tracing::info!(
+                owner = %ctx.owner,
+                network = %ctx.network,
+                added = ?added,
+                "tokens discovered"
+            );
+            // the leader state keeps balances only, discovered tokens are a change of the session
+            ctx.sub_manager.share_session(key).await;
+        }
+
+        added
     }
 
     // watcher to request balances via multicall every interval_secs to have an actual state
     // it update the whole state of balances and then send event to clients
+    // tokens, nft collections and erc1155 tokens added to the session in the meantime are fetched immediately
+    // resync request (admin API) triggers the full update out of schedule
+    // restored session starts with the gap backfill instead of the first full update
     // could be removed if we check more ws subscriptions for updates
-    async fn spawn_snapshot_updater(&self, interval_secs: usize) {
+    async fn spawn_snapshot_updater(&self, interval_secs: usize, resume_from_block: Option<u64>) {
+        let sub = Arc::clone(&self.sub);
+        let ctx = Arc::clone(&self.ctx);
+        let cancel = self.cancel.clone();
+
+        let balance_call_ctx = Arc::new(BalanceCallCtx {
+            owner: ctx.owner,
+            multicall3: ctx.multicall3,
+            provider: Arc::new(ctx.provider.clone()),
+            network: ctx.network,
+        });
+
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(interval_secs as u64));
+
+            if let Some(from_block) = resume_from_block {
+                if Self::backfill_gap(&ctx, &balance_call_ctx, &sub, from_block).await {
+                    // first tick completes immediately, the snapshot is actual already
+                    interval.tick().await;
+                }
+            }
+
+            loop {
+                let full_update = tokio::select! {
+                    _ = cancel.cancelled() => { break; }
+                    _ = interval.tick() => true,
+                    _ = sub.resync.notified() => {
+                        counter!("snapshot_resync_requests_total").increment(1);
+                        interval.reset();
+                        true
+                    }
+                    _ = sub.tokens_added.notified() => false,
+                };
+
+                if full_update {
+                    counter!("snapshot_updater_runs_total").increment(1);
+                    // full update covers tokens added before it
+                    sub.take_added_tokens().await;
+                    sub.take_added_nft_collections().await;
+                    sub.take_added_erc1155_tokens().await;
+                    // watched tokens could be changed by session updates, take the actual set
+                    let tokens: Vec<Address> = sub.tokens.read().await.iter().copied().collect();
+                    Self::fetch_balances_and_broadcast(
+                        Arc::clone(&balance_call_ctx),
+                        &tokens,
+                        Arc::clone(&sub),
+                    )
+                    .await;
+                    Self::update_valuation(&ctx, &sub).await;
+                    let collections: Vec<Address> =
+                        sub.nft_collections.read().await.iter().copied().collect();
+                    Self::update_nft_holdings(&balance_call_ctx, &sub, &collections).await;
+                    let erc1155_tokens: Vec<Erc1155Token> =
+                        sub.erc1155_tokens.read().await.iter().copied().collect();
+                    Self::update_erc1155_balances(
+                        &balance_call_ctx,
+                        &sub,
+                        &erc1155_tokens,
+                        BlockId::latest(),
+                    )
+                    .await;
+                    continue;
+                }
+
+                // targeted fetch for tokens added to the session, so clients see them right away
+                let added = sub.take_added_tokens().await;
+                if !added.is_empty() {
+                    counter!("added_tokens_fetch_total").increment(1);
+                    Self::fetch_balances_and_broadcast(
+                        Arc::clone(&balance_call_ctx),
+                        &added,
+                        Arc::clone(&sub),
+                    )
+                    .await;
+                }
+
+                let added_collections = sub.take_added_nft_collections().await;
+                if !added_collections.is_empty() {
+                    counter!("added_nft_collections_fetch_total").increment(1);
+                    Self::update_nft_holdings(&balance_call_ctx, &sub, &added_collections).await;
+                }
+
+                let added_erc1155_tokens = sub.take_added_erc1155_tokens().await;
+                if !added_erc1155_tokens.is_empty() {
+                    counter!("added_erc1155_tokens_fetch_total").increment(1);
+                    Self::update_erc1155_balances(
+                        &balance_call_ctx,
+                        &sub,
+                        &added_erc1155_tokens,
+                        BlockId::latest(),
+                    )
+                    .await;
+                }
+            }
+        });
+    }
+
+    // balanceOf of the collections, token IDs are enumerated for collections whose balance
+    // differs from the known IDs (new ones included), changes are sent as nft_update
+    async fn update_nft_holdings(
+        balance_call_ctx: &Arc<BalanceCallCtx>,
+        sub: &Subscription,
+        collections: &[Address],
+    ) {
+        if collections.is_empty() {
+            return;
+        }
+
+        // balanceOf has the same selector in ERC-20 and ERC-721
+        let (balances, block_number) =
+            match fetch_balances_via_multicall::fetch_erc20_balances_allow_failure(
+                Arc::clone(balance_call_ctx),
+                collections,
+                BlockId::latest(),
+            )
+            .await
+            {
+                Ok(balances) => balances,
+                Err(err) => {
+                    tracing::warn!(
+                        error = %err,
+                        owner = %balance_call_ctx.owner,
+                        network = %balance_call_ctx.network,
+                        "unable to get nft balances"
+                    );
+                    return;
+                }
+            };
+
+        let current = sub.nft_holdings();
+        let to_enumerate: Vec<(Address, u64)> = balances
+            .iter()
+            .filter(|(collection, balance)| {
+                !balance.is_zero()
+                    && current
+                        .get(*collection)
+                        .is_none_or(|holding| U256::from(holding.token_ids.len()) != **balance)
+            })
+            .map(|(collection, balance)| {
+                (
+                    *collection,
+                    balance
+                        .saturating_to::<u64>()
+                        .min(MAX_ENUMERATED_NFTS_PER_COLLECTION),
+                )
+            })
+            .collect();
+
+        // multicall is pinned to the block of balances, so IDs match them
+        let mut enumerated = if to_enumerate.is_empty() {
+            HashMap::new()
+        } else {
+            fetch_balances_via_multicall::fetch_nft_token_ids(
+                Arc::clone(balance_call_ctx),
+                &to_enumerate,
+                BlockId::number(block_number.saturating_to::<u64>()),
+            )
+            .await
+            .unwrap_or_else(|err| {
+                tracing::warn!(
+                    error = %err,
+                    owner = %balance_call_ctx.owner,
+                    network = %balance_call_ctx.network,
+                    "unable to enumerate nft collections"
+                );
+                HashMap::new()
+            })
+        };
+
+        // IDs of collections which can't be enumerated are kept from transfers
+        let holdings = balances
+            .into_iter()
+            .map(|(collection, balance)| {
+                let token_ids = if balance.is_zero() {
+                    BTreeSet::new()
+                } else if let Some(token_ids) = enumerated.remove(&collection) {
+                    token_ids
+                } else {
+                    current
+                        .get(&collection)
+                        .map(|holding| holding.token_ids.clone())
+                        .unwrap_or_default()
+                };
+
+                let holding = NftHolding {
+                    balance,
+                    token_ids,
+                    block_number,
+                };
+                (collection, holding)
+            })
+            .collect();
+
+        Self::publish_nft_holdings(sub, holdings, block_number).await;
+    }
+
+    // store holdings of watched collections and send their changes
+    async fn publish_nft_holdings(
+        sub: &Subscription,
+        mut holdings: HashMap<Address, NftHolding>,
+        block_number: U256,
+    ) {
+        {
+            let collections = sub.nft_collections.read().await;
+            holdings.retain(|collection, _| collections.contains(collection));
+        }
+
+        let collections = sub.update_nft_holdings(holdings);
+        if collections.is_empty() {
+            return;
+        }
+
+        let update = NftUpdate {
+            collections,
+            block_number: block_number.to_string(),
+        };
+        if sub.publish(BalanceEvent::NftUpdate(update)) {
+            counter!("nft_updates_sent_total").increment(1);
+        }
+    }
+
+    // balanceOf(owner, id) of the pairs at the block, changes are sent as erc1155_update
+    async fn update_erc1155_balances(
+        balance_call_ctx: &Arc<BalanceCallCtx>,
+        sub: &Subscription,
+        tokens: &[Erc1155Token],
+        block_id: BlockId,
+    ) {
+        if tokens.is_empty() {
+            return;
+        }
+
+        let (mut balances, block_number) =
+            match fetch_balances_via_multicall::fetch_erc1155_balances(
+                Arc::clone(balance_call_ctx),
+                tokens,
+                block_id,
+            )
+            .await
+            {
+                Ok(balances) => balances,
+                Err(err) => {
+                    tracing::warn!(
+                        error = %err,
+                        owner = %balance_call_ctx.owner,
+                        network = %balance_call_ctx.network,
+                        "unable to get erc1155 balances"
+                    );
+                    return;
+                }
+            };
+
+        // pairs could be removed by a cluster sync in the meantime
+        {
+            let erc1155_tokens = sub.erc1155_tokens.read().await;
+            balances.retain(|token, _| erc1155_tokens.contains(token));
+        }
+
+        let diff = sub.update_erc1155_balances(balances, block_number);
+        if !diff.is_empty()
+            && sub.publish(BalanceEvent::Erc1155Update(Erc1155Update::new(
+                &diff,
+                block_number,
+            )))
+        {
+            counter!("erc1155_updates_sent_total").increment(1);
+        }
+    }
+
+    // find tokens touched by the owner's logs between the restored snapshot and the head
+    // and fetch only them (native balance is always fetched)
+    // false - the gap is too large or logs are not available, the full update is needed
+    async fn backfill_gap(
+        ctx: &WatcherContext,
+        balance_call_ctx: &Arc<BalanceCallCtx>,
+        sub: &Arc<Subscription>,
+        from_block: u64,
+    ) -> bool {
+        let latest = match ctx.provider.get_block_number().await {
+            Ok(block_number) => block_number,
+            Err(err) => {
+                counter!("backfill_failed_total").increment(1);
+                tracing::warn!(
+                    error = %err,
+                    owner = %ctx.owner,
+                    network = %ctx.network,
+                    "unable to get block number for backfill"
+                );
+                return false;
+            }
+        };
+
+        if latest.saturating_sub(from_block) > ctx.max_backfill_blocks {
+            tracing::info!(
+                owner = %ctx.owner,
+                network = %ctx.network,
+                from_block,
+                latest,
+                "gap is too large for backfill, take the full snapshot"
+            );
+            return false;
+        }
+
+        let owner = Topic::from(ctx.owner);
+        let filters = [
+            Filter::new()
+                .event_signature(ERC20::Transfer::SIGNATURE_HASH)
+                .topic1(owner.clone()),
+            Filter::new()
+                .event_signature(ERC20::Transfer::SIGNATURE_HASH)
+                .topic2(owner.clone()),
+            Filter::new()
+                .address(ctx.wrapped_tokens.keys().copied().collect::<Vec<_>>())
+                .event_signature(vec![
+                    WrappedToken::Deposit::SIGNATURE_HASH,
+                    WrappedToken::Withdrawal::SIGNATURE_HASH,
+                ])
+                .topic1(owner),
+        ];
+
+        let mut touched: HashSet<Address> = HashSet::new();
+        let mut start = from_block + 1;
+        while start <= latest {
+            let end = start.saturating_add(BACKFILL_PAGE_SIZE - 1).min(latest);
+
+            for filter in &filters {
+                let filter = filter.clone().from_block(start).to_block(end);
+                match ctx.provider.get_logs(&filter).await {
+                    Ok(logs) => touched.extend(logs.iter().map(|log| log.address())),
+                    Err(err) => {
+                        counter!("backfill_failed_total").increment(1);
+                        tracing::warn!(
+                            error = %err,
+                            owner = %ctx.owner,
+                            network = %ctx.network,
+                            from = start,
+                            to = end,
+                            "unable to get logs for backfill"
+                        );
+                        return false;
+                    }
+                }
+            }
+
+            start = end + 1;
+        }
+
+        let tokens: Vec<Address> = {
+            let watched_tokens = sub.tokens.read().await;
+            touched
+                .into_iter()
+                .filter(|token| watched_tokens.contains(token))
+                .collect()
+        };
+
+        counter!("backfill_runs_total").increment(1);
+        tracing::info!(
+            owner = %ctx.owner,
+            network = %ctx.network,
+            from_block,
+            latest,
+            tokens_len = tokens.len(),
+            "backfill the gap of the restored session"
+        );
+
+        Self::fetch_balances_and_broadcast(Arc::clone(balance_call_ctx), &tokens, Arc::clone(sub))
+            .await;
+        true
+    }
+
+    // price the snapshot at its latest block after the full update
+    // price_update is published when the total value changed by more than the threshold
+    async fn update_valuation(ctx: &WatcherContext, sub: &Subscription) {
+        let Some(oracle) = &ctx.price_oracle else {
+            return;
+        };
+        if !oracle.has_feeds(ctx.network) {
+            return;
+        }
+
+        let (balances, block_number) = {
+            let snapshot = sub.balances_snapshot.read().await;
+            let block_number = snapshot
+                .values()
+                .map(|balance| balance.block_number)
+                .max()
+                .unwrap_or_default();
+            let balances: HashMap<Address, U256> = snapshot
+                .iter()
+                .map(|(token, balance)| (*token, balance.amount))
+                .collect();
+            (balances, block_number)
+        };
+
+        if balances.is_empty() {
+            return;
+        }
+
+        let prices = match oracle
+            .prices(ctx.network, &ctx.provider, ctx.multicall3, block_number)
+            .await
+        {
+            Ok(prices) => prices,
+            Err(err) => {
+                tracing::warn!(
+                    error = %err,
+                    owner = %ctx.owner,
+                    network = %ctx.network,
+                    "unable to get prices for valuation"
+                );
+                return;
+            }
+        };
+
+        let valuation = oracle.valuation(ctx.network, &balances, &prices, block_number);
+        if sub.update_valuation(valuation.clone(), oracle.update_threshold_bps())
+            && sub.publish(BalanceEvent::PriceUpdate(valuation))
+        {
+            counter!("price_updates_sent_total").increment(1);
+        }
+    }
+
+    // the tracker is idle while the session finality is `latest`, the level can be changed at any time
+    fn spawn_finality_tracker(&self) {
+        let ctx = Arc::clone(&self.ctx);
         let sub = Arc::clone(&self.sub);
+        let cancel = self.cancel.clone();
+
+        let balance_call_ctx = Arc::new(BalanceCallCtx {
+            owner: ctx.owner,
+            network: ctx.network,
+            provider: Arc::new(ctx.provider.clone()),
+            multicall3: ctx.multicall3,
+        });
+
+        tokio::spawn(async move {
+            let mut interval = interval(ctx.finality_poll_interval);
+
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => { break; }
+                    _ = interval.tick() => {}
+                    _ = sub.finality_changed.notified() => {}
+                }
+
+                Self::update_confirmed_balances(&ctx, &balance_call_ctx, &sub).await;
+            }
+        });
+    }
+
+    // fetch balances at the block of the finality level when it advances
+    // or the watched set has tokens without a confirmed balance
+    async fn update_confirmed_balances(
+        ctx: &WatcherContext,
+        balance_call_ctx: &Arc<BalanceCallCtx>,
+        sub: &Subscription,
+    ) {
+        let finality = sub.finality();
+        if finality.is_latest() {
+            return;
+        }
+
+        let block_number = match Self::finality_block(ctx, finality).await {
+            Ok(Some(block_number)) => block_number,
+            Ok(None) => return,
+            Err(err) => {
+                counter!("finality_block_failed_total").increment(1);
+                tracing::warn!(error = %err, owner = %ctx.owner, "unable to resolve finality block");
+                return;
+            }
+        };
+
+        let tokens: Vec<Address> = sub.tokens.read().await.iter().copied().collect();
+        let up_to_date = {
+            let confirmed_snapshot = sub.confirmed_snapshot.read().await;
+            let confirmed_block = confirmed_snapshot
+                .values()
+                .map(|balance| balance.block_number)
+                .max();
+
+            confirmed_block.is_some_and(|block| block >= U256::from(block_number))
+                && tokens
+                    .iter()
+                    .all(|token| confirmed_snapshot.contains_key(token))
+        };
+        if up_to_date {
+            return;
+        }
+
+        counter!("confirmed_snapshot_updates_total").increment(1);
+        let (mut balances, fetched_block) = match Self::get_tokens_balance(
+            Arc::clone(balance_call_ctx),
+            &tokens,
+            BlockId::number(block_number),
+        )
+        .await
+        {
+            Ok(balances) => balances,
+            Err(err) => {
+                tracing::warn!(error = %err, "unable to get confirmed balances");
+                return;
+            }
+        };
+
+        // the level could be changed during the request
+        if sub.finality() != finality {
+            return;
+        }
+
+        let diff = {
+            let tokens = sub.tokens.read().await;
+            let native_address = ctx.network.native_token_address();
+            balances.retain(|token, _| *token == native_address || tokens.contains(token));
+
+            let confirmed_snapshot = sub.confirmed_snapshot.write().await;
+            let (diff, _) = Self::update_balances_and_take_diff(
+                confirmed_snapshot,
+                &mut [],
+                (balances, fetched_block),
+            );
+            diff
+        };
+
+        if !diff.is_empty()
+            && sub.publish(BalanceEvent::ConfirmedUpdate {
+                finality,
+                balances: diff,
+                block_number: fetched_block.to_string(),
+            })
+        {
+            counter!("confirmed_updates_sent_total").increment(1);
+        }
+    }
+
+    // block of the finality level, None while the chain is shorter than the confirmations
+    async fn finality_block(
+        ctx: &WatcherContext,
+        finality: Finality,
+    ) -> Result<Option<u64>, WatcherError> {
+        let map_err = |err: alloy::transports::TransportError| {
+            WatcherError::FinalityBlock(ctx.network, err.to_string())
+        };
+
+        let tag = match finality.effective(ctx.network) {
+            Finality::Latest => return Ok(None),
+            Finality::Confirmations(confirmations) => {
+                let head = ctx.provider.get_block_number().await.map_err(map_err)?;
+                return Ok(head.checked_sub(confirmations));
+            }
+            Finality::Safe => BlockNumberOrTag::Safe,
+            Finality::Finalized => BlockNumberOrTag::Finalized,
+        };
+
+        let block = ctx
+            .provider
+            .get_block_by_number(tag)
+            .await
+            .map_err(map_err)?;
+
+        Ok(block.map(|block| block.header.number))
+    }
+
+    // internal calls moving native value don't emit logs, the trace dispatcher
+    // reports blocks with such transfers of the owner and only native balance is refreshed
+    fn spawn_trace_listener(&self) {
+        let Some(dispatcher) = self.ctx.trace_dispatcher.as_ref() else {
+            return;
+        };
+        let mut registration = dispatcher.register(self.ctx.owner);
+        let sub = Arc::clone(&self.sub);
+        let cancel = self.cancel.clone();
+        let balance_call_ctx = Arc::new(BalanceCallCtx {
+            owner: self.ctx.owner,
+            network: self.ctx.network,
+            provider: Arc::new(self.ctx.provider.clone()),
+            multicall3: self.ctx.multicall3,
+        });
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => { break; }
+                    block_number = registration.recv() => {
+                        let Some(block_number) = block_number else { break; };
+                        counter!("native_trace_refreshes_total").increment(1);
+                        Self::refresh_balances_at(&balance_call_ctx, &sub, &[], block_number).await;
+                    }
+                }
+            }
+        });
+    }
+
+    // the tracker is registered in the mempool dispatcher only while projections are enabled
+    // tracked transactions are resolved by receipts every check interval
+    fn spawn_pending_tracker(&self) {
+        let Some(dispatcher) = self.ctx.mempool_dispatcher.clone() else {
+            return;
+        };
         let ctx = Arc::clone(&self.ctx);
-        let cancel = sub.cancel_token.clone();
+        let sub = Arc::clone(&self.sub);
+        let cancel = self.cancel.clone();
+
+        tokio::spawn(async move {
+            let mut registration: Option<MempoolRegistration> = None;
+            let mut tracked: HashMap<B256, TrackedTx> = HashMap::new();
+            let mut interval = interval(ctx.pending_check_interval);
+
+            loop {
+                let enabled = sub.pending_enabled.load(Ordering::SeqCst);
+                if enabled && registration.is_none() {
+                    registration = Some(dispatcher.register(ctx.owner));
+                } else if !enabled && registration.is_some() {
+                    registration = None;
+                    gauge!("pending_txs_tracked").decrement(tracked.len() as f64);
+                    tracked.clear();
+                }
 
-        let (balance_call_ctx, tokens) = {
+                tokio::select! {
+                    _ = cancel.cancelled() => { break; }
+                    _ = sub.pending_changed.notified() => {}
+                    _ = interval.tick() => {
+                        Self::check_pending_txs(&ctx, &sub, &mut tracked).await;
+                    }
+                    Some(tx) = Self::recv_pending_tx(&mut registration) => {
+                        Self::track_pending_tx(&ctx, &sub, &mut tracked, tx).await;
+                    }
+                }
+            }
+
+            gauge!("pending_txs_tracked").decrement(tracked.len() as f64);
+        });
+    }
+
+    async fn recv_pending_tx(registration: &mut Option<MempoolRegistration>) -> Option<PendingTx> {
+        match registration {
+            Some(registration) => registration.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    // a transaction with the nonce of a tracked one replaces it (speed up / cancel),
+    // the replaced one is reported as dropped
+    async fn track_pending_tx(
+        ctx: &WatcherContext,
+        sub: &Subscription,
+        tracked: &mut HashMap<B256, TrackedTx>,
+        mut tx: PendingTx,
+    ) {
+        if tracked.contains_key(&tx.hash) {
+            return;
+        }
+
+        // only balances of the session are projected
+        {
             let tokens = sub.tokens.read().await;
-            let tokens: Vec<Address> = tokens.iter().copied().collect();
+            let native_address = ctx.network.native_token_address();
+            tx.deltas
+                .retain(|delta| delta.token == native_address || tokens.contains(&delta.token));
+        }
+
+        let replaced: Vec<B256> = tracked
+            .values()
+            .filter(|tracked_tx| tracked_tx.tx.from == tx.from && tracked_tx.tx.nonce == tx.nonce)
+            .map(|tracked_tx| tracked_tx.tx.hash)
+            .collect();
+        for hash in replaced {
+            Self::resolve_pending_tx(ctx, sub, tracked, hash, PendingStatus::Dropped).await;
+        }
+
+        if tx.deltas.is_empty() {
+            return;
+        }
+
+        if tracked.len() >= MAX_PENDING_TXS_PER_SESSION {
+            counter!("pending_txs_skipped_total").increment(1);
+            tracing::warn!(
+                owner = %ctx.owner,
+                network = %ctx.network,
+                tx_hash = %tx.hash,
+                "limit of tracked pending transactions is reached"
+            );
+            return;
+        }
+
+        tracked.insert(
+            tx.hash,
+            TrackedTx {
+                tx: tx.clone(),
+                seen_at: Instant::now(),
+            },
+        );
+        gauge!("pending_txs_tracked").increment(1);
+
+        Self::publish_pending_balance(sub, tracked, &tx, PendingStatus::Pending).await;
+    }
+
+    // mined transactions are confirmed or reverted, transactions without a receipt
+    // after the timeout are dropped, balances of mined ones are updated from logs
+    async fn check_pending_txs(
+        ctx: &WatcherContext,
+        sub: &Subscription,
+        tracked: &mut HashMap<B256, TrackedTx>,
+    ) {
+        let hashes: Vec<B256> = tracked.keys().copied().collect();
+
+        for hash in hashes {
+            let status = match ctx.provider.get_transaction_receipt(hash).await {
+                Ok(Some(receipt)) if receipt.status() => PendingStatus::Confirmed,
+                Ok(Some(_)) => PendingStatus::Reverted,
+                Ok(None) => {
+                    let expired = tracked.get(&hash).is_some_and(|tracked_tx| {
+                        tracked_tx.seen_at.elapsed() > ctx.pending_tx_timeout
+                    });
+                    if !expired {
+                        continue;
+                    }
+                    PendingStatus::Dropped
+                }
+                Err(err) => {
+                    counter!("pending_receipt_failed_total").increment(1);
+                    tracing::warn!(
+                        error = %err,
+                        owner = %ctx.owner,
+                        network = %ctx.network,
+                        tx_hash = %hash,
+                        "unable to get receipt of pending transaction"
+                    );
+                    continue;
+                }
+            };
+
+            Self::resolve_pending_tx(ctx, sub, tracked, hash, status).await;
+        }
+    }
+
+    async fn resolve_pending_tx(
+        ctx: &WatcherContext,
+        sub: &Subscription,
+        tracked: &mut HashMap<B256, TrackedTx>,
+        hash: B256,
+        status: PendingStatus,
+    ) {
+        let Some(resolved) = tracked.remove(&hash) else {
+            return;
+        };
+        gauge!("pending_txs_tracked").decrement(1);
+
+        tracing::debug!(
+            owner = %ctx.owner,
+            network = %ctx.network,
+            tx_hash = %hash,
+            status = status.as_str(),
+            "pending transaction is resolved"
+        );
+
+        Self::publish_pending_balance(sub, tracked, &resolved.tx, status).await;
+    }
+
+    // balances of tokens touched by the transaction: the latest snapshot
+    // with deltas of all tracked transactions, balances without a snapshot are skipped
+    async fn publish_pending_balance(
+        sub: &Subscription,
+        tracked: &HashMap<B256, TrackedTx>,
+        tx: &PendingTx,
+        status: PendingStatus,
+    ) {
+        let balances: HashMap<Address, String> = {
+            let snapshot = sub.balances_snapshot.read().await;
+            tx.deltas
+                .iter()
+                .filter_map(|delta| {
+                    let balance = snapshot.get(&delta.token)?.amount;
+                    let projected = tracked
+                        .values()
+                        .flat_map(|tracked_tx| tracked_tx.tx.deltas.iter())
+                        .filter(|tracked_delta| tracked_delta.token == delta.token)
+                        .fold(balance, |balance, tracked_delta| {
+                            if tracked_delta.incoming {
+                                balance.saturating_add(tracked_delta.amount)
+                            } else {
+                                balance.saturating_sub(tracked_delta.amount)
+                            }
+                        });
+                    Some((delta.token, projected.to_string()))
+                })
+                .collect()
+        };
+
+        let event = PendingBalanceEvent {
+            tx_hash: tx.hash,
+            status,
+            balances,
+        };
+        if sub.publish(BalanceEvent::PendingBalance(event)) {
+            counter!("pending_balance_events_sent_total", "status" => status.as_str()).increment(1);
+        }
+    }
+
+    // refresh tokens of the balance strategies registry:
+    // on the network block monitor triggers (every block, rebase events) and on their intervals,
+    // shares of share-based tokens are updated when their balances change
+    fn spawn_strategy_tracker(&self) {
+        let Some(registry) = self.ctx.balance_strategies.clone() else {
+            return;
+        };
+        let Some(strategies) = registry.strategies(self.ctx.network).cloned() else {
+            return;
+        };
+        let ctx = Arc::clone(&self.ctx);
+        let sub = Arc::clone(&self.sub);
+        let cancel = self.cancel.clone();
 
-            let balance_call_ctx = BalanceCallCtx {
-                owner: ctx.owner,
-                multicall3: ctx.multicall3,
-                provider: Arc::new(ctx.provider.clone()),
-                network: ctx.network,
-            };
+        let balance_call_ctx = Arc::new(BalanceCallCtx {
+            owner: ctx.owner,
+            network: ctx.network,
+            provider: Arc::new(ctx.provider.clone()),
+            multicall3: ctx.multicall3,
+        });
 
-            (Arc::new(balance_call_ctx), tokens)
-        };
+        let mut triggers = registry.subscribe(ctx.network, &ctx.provider);
+        let mut events = sub.sender.subscribe();
+        let share_tokens: Vec<Address> = strategies
+            .values()
+            .filter(|strategy| strategy.shares.is_some())
+            .map(|strategy| strategy.token)
+            .collect();
+
+        let now = tokio::time::Instant::now();
+        let intervals: HashMap<Address, Duration> = strategies
+            .values()
+            .filter_map(|strategy| match strategy.refresh {
+                Some(RefreshStrategy::IntervalSecs(secs)) => {
+                    Some((strategy.token, Duration::from_secs(secs)))
+                }
+                _ => None,
+            })
+            .collect();
+        let mut next_refresh: HashMap<Address, tokio::time::Instant> = intervals
+            .iter()
+            .map(|(token, period)| (*token, now + *period))
+            .collect();
 
         tokio::spawn(async move {
-            let mut interval = interval(Duration::from_secs(interval_secs as u64));
+            // restored snapshot has balances without shares
+            Self::update_share_balances(&registry, &balance_call_ctx, &sub, &share_tokens).await;
 
             loop {
-                tokio::select! {
+                let next_due = next_refresh.values().min().copied();
+
+                let tokens: Vec<Address> = tokio::select! {
                     _ = cancel.cancelled() => { break; }
-                    _ = interval.tick() => {
-                        counter!("snapshot_updater_runs_total").increment(1);
-                        Self::fetch_balances_and_broadcast(Arc::clone(&balance_call_ctx), &tokens, Arc::clone(&sub)).await;
+                    trigger = Self::recv_refresh_trigger(&mut triggers) => match trigger {
+                        Ok(trigger) => {
+                            tracing::debug!(
+                                owner = %ctx.owner,
+                                block = trigger.block_number,
+                                tokens = trigger.tokens.len(),
+                                "balance strategies triggered"
+                            );
+                            trigger.tokens
+                        }
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => {
+                            triggers = None;
+                            continue;
+                        }
+                    },
+                    _ = Self::sleep_until_due(next_due) => {
+                        let now = tokio::time::Instant::now();
+                        let due: Vec<Address> = next_refresh
+                            .iter()
+                            .filter(|(_, at)| **at <= now)
+                            .map(|(token, _)| *token)
+                            .collect();
+                        for token in &due {
+                            if let Some(period) = intervals.get(token) {
+                                next_refresh.insert(*token, now + *period);
+                            }
+                        }
+                        due
+                    }
+                    event = events.recv(), if !share_tokens.is_empty() => {
+                        let changed: Vec<Address> = match event {
+                            Ok(BalanceEvent::BalanceUpdate(balances)) => share_tokens
+                                .iter()
+                                .filter(|token| balances.contains_key(*token))
+                                .copied()
+                                .collect(),
+                            // missed updates, take shares of all tokens
+                            Err(broadcast::error::RecvError::Lagged(_)) => share_tokens.clone(),
+                            Err(broadcast::error::RecvError::Closed) => break,
+                            Ok(_) => continue,
+                        };
+                        Self::update_share_balances(&registry, &balance_call_ctx, &sub, &changed).await;
+                        continue;
                     }
+                };
+
+                let tokens: Vec<Address> = {
+                    let watched_tokens = sub.tokens.read().await;
+                    tokens
+                        .into_iter()
+                        .filter(|token| watched_tokens.contains(token))
+                        .collect()
+                };
+                if tokens.is_empty() {
+                    continue;
                 }
+
+                counter!("strategy_refreshes_total").increment(1);
+                Self::fetch_balances_and_broadcast(
+                    Arc::clone(&balance_call_ctx),
+                    &tokens,
+                    Arc::clone(&sub),
+                )
+                .await;
             }
         });
     }
 
+    async fn recv_refresh_trigger(
+        triggers: &mut Option<broadcast::Receiver<RefreshTrigger>>,
+    ) -> Result<RefreshTrigger, broadcast::error::RecvError> {
+        match triggers {
+            Some(triggers) => triggers.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    async fn sleep_until_due(due: Option<tokio::time::Instant>) {
+        match due {
+            Some(due) => tokio::time::sleep_until(due).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    // shares of the tokens at the block of their latest balances, changes are sent as shares_update
+    async fn update_share_balances(
+        registry: &BalanceStrategies,
+        balance_call_ctx: &BalanceCallCtx,
+        sub: &Subscription,
+        tokens: &[Address],
+    ) {
+        if tokens.is_empty() {
+            return;
+        }
+
+        let (balances, block_number) = {
+            let snapshot = sub.balances_snapshot.read().await;
+            let balances: HashMap<Address, U256> = tokens
+                .iter()
+                .filter_map(|token| Some((*token, snapshot.get(token)?.amount)))
+                .collect();
+            let block_number = tokens
+                .iter()
+                .filter_map(|token| snapshot.get(token).map(|balance| balance.block_number))
+                .max()
+                .unwrap_or_default();
+            (balances, block_number)
+        };
+        if balances.is_empty() {
+            return;
+        }
+
+        let share_balances = match registry
+            .share_balances(balance_call_ctx, &balances, block_number)
+            .await
+        {
+            Ok(share_balances) => share_balances,
+            Err(err) => {
+                tracing::warn!(
+                    error = %err,
+                    owner = %balance_call_ctx.owner,
+                    network = %balance_call_ctx.network,
+                    "unable to get share balances"
+                );
+                return;
+            }
+        };
+
+        let changed = sub.update_share_balances(share_balances);
+        if !changed.is_empty() && sub.publish(BalanceEvent::SharesUpdate(changed)) {
+            counter!("shares_updates_sent_total").increment(1);
+        }
+    }
+
     // request all balances for a list of watched tokens via multicall and broadcast them to clients
     async fn fetch_balances_and_broadcast(
         ctx: Arc<BalanceCallCtx>,
@@ -122,14 +1372,13 @@ impl Watcher {
         sub: Arc<Subscription>,
     ) {
         let owner = ctx.owner;
+        let network = ctx.network;
         let result = Self::get_tokens_balance(ctx, tokens, BlockId::latest()).await;
 
         let event = match result {
             Ok(balances) => {
-                let diff = {
-                    let balance_snapshot = sub.balances_snapshot.write().await;
-                    Self::update_balances_and_take_diff(balance_snapshot, balances)
-                };
+                let diff =
+                    Self::update_watched_balances_and_take_diff(&sub, network, balances).await;
 
                 if !diff.is_empty() {
                     Some(BalanceEvent::BalanceUpdate(diff))
@@ -147,9 +1396,9 @@ impl Watcher {
         };
 
         if let Some(event) = event {
-            let _ = sub.sender.send(event).inspect(|_| {
+            if sub.publish(event) {
                 counter!("balance_updates_sent_total").increment(1);
-            });
+            }
         }
     }
 
@@ -169,135 +1418,396 @@ impl Watcher {
             })
     }
 
-    /**
-     * Listen Deposit/Withdrawal events
-     *
-     * Need to sync wrap/unwrap txs to handle wrapped token balance
-     */
-    async fn spawn_weth9_events_listener(&self) {
+    // receive logs of the owner from the network log dispatcher
+    // Transfer (in/out) - get balance for token(+ eth balance) and send it to clients
+    // ERC-721 Transfer (tokenId in topic3) of watched collections - update token IDs of the owner
+    // ERC-1155 TransferSingle/TransferBatch - get balances of the transferred watched (contract, id) pairs
+    // Safe ExecutionSuccess/SafeReceived, EntryPoint UserOperationEvent - native and tokens touched by the tx,
+    // native movements of contract wallets are internal calls without Transfer logs
+    // CoW settlement Trade / EthFlow OrderPlacement (COW_NETWORKS) - sell and buy tokens plus native in one multicall
+    // Deposit/Withdrawal of wrapped tokens - need to sync wrap/unwrap txs to handle both sides of the wrap
+    fn spawn_log_listener(&self) {
         let ctx = Arc::clone(&self.ctx);
-        let weth9_address = ctx.weth9_address;
+        let sub = Arc::clone(&self.sub);
+        let cancel = self.cancel.clone();
+        let mut registration = ctx.log_dispatcher.register(ctx.owner);
+
+        let balance_call_ctx = Arc::new(BalanceCallCtx {
+            owner: ctx.owner,
+            network: ctx.network,
+            provider: Arc::new(ctx.provider.clone()),
+            multicall3: ctx.multicall3,
+        });
 
-        let event_signatures = vec![
-            WrappedToken::Deposit::SIGNATURE_HASH,
-            WrappedToken::Withdrawal::SIGNATURE_HASH,
-        ];
-        let filter = Filter::new()
-            .address(weth9_address)
-            .event_signature(event_signatures)
-            .topic1(Topic::from(ctx.owner));
-
-        let sub: Arc<Subscription> = Arc::clone(&self.sub);
-        let cancel = sub.cancel_token.clone();
-
-        let balance_call_ctx = {
-            let ctx = BalanceCallCtx {
-                owner: ctx.owner,
-                network: ctx.network,
-                provider: Arc::new(self.ctx.provider.clone()),
-                multicall3: ctx.multicall3,
+        tokio::spawn(async move {
+            Self::detect_contract_wallet(&ctx, &sub).await;
+            // a wallet tx could emit several wallet events, its balances are fetched once
+            let mut last_wallet_tx: Option<B256> = None;
+
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => {
+                        tracing::info!("cancelled log listener");
+                        break;
+                    }
+                    event = registration.recv() => {
+                        let log = match event {
+                            None => break,
+                            Some(SourceEvent::Log(log)) => *log,
+                            Some(SourceEvent::Degraded) => {
+                                sub.publish(BalanceEvent::Degraded {
+                                    message: format!("log source of network {} is reconnecting, balance updates are delayed", ctx.network),
+                                });
+                                continue;
+                            }
+                            Some(SourceEvent::Recovered) => {
+                                // events of the outage are missed, take the full snapshot
+                                sub.resync.notify_one();
+                                sub.publish(BalanceEvent::Recovered {
+                                    message: format!("log source of network {} is recovered", ctx.network),
+                                });
+                                continue;
+                            }
+                            Some(SourceEvent::Resubscribed) => {
+                                // clients are not told about a short gap, only the snapshot is refreshed
+                                sub.resync.notify_one();
+                                continue;
+                            }
+                        };
+
+                        let is_transfer = log
+                            .topic0()
+                            .is_some_and(|topic0| *topic0 == ERC20::Transfer::SIGNATURE_HASH);
+                        let is_erc1155_transfer = log.topic0().is_some_and(|topic0| {
+                            *topic0 == ERC1155::TransferSingle::SIGNATURE_HASH
+                                || *topic0 == ERC1155::TransferBatch::SIGNATURE_HASH
+                        });
+
+                        // ERC-721 Transfer has the same signature with the indexed tokenId
+                        if is_transfer && log.topics().len() == 4 {
+                            if sub.nft_collections.read().await.contains(&log.address()) {
+                                Self::handle_nft_transfer_log(&balance_call_ctx, &sub, log).await;
+                            }
+                        } else if Self::is_wallet_log(&log) {
+                            if log.transaction_hash.is_none() || log.transaction_hash != last_wallet_tx {
+                                last_wallet_tx = log.transaction_hash;
+                                Self::handle_wallet_log(&ctx, &balance_call_ctx, &sub, log).await;
+                            }
+                        } else if Self::is_cow_log(&log) {
+                            Self::handle_cow_log(&balance_call_ctx, &sub, log).await;
+                        } else if is_erc1155_transfer {
+                            Self::handle_erc1155_log(&balance_call_ctx, &sub, log).await;
+                        } else if is_transfer {
+                            Self::handle_transfer_log(&ctx, Arc::clone(&balance_call_ctx), &sub, log).await;
+                        } else if let Some(underlying) = ctx.wrapped_tokens.get(&log.address()).copied() {
+                            Self::handle_wrapped_log(Arc::clone(&balance_call_ctx), &sub, log, underlying).await;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // contract wallets are recognized by their code, they are handled the same way
+    // the flag is informational, wallet events are routed only to contract owners anyway
+    async fn detect_contract_wallet(ctx: &WatcherContext, sub: &Subscription) {
+        match ctx.provider.get_code_at(ctx.owner).await {
+            Ok(code) if !code.is_empty() => {
+                sub.contract_wallet.store(true, Ordering::SeqCst);
+                counter!("contract_wallets_detected_total").increment(1);
+                tracing::info!(owner = %ctx.owner, network = %ctx.network, "owner is a contract wallet");
+            }
+            Ok(_) => {}
+            Err(err) => {
+                tracing::warn!(
+                    error = %err,
+                    owner = %ctx.owner,
+                    network = %ctx.network,
+                    "unable to get code of the owner"
+                );
+            }
+        }
+    }
+
+    fn is_wallet_log(log: &Log) -> bool {
+        log.topic0().is_some_and(|topic0| {
+            *topic0 == Safe::ExecutionSuccess::SIGNATURE_HASH
+                || *topic0 == Safe::SafeReceived::SIGNATURE_HASH
+                || *topic0 == EntryPoint::UserOperationEvent::SIGNATURE_HASH
+        })
+    }
+
+    // watched tokens emitting logs in the transaction, each once
+    fn touched_tokens(logs: &[Log], watched_tokens: &HashSet<Address>) -> Vec<Address> {
+        let mut tokens: Vec<Address> = logs
+            .iter()
+            .map(|log| log.address())
+            .filter(|token| watched_tokens.contains(token))
+            .collect();
+        tokens.sort();
+        tokens.dedup();
+        tokens
+    }
+
+    // native balance and watched tokens emitting logs in the wallet tx, at the block of the log
+    // without the receipt only the native balance is fetched
+    async fn handle_wallet_log(
+        watcher_ctx: &WatcherContext,
+        ctx: &Arc<BalanceCallCtx>,
+        sub: &Subscription,
+        log: Log,
+    ) {
+        counter!("wallet_events_received_total").increment(1);
+        sub.contract_wallet.store(true, Ordering::SeqCst);
+
+        let Some(block_number) = log.block_number else {
+            tracing::warn!(network = %ctx.network, "block number is undefined");
+            return;
+        };
+
+        let tokens: Vec<Address> = match log.transaction_hash {
+            Some(tx_hash) => match watcher_ctx.provider.get_transaction_receipt(tx_hash).await {
+                Ok(Some(receipt)) => {
+                    let watched_tokens = sub.tokens.read().await;
+                    Self::touched_tokens(receipt.logs(), &watched_tokens)
+                }
+                Ok(None) => vec![],
+                Err(err) => {
+                    counter!("wallet_receipt_failed_total").increment(1);
+                    tracing::warn!(
+                        error = %err,
+                        owner = %ctx.owner,
+                        network = %ctx.network,
+                        tx_hash = %tx_hash,
+                        "unable to get receipt of wallet transaction"
+                    );
+                    vec![]
+                }
+            },
+            None => vec![],
+        };
+
+        // the native balance is always fetched by the multicall
+        Self::refresh_balances_at(ctx, sub, &tokens, block_number).await;
+    }
+
+    fn is_cow_log(log: &Log) -> bool {
+        log.topic0().is_some_and(|topic0| {
+            *topic0 == GPv2Settlement::Trade::SIGNATURE_HASH
+                || *topic0 == CoWSwapEthFlow::OrderPlacement::SIGNATURE_HASH
+        })
+    }
+
+    // the owner's native token leaves via the settlement (or EthFlow) without a Transfer from the owner,
+    // so both tokens of the order and the native balance are fetched at the block of the log
+    async fn handle_cow_log(ctx: &Arc<BalanceCallCtx>, sub: &Subscription, log: Log) {
+        let Some(block_number) = log.block_number else {
+            tracing::warn!(network = %ctx.network, "block number is undefined");
+            return;
+        };
+
+        let Some((event, sell_token, buy_token)) = Self::cow_order_tokens(&log) else {
+            counter!("parse_cow_log_errors_total").increment(1);
+            tracing::error!(
+                network = %ctx.network,
+                owner = %ctx.owner,
+                "error when parse cow log",
+            );
+            return;
+        };
+        counter!("cow_events_received_total", "event" => event).increment(1);
+
+        // the native token (0xEeee...EEeE) is fetched by every multicall
+        let tokens: Vec<Address> = {
+            let watched_tokens = sub.tokens.read().await;
+            let mut tokens = vec![sell_token, buy_token];
+            tokens.dedup();
+            tokens.retain(|token| watched_tokens.contains(token));
+            tokens
+        };
+
+        Self::refresh_balances_at(ctx, sub, &tokens, block_number).await;
+    }
+
+    // event name, sell and buy tokens of the order
+    fn cow_order_tokens(log: &Log) -> Option<(&'static str, Address, Address)> {
+        if let Ok(decoded_log) = log.log_decode::<GPv2Settlement::Trade>() {
+            let trade = decoded_log.inner.data;
+            return Some(("trade", trade.sellToken, trade.buyToken));
+        }
+
+        let order = log
+            .log_decode::<CoWSwapEthFlow::OrderPlacement>()
+            .ok()?
+            .inner
+            .data
+            .order;
+        Some(("order_placement", order.sellToken, order.buyToken))
+    }
+
+    // fetch balances of the tokens (+ native) at the block and send the diff
+    async fn refresh_balances_at(
+        ctx: &Arc<BalanceCallCtx>,
+        sub: &Subscription,
+        tokens: &[Address],
+        block_number: u64,
+    ) {
+        let event =
+            match Self::get_tokens_balance(Arc::clone(ctx), tokens, BlockId::from(block_number))
+                .await
+            {
+                Ok(balances) => {
+                    counter!("partial_snapshot_updater_runs_total").increment(1);
+                    let diff =
+                        Self::update_watched_balances_and_take_diff(sub, ctx.network, balances)
+                            .await;
+
+                    (!diff.is_empty()).then_some(BalanceEvent::BalanceUpdate(diff))
+                }
+                Err(err) => Some(BalanceEvent::Error {
+                    code: 500,
+                    message: err.to_string(),
+                }),
             };
 
-            Arc::new(ctx)
+        if let Some(event) = event {
+            if sub.publish(event) {
+                counter!("balance_updates_sent_total").increment(1);
+            }
+        }
+    }
+
+    // balances of the watched pairs transferred by the log, at the block of the log
+    async fn handle_erc1155_log(ctx: &Arc<BalanceCallCtx>, sub: &Subscription, log: Log) {
+        counter!("erc1155_transfer_events_received_total").increment(1);
+
+        let Some(block_number) = log.block_number else {
+            tracing::warn!(network = %ctx.network, "block number is undefined");
+            return;
+        };
+
+        let ids = if let Ok(decoded_log) = log.log_decode::<ERC1155::TransferSingle>() {
+            vec![decoded_log.inner.data.id]
+        } else if let Ok(decoded_log) = log.log_decode::<ERC1155::TransferBatch>() {
+            decoded_log.inner.data.ids
+        } else {
+            counter!("parse_erc1155_log_errors_total").increment(1);
+            tracing::error!(
+                network = %ctx.network,
+                owner = %ctx.owner,
+                "error when parse erc1155 transfer log",
+            );
+            return;
+        };
+
+        let contract = log.address();
+        let tokens: Vec<Erc1155Token> = {
+            let erc1155_tokens = sub.erc1155_tokens.read().await;
+            let mut tokens: Vec<Erc1155Token> = ids
+                .into_iter()
+                .map(|id| Erc1155Token { contract, id })
+                .filter(|token| erc1155_tokens.contains(token))
+                .collect();
+            tokens.sort();
+            tokens.dedup();
+            tokens
         };
 
-        let ws_provider = self.ctx.ws_provider.clone();
+        Self::update_erc1155_balances(ctx, sub, &tokens, BlockId::from(block_number)).await;
+    }
 
-        tokio::spawn(async move {
-            Self::run_log_subscription_loop(ws_provider, filter, cancel, move |log: Log| {
-                let sub = Arc::clone(&sub);
-                let ctx = Arc::clone(&balance_call_ctx);
+    // add or remove the transferred token ID and take balanceOf at the block of the transfer
+    async fn handle_nft_transfer_log(ctx: &Arc<BalanceCallCtx>, sub: &Subscription, log: Log) {
+        counter!("nft_transfer_events_received_total").increment(1);
 
-                Box::pin(async move {
-                    counter!("weth9_events_received_total").increment(1);
+        let Some(block_number) = log.block_number else {
+            tracing::warn!(network = %ctx.network, "block number is undefined");
+            return;
+        };
 
-                    let event =
-                        match Self::parse_weth9_logs_and_fetch_balance(ctx, &log, weth9_address)
-                            .await
-                        {
-                            Ok(balances) => {
-                                let balance_snapshot = sub.balances_snapshot.write().await;
-                                counter!("partial_snapshot_updater_runs_total").increment(1);
-                                let diff =
-                                    Self::update_balances_and_take_diff(balance_snapshot, balances);
+        let decoded_log: Log<ERC721::Transfer> = match log.log_decode() {
+            Ok(log) => log,
+            Err(err) => {
+                counter!("parse_nft_log_errors_total").increment(1);
+                tracing::error!(
+                    error = %err,
+                    network = %ctx.network,
+                    owner = %ctx.owner,
+                    "error when parse nft transfer log",
+                );
+                return;
+            }
+        };
 
-                                (!diff.is_empty()).then_some(BalanceEvent::BalanceUpdate(diff))
-                            }
-                            Err(err) => Some(BalanceEvent::Error {
-                                code: 500,
-                                message: err.to_string(),
-                            }),
-                        };
+        let collection = decoded_log.address();
+        let (balances, block_number) =
+            match fetch_balances_via_multicall::fetch_erc20_balances_allow_failure(
+                Arc::clone(ctx),
+                &[collection],
+                BlockId::from(block_number),
+            )
+            .await
+            {
+                Ok(balances) => balances,
+                Err(err) => {
+                    tracing::warn!(
+                        error = %err,
+                        collection = %collection,
+                        network = %ctx.network,
+                        "unable to get nft balance"
+                    );
+                    return;
+                }
+            };
+        let Some(balance) = balances.get(&collection).copied() else {
+            return;
+        };
 
-                    if let Some(event) = event {
-                        let _ = sub.sender.send(event).inspect(|_| {
-                            counter!("balance_updates_sent_total").increment(1);
-                        });
-                    }
-                })
-            })
-            .await;
-        });
+        let transfer = decoded_log.inner.data;
+        let mut holding = sub.nft_holding(collection).unwrap_or_default();
+        if transfer.to == ctx.owner {
+            holding.token_ids.insert(transfer.tokenId);
+        } else {
+            holding.token_ids.remove(&transfer.tokenId);
+        }
+        if balance.is_zero() {
+            holding.token_ids.clear();
+        }
+        holding.balance = balance;
+        holding.block_number = block_number;
+
+        Self::publish_nft_holdings(sub, HashMap::from([(collection, holding)]), block_number).await;
     }
 
-    // create a subscription to ws provider and run a loop to listen to logs
-    // if log is received - call on_log callback
-    // if ws provider disconnects - reconnect and continue listening
-    async fn run_log_subscription_loop(
-        ws_provider: DynProvider,
-        filter: Filter,
-        cancel: tokio_util::sync::CancellationToken,
-        mut on_log: impl FnMut(Log) -> BoxFuture<'static, ()> + Send + Sync + 'static,
+    async fn handle_wrapped_log(
+        ctx: Arc<BalanceCallCtx>,
+        sub: &Subscription,
+        log: Log,
+        underlying: Address,
     ) {
-        let mut attempt: u32 = 0;
-
-        loop {
-            tokio::select! {
-                _ = cancel.cancelled() => {
-                    tracing::info!("cancelled log subscription");
-                    break;
-                },
-                _ = async {
-                    match ws_provider.clone().subscribe_logs(&filter).await {
-                        Ok(sub) => {
-                            tracing::info!("subscribed to logs");
-                            attempt = 0;
-
-                            let mut stream = sub.into_stream();
-                            loop {
-                                tokio::select! {
-                                    _ = cancel.cancelled() => {
-                                        tracing::info!("cancelled log subscription");
-                                        break;
-                                    },
-                                    item = stream.next() => {
-                                        match item {
-                                            Some(log) => {
-                                                counter!("events_received_total").increment(1);
-                                                on_log(log).await;
-                                            },
-                                            None => {
-                                                counter!("ws_provider_disconnected_total").increment(1);
-                                                tracing::warn!("ws stream ended (disconnect). will resubscribe");
-                                                break;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        },
-                        Err(err) => {
-                            counter!("ws_subscribe_errors_total").increment(1);
-                            tracing::error!(error = %err, "error to subscribe on logs");
-                        }
-                    }
+        counter!("weth9_events_received_total").increment(1);
+
+        let network = ctx.network;
+        let wrapper = log.address();
+        let event = match Self::parse_wrapped_logs_and_fetch_balance(ctx, &log, wrapper, underlying)
+            .await
+        {
+            Ok(balances) => {
+                counter!("partial_snapshot_updater_runs_total").increment(1);
+                let diff =
+                    Self::update_watched_balances_and_take_diff(sub, network, balances).await;
+
+                (!diff.is_empty()).then_some(BalanceEvent::BalanceUpdate(diff))
+            }
+            Err(err) => Some(BalanceEvent::Error {
+                code: 500,
+                message: err.to_string(),
+            }),
+        };
 
-                    // TODO make it more configurable
-                    let delay = Duration::from_secs(1);
-                    attempt = attempt.saturating_add(1);
-                    tokio::time::sleep(delay).await;
-                    counter!("ws_reconnect_attempts_total").increment(1);
-                } => {}
+        if let Some(event) = event {
+            if sub.publish(event) {
+                counter!("balance_updates_sent_total").increment(1);
             }
         }
     }
@@ -329,12 +1839,14 @@ impl Watcher {
             })
     }
 
-    async fn parse_weth9_logs_and_fetch_balance(
+    // wrapper, underlying and native balances (gas) at the block of the event
+    async fn parse_wrapped_logs_and_fetch_balance(
         ctx: Arc<BalanceCallCtx>,
         log: &Log,
-        weth9_address: Address,
+        wrapper: Address,
+        underlying: Address,
     ) -> Result<BalancesWithBlock, WatcherError> {
-        let parsed_log = Self::parse_weth9_logs(log).map_err(|err| {
+        let parsed_log = Self::parse_wrapped_logs(log).map_err(|err| {
             counter!("parse_weth9_logs_failed_total").increment(1);
             WatcherError::ParseLog(ctx.network, ctx.owner, err.to_string())
         })?;
@@ -346,13 +1858,30 @@ impl Watcher {
         }
         .unwrap_or(BlockId::latest());
 
-        Self::fetch_erc20_and_eth_balance(ctx, weth9_address, block_id).await
+        let native_address = ctx.network.native_token_address();
+        if underlying == native_address {
+            return Self::fetch_erc20_and_eth_balance(ctx, wrapper, block_id).await;
+        }
+
+        let owner = ctx.owner;
+        let network = ctx.network;
+        let tokens = [wrapper, underlying, native_address];
+        fetch_balances_via_multicall::fetch_balances_via_multicall(ctx, &tokens, block_id)
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    error = %err,
+                    network = %network,
+                    "error when get balance for wrapped token: {wrapper}, {underlying}"
+                );
+                WatcherError::GettingBalance(owner, network, err.to_string())
+            })
     }
 
-    // parse WETH logs, search DEPOSIT/WITHDRAWAL events
+    // parse wrapped token logs, search DEPOSIT/WITHDRAWAL events
     // if there is no DEPOSIT/WITHDRAWAL event signature in a log - return Error
     // otherwise return parsed event data
-    fn parse_weth9_logs(log: &Log) -> Result<Option<WethEvents>, ParseWeb3LogsError> {
+    fn parse_wrapped_logs(log: &Log) -> Result<Option<WethEvents>, ParseWeb3LogsError> {
         let topic0 = match log.topic0() {
             Some(topic0) => topic0,
             None => {
@@ -411,104 +1940,110 @@ impl Watcher {
         Err(ParseWeb3LogsError::UnexpectedHashSignature)
     }
 
-    async fn spawn_erc20_transfer_listeners(&self) {
-        let ctx = Arc::clone(&self.ctx);
-        let base = Filter::new().event_signature(ERC20::Transfer::SIGNATURE_HASH);
-        let from = base.clone().topic1(Topic::from(ctx.owner));
-        let to = base.clone().topic2(Topic::from(ctx.owner));
+    async fn handle_transfer_log(
+        watcher_ctx: &WatcherContext,
+        ctx: Arc<BalanceCallCtx>,
+        sub: &Subscription,
+        log: Log,
+    ) {
+        tracing::info!("received erc20 transfer event: {:#?}", log);
+        counter!("erc20_event_received_total").increment(1);
 
-        self.spawn_erc20_transfer_listener_with_filter(from).await;
-        self.spawn_erc20_transfer_listener_with_filter(to).await;
-        self.spawn_weth9_events_listener().await;
-    }
+        let network = ctx.network;
+        let token_balance = Self::parse_transfer_event_and_fetch_balance(ctx, &log).await;
 
-    // listent to erc20 transfer events for owner (in/out)
-    // if an event is received - get balance for token(+ eth balance) and send it to clients
-    async fn spawn_erc20_transfer_listener_with_filter(&self, filter: Filter) {
-        let ctx = Arc::clone(&self.ctx);
-        let sub = Arc::clone(&self.sub);
-        let cancel = sub.cancel_token.clone();
-
-        let balance_call_ctx = {
-            let ctx = BalanceCallCtx {
-                owner: ctx.owner,
-                network: ctx.network,
-                provider: Arc::new(ctx.provider.clone()),
-                multicall3: ctx.multicall3,
-            };
+        if let Some((balances, _)) = &token_balance {
+            Self::discover_token_from_transfer(watcher_ctx, sub, &log, balances).await;
+        }
 
-            Arc::new(ctx)
+        let event = match token_balance {
+            Some(token_balance) => {
+                counter!("partial_snapshot_updater_runs_total").increment(1);
+                let diff =
+                    Self::update_watched_balances_and_take_diff(sub, network, token_balance).await;
+
+                (!diff.is_empty()).then_some(BalanceEvent::BalanceUpdate(diff))
+            }
+            None => Some(BalanceEvent::Error {
+                code: 500,
+                message: "unable to parse erc20 tranfer event".to_string(),
+            }),
         };
 
-        let ws_provider = self.ctx.ws_provider.clone();
+        if let Some(event) = event {
+            if sub.publish(event) {
+                counter!("balance_updates_sent_total").increment(1);
+            }
+        }
+    }
 
-        tokio::spawn(async move {
-            Self::run_log_subscription_loop(ws_provider, filter, cancel, move |log: Log| {
-                let sub = Arc::clone(&sub);
-                let ctx = Arc::clone(&balance_call_ctx);
-
-                tracing::info!("received erc20 transfer event: {:#?}", log);
-                counter!("erc20_event_received_total").increment(1);
-
-                Box::pin(async move {
-                    let token_balance =
-                        Self::parse_transfer_event_and_fetch_balance(Arc::clone(&ctx), &log).await;
-
-                    let event = match token_balance {
-                        Some(token_balance) => {
-                            let balance_snapshot = sub.balances_snapshot.write().await;
-                            counter!("partial_snapshot_updater_runs_total").increment(1);
-                            let diff = Self::update_balances_and_take_diff(
-                                balance_snapshot,
-                                token_balance,
-                            );
+    // drop balances of tokens which are not watched anymore (native balance is always watched)
+    // and update the snapshot with the rest, return diff
+    // tokens lock is held during the update, so removed tokens can't come back to the snapshot
+    // alerts crossed by the update are published right away
+    async fn update_watched_balances_and_take_diff(
+        sub: &Subscription,
+        network: EvmNetwork,
+        (mut balances, block_number): BalancesWithBlock,
+    ) -> HashMap<Address, String> {
+        let tokens = sub.tokens.read().await;
+        let native_address = network.native_token_address();
+        balances.retain(|token, _| *token == native_address || tokens.contains(token));
+
+        *sub.snapshot_updated_at.write().await = Some(Instant::now());
+
+        let balance_snapshot = sub.balances_snapshot.write().await;
+        let (diff, alerts) = {
+            let mut alert_rules = sub
+                .alert_rules
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            Self::update_balances_and_take_diff(
+                balance_snapshot,
+                &mut alert_rules,
+                (balances, block_number),
+            )
+        };
 
-                            (!diff.is_empty()).then_some(BalanceEvent::BalanceUpdate(diff))
-                        }
-                        None => Some(BalanceEvent::Error {
-                            code: 500,
-                            message: "unable to parse erc20 tranfer event".to_string(),
-                        }),
-                    };
-
-                    if let Some(event) = event {
-                        let _ = sub.sender.send(event).inspect(|_| {
-                            counter!("balance_updates_sent_total").increment(1);
-                        });
-                    }
-                })
-            })
-            .await;
-        });
+        for alert in alerts {
+            sub.publish(alert);
+        }
+
+        diff
     }
 
     // update snapshot with new balances
     // first compare block_number, if it is bigger than in snapshot - update it
     // if the balance is different - put it in diff
-    // return diff
+    // alert rules of updated tokens are evaluated with the new balance
+    // return diff and alert events
     fn update_balances_and_take_diff(
         mut snapshot: RwLockWriteGuard<BalanceSnapshot>,
+        alert_rules: &mut [AlertRule],
         (new_balances, block_number): BalancesWithBlock,
-    ) -> HashMap<Address, String> {
+    ) -> (HashMap<Address, String>, Vec<BalanceEvent>) {
         let mut diff: HashMap<Address, String> = HashMap::new();
+        let mut alerts = Vec::new();
         if new_balances.is_empty() {
             tracing::warn!("balances is empty, nothing to update");
-            return diff;
+            return (diff, alerts);
         }
 
         for (address, new_balance) in new_balances {
             let current_balance = snapshot.get_mut(&address);
             if let Some(current_balance) = current_balance {
-                if current_balance.block_number < block_number {
-                    if current_balance.amount != new_balance {
-                        diff.insert(address, new_balance.to_string());
-                    }
+                if current_balance.block_number >= block_number {
+                    continue;
+                }
 
-                    *current_balance = Balance {
-                        amount: new_balance,
-                        block_number,
-                    };
+                if current_balance.amount != new_balance {
+                    diff.insert(address, new_balance.to_string());
                 }
+
+                *current_balance = Balance {
+                    amount: new_balance,
+                    block_number,
+                };
             } else {
                 diff.insert(address, new_balance.to_string());
                 snapshot.insert(
@@ -519,9 +2054,24 @@ impl Watcher {
                     },
                 );
             }
+
+            for rule in alert_rules.iter_mut().filter(|rule| rule.token == address) {
+                let alert = rule.event(new_balance, block_number);
+                match rule.evaluate(new_balance) {
+                    Some(AlertTransition::Triggered) => {
+                        counter!("alerts_triggered_total").increment(1);
+                        alerts.push(BalanceEvent::AlertTriggered(alert));
+                    }
+                    Some(AlertTransition::Resolved) => {
+                        counter!("alerts_resolved_total").increment(1);
+                        alerts.push(BalanceEvent::AlertResolved(alert));
+                    }
+                    None => {}
+                }
+            }
         }
 
-        diff
+        (diff, alerts)
     }
 
     async fn parse_transfer_event_and_fetch_balance(
@@ -555,3 +2105,125 @@ impl Watcher {
             .ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::cow_address::{COW_ETH_FLOW_ADDRESSES, COW_SETTLEMENT_ADDRESS};
+    use crate::evm::cow::{OnchainSignature, OrderData};
+    use alloy::primitives::{address, Bytes, LogData};
+
+    const OWNER: Address = address!("0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045");
+    const USDC: Address = address!("0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48");
+    const DAI: Address = address!("0x6B175474E89094C44Da98b954EedeAC495271d0F");
+    const LINK: Address = address!("0x514910771AF9Ca656af840dff83E8264EcF986CA");
+    const WETH: Address = address!("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+
+    fn log(address: Address, data: LogData) -> Log {
+        Log {
+            inner: alloy::primitives::Log { address, data },
+            ..Default::default()
+        }
+    }
+
+    fn topics_log(address: Address, topics: Vec<B256>) -> Log {
+        log(address, LogData::new_unchecked(topics, Bytes::new()))
+    }
+
+    #[test]
+    fn detects_wallet_logs() {
+        for topic0 in [
+            Safe::ExecutionSuccess::SIGNATURE_HASH,
+            Safe::SafeReceived::SIGNATURE_HASH,
+            EntryPoint::UserOperationEvent::SIGNATURE_HASH,
+        ] {
+            assert!(Watcher::is_wallet_log(&topics_log(OWNER, vec![topic0])));
+        }
+
+        assert!(!Watcher::is_wallet_log(&topics_log(
+            USDC,
+            vec![ERC20::Transfer::SIGNATURE_HASH]
+        )));
+        assert!(!Watcher::is_wallet_log(&topics_log(OWNER, vec![])));
+    }
+
+    #[test]
+    fn touched_tokens_are_watched_and_unique() {
+        let logs = [
+            topics_log(DAI, vec![]),
+            topics_log(USDC, vec![]),
+            topics_log(LINK, vec![]),
+            topics_log(USDC, vec![]),
+        ];
+        let watched_tokens = HashSet::from([USDC, DAI]);
+
+        let mut expected = vec![USDC, DAI];
+        expected.sort();
+        assert_eq!(Watcher::touched_tokens(&logs, &watched_tokens), expected);
+        assert!(Watcher::touched_tokens(&[], &watched_tokens).is_empty());
+    }
+
+    #[test]
+    fn decodes_tokens_of_settlement_trade() {
+        let trade = GPv2Settlement::Trade {
+            owner: OWNER,
+            sellToken: USDC,
+            buyToken: DAI,
+            sellAmount: U256::from(100),
+            buyAmount: U256::from(99),
+            feeAmount: U256::ZERO,
+            orderUid: Bytes::new(),
+        };
+        let trade_log = log(COW_SETTLEMENT_ADDRESS, trade.encode_log_data());
+
+        assert!(Watcher::is_cow_log(&trade_log));
+        assert_eq!(
+            Watcher::cow_order_tokens(&trade_log),
+            Some(("trade", USDC, DAI))
+        );
+    }
+
+    #[test]
+    fn decodes_tokens_of_eth_flow_order() {
+        let placement = CoWSwapEthFlow::OrderPlacement {
+            sender: OWNER,
+            order: OrderData {
+                sellToken: WETH,
+                buyToken: USDC,
+                receiver: OWNER,
+                sellAmount: U256::from(1),
+                buyAmount: U256::from(2_000),
+                validTo: 0,
+                appData: B256::ZERO,
+                feeAmount: U256::ZERO,
+                kind: B256::ZERO,
+                partiallyFillable: false,
+                sellTokenBalance: B256::ZERO,
+                buyTokenBalance: B256::ZERO,
+            },
+            signature: OnchainSignature {
+                scheme: 0,
+                data: Bytes::new(),
+            },
+            data: Bytes::new(),
+        };
+        let placement_log = log(COW_ETH_FLOW_ADDRESSES[0], placement.encode_log_data());
+
+        assert!(Watcher::is_cow_log(&placement_log));
+        assert_eq!(
+            Watcher::cow_order_tokens(&placement_log),
+            Some(("order_placement", WETH, USDC))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_cow_logs() {
+        let truncated = topics_log(
+            COW_SETTLEMENT_ADDRESS,
+            vec![GPv2Settlement::Trade::SIGNATURE_HASH, OWNER.into_word()],
+        );
+
+        assert!(Watcher::is_cow_log(&truncated));
+        assert_eq!(Watcher::cow_order_tokens(&truncated), None);
+    }
+}
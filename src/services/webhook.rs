@@ -0,0 +1,475 @@
+use crate::config::constants::WEBHOOK_QUEUE_CAPACITY;
+use crate::config::webhook_config::{is_public_ip, WebhookConfig};
+use crate::domain::{BalanceEvent, Finality, SubscriptionKey};
+use crate::services::errors::WebhookError;
+use alloy::hex;
+use alloy::primitives::Address;
+use alloy::transports::http::reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use alloy::transports::http::reqwest::{self, header::CONTENT_TYPE, redirect, StatusCode, Url};
+use hmac::{Hmac, Mac};
+use metrics::{counter, gauge, histogram};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio_util::sync::CancellationToken;
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+/// Endpoint receiving events of a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookTarget {
+    pub url: String,
+    /// Key of the HMAC-SHA256 signature of every request
+    pub secret: String,
+    /// Only alert_triggered / alert_resolved events are delivered
+    #[serde(default)]
+    pub alerts_only: bool,
+}
+
+impl WebhookTarget {
+    pub fn new(
+        url: String,
+        secret: String,
+        alerts_only: bool,
+        config: &WebhookConfig,
+    ) -> Result<Self, WebhookError> {
+        let parsed = Url::parse(&url)
+            .map_err(|err| WebhookError::InvalidUrl(url.clone(), err.to_string()))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(WebhookError::InvalidUrl(
+                url,
+                "scheme should be http or https".to_string(),
+            ));
+        }
+        config
+            .check_url(&parsed)
+            .map_err(|err| WebhookError::InvalidUrl(url.clone(), err))?;
+
+        if secret.is_empty() {
+            return Err(WebhookError::EmptySecret);
+        }
+
+        Ok(Self {
+            url,
+            secret,
+            alerts_only,
+        })
+    }
+}
+
+/// Delivery state of a session webhook for the admin API
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookStatus {
+    pub url: String,
+    pub delivered: u64,
+    pub failed: u64,
+    pub retries: u64,
+    pub dropped: u64,
+    pub pending: usize,
+    pub last_status: Option<u16>,
+    pub last_error: Option<String>,
+    pub last_attempt_secs_ago: Option<u64>,
+}
+
+#[derive(Default)]
+struct LastAttempt {
+    status: Option<u16>,
+    error: Option<String>,
+    at: Option<Instant>,
+}
+
+#[derive(Default)]
+struct WebhookStats {
+    delivered: AtomicU64,
+    failed: AtomicU64,
+    retries: AtomicU64,
+    dropped: AtomicU64,
+    last_attempt: Mutex<LastAttempt>,
+}
+
+impl WebhookStats {
+    fn record_attempt(&self, status: Option<u16>, error: Option<String>) {
+        if let Ok(mut last_attempt) = self.last_attempt.lock() {
+            *last_attempt = LastAttempt {
+                status,
+                error,
+                at: Some(Instant::now()),
+            };
+        }
+    }
+}
+
+/// Queue of events of one session, delivered in order by a background task
+pub struct Webhook {
+    target: WebhookTarget,
+    queue: mpsc::Sender<BalanceEvent>,
+    stats: Arc<WebhookStats>,
+}
+
+impl Webhook {
+    pub fn target(&self) -> &WebhookTarget {
+        &self.target
+    }
+
+    // queue is bounded, events are dropped while the endpoint can't keep up
+    pub fn enqueue(&self, event: BalanceEvent) -> bool {
+        if self.target.alerts_only && !event.is_alert() {
+            return false;
+        }
+
+        match self.queue.try_send(event) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                counter!("webhook_events_dropped_total").increment(1);
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    pub fn status(&self) -> WebhookStatus {
+        let (last_status, last_error, last_attempt_secs_ago) = match self.stats.last_attempt.lock()
+        {
+            Ok(last_attempt) => (
+                last_attempt.status,
+                last_attempt.error.clone(),
+                last_attempt.at.map(|at| at.elapsed().as_secs()),
+            ),
+            Err(_) => (None, None, None),
+        };
+
+        WebhookStatus {
+            url: self.target.url.clone(),
+            delivered: self.stats.delivered.load(Ordering::Relaxed),
+            failed: self.stats.failed.load(Ordering::Relaxed),
+            retries: self.stats.retries.load(Ordering::Relaxed),
+            dropped: self.stats.dropped.load(Ordering::Relaxed),
+            pending: self.queue.max_capacity() - self.queue.capacity(),
+            last_status,
+            last_error,
+            last_attempt_secs_ago,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebhookPayload {
+    chain_id: u64,
+    owner: Address,
+    /// Same as the SSE event name
+    event: &'static str,
+    data: serde_json::Value,
+    timestamp: u64,
+}
+
+struct DeliveryError {
+    status: Option<u16>,
+    message: String,
+    retryable: bool,
+}
+
+// HTTP client shared by webhooks of all sessions
+pub struct WebhookClient {
+    client: reqwest::Client,
+    config: WebhookConfig,
+}
+
+// resolved addresses of webhook hosts are checked on every connection,
+// so a public name can't be pointed to an internal address after registration
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+
+            if addrs.is_empty() {
+                counter!("webhook_blocked_total").increment(1);
+                return Err(format!("host {host} has no public address").into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+impl WebhookClient {
+    // redirects are not followed, a public endpoint could redirect to an internal one
+    pub fn new(config: WebhookConfig) -> Result<Self, WebhookError> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .redirect(redirect::Policy::none());
+        if !config.allow_private_hosts {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+
+        let client = builder
+            .build()
+            .map_err(|err| WebhookError::Client(err.to_string()))?;
+
+        Ok(Self { client, config })
+    }
+
+    pub fn session_ttl(&self) -> Duration {
+        self.config.session_ttl
+    }
+
+    // the task stops when the webhook is replaced (queue is closed) or the session is cancelled
+    pub fn spawn(
+        self: &Arc<Self>,
+        key: SubscriptionKey,
+        target: WebhookTarget,
+        cancel: CancellationToken,
+    ) -> Webhook {
+        let (queue, mut receiver) = mpsc::channel::<BalanceEvent>(WEBHOOK_QUEUE_CAPACITY);
+        let stats = Arc::new(WebhookStats::default());
+
+        let client = Arc::clone(self);
+        let task_target = target.clone();
+        let task_stats = Arc::clone(&stats);
+
+        tokio::spawn(async move {
+            gauge!("webhooks_active").increment(1);
+            tracing::info!(sub = %key, url = %task_target.url, "webhook delivery is started");
+
+            loop {
+                let event = tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    event = receiver.recv() => match event {
+                        Some(event) => event,
+                        None => break,
+                    },
+                };
+
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = client.deliver(key, &task_target, &task_stats, event) => {}
+                }
+            }
+
+            gauge!("webhooks_active").decrement(1);
+            tracing::info!(sub = %key, url = %task_target.url, "webhook delivery is stopped");
+        });
+
+        Webhook {
+            target,
+            queue,
+            stats,
+        }
+    }
+
+    // retry with backoff while the error is temporary, the event is dropped after the last attempt
+    async fn deliver(
+        &self,
+        key: SubscriptionKey,
+        target: &WebhookTarget,
+        stats: &WebhookStats,
+        event: BalanceEvent,
+    ) {
+        let (name, data) = event_data(event);
+        let payload = WebhookPayload {
+            chain_id: key.network.chain_id(),
+            owner: key.owner,
+            event: name,
+            data,
+            timestamp: unix_timestamp(),
+        };
+
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::error!(error = %err, sub = %key, "unable to encode webhook payload");
+                return;
+            }
+        };
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+
+            let t0 = Instant::now();
+            let result = self.send(target, &body).await;
+            histogram!("webhook_request_duration_ms").record(t0.elapsed().as_millis() as f64);
+
+            let err = match result {
+                Ok(status) => {
+                    stats.record_attempt(Some(status), None);
+                    stats.delivered.fetch_add(1, Ordering::Relaxed);
+                    counter!("webhook_deliveries_total", "result" => "delivered").increment(1);
+                    return;
+                }
+                Err(err) => err,
+            };
+
+            stats.record_attempt(err.status, Some(err.message.clone()));
+
+            if !err.retryable || attempt >= self.config.max_attempts {
+                stats.failed.fetch_add(1, Ordering::Relaxed);
+                counter!("webhook_deliveries_total", "result" => "failed").increment(1);
+                tracing::warn!(
+                    sub = %key,
+                    url = %target.url,
+                    event = name,
+                    attempt,
+                    error = %err.message,
+                    "webhook delivery failed"
+                );
+                return;
+            }
+
+            stats.retries.fetch_add(1, Ordering::Relaxed);
+            counter!("webhook_retries_total").increment(1);
+            tracing::debug!(
+                sub = %key,
+                url = %target.url,
+                attempt,
+                error = %err.message,
+                "retry webhook delivery"
+            );
+
+            tokio::time::sleep(self.config.retry_delay(attempt)).await;
+        }
+    }
+
+    async fn send(&self, target: &WebhookTarget, body: &[u8]) -> Result<u16, DeliveryError> {
+        // restored and shared sessions keep targets accepted by the previous settings
+        Url::parse(&target.url)
+            .map_err(|err| err.to_string())
+            .and_then(|url| self.config.check_url(&url))
+            .map_err(|message| DeliveryError {
+                status: None,
+                message,
+                retryable: false,
+            })?;
+
+        let timestamp = unix_timestamp().to_string();
+
+        let response = self
+            .client
+            .post(&target.url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, &timestamp)
+            .header(SIGNATURE_HEADER, sign(&target.secret, &timestamp, body))
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|err| DeliveryError {
+                status: None,
+                message: err.to_string(),
+                retryable: true,
+            })?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(status.as_u16());
+        }
+
+        // client errors won't be fixed by a retry, except rate limiting and timeouts
+        let retryable = status.is_server_error()
+            || status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::REQUEST_TIMEOUT;
+
+        Err(DeliveryError {
+            status: Some(status.as_u16()),
+            message: format!("endpoint responded with {status}"),
+            retryable,
+        })
+    }
+}
+
+// `sha256=<hex hmac>` of `<timestamp>.<body>`, the timestamp protects from replays
+fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        // hmac accepts keys of any length
+        return String::new();
+    };
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+// event name and data as in the SSE stream
+fn event_data(event: BalanceEvent) -> (&'static str, serde_json::Value) {
+    match event {
+        BalanceEvent::BalanceUpdate(balances) => (
+            "balance_update",
+            json!({ "balances": balances, "finality": Finality::Latest }),
+        ),
+        BalanceEvent::Snapshot {
+            balances,
+            valuation,
+        } => (
+            "balance_update",
+            json!({ "balances": balances, "finality": Finality::Latest, "valuation": valuation }),
+        ),
+        BalanceEvent::ConfirmedUpdate {
+            finality,
+            balances,
+            block_number,
+        } => (
+            "balance_update",
+            json!({ "balances": balances, "finality": finality, "blockNumber": block_number }),
+        ),
+        BalanceEvent::TokensRemoved(tokens) => ("tokens_removed", json!({ "tokens": tokens })),
+        BalanceEvent::Error { code, message } => {
+            ("error", json!({ "code": code, "message": message }))
+        }
+        BalanceEvent::Degraded { message } => ("degraded", json!({ "message": message })),
+        BalanceEvent::Recovered { message } => ("recovered", json!({ "message": message })),
+        BalanceEvent::AlertTriggered(alert) => ("alert_triggered", json!(alert)),
+        BalanceEvent::AlertResolved(alert) => ("alert_resolved", json!(alert)),
+        BalanceEvent::PriceUpdate(valuation) => ("price_update", json!(valuation)),
+        BalanceEvent::SharesUpdate(share_balances) => {
+            ("shares_update", json!({ "balances": share_balances }))
+        }
+        BalanceEvent::NftUpdate(update) => ("nft_update", json!(update)),
+        BalanceEvent::Erc1155Update(update) => ("erc1155_update", json!(update)),
+        BalanceEvent::PendingBalance(pending) => ("pending_balance", json!(pending)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        let body = br#"{"event":"balance_update"}"#;
+        assert_eq!(
+            sign("secret", "1700000000", body),
+            "sha256=fcd6906f44abd37a3885732aa3d49739610de979800522a77ff753d6c331514d"
+        );
+        assert_ne!(
+            sign("secret", "1700000001", body),
+            sign("secret", "1700000000", body)
+        );
+        assert_ne!(
+            sign("other", "1700000000", body),
+            sign("secret", "1700000000", body)
+        );
+    }
+}